@@ -0,0 +1,172 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::websocket;
+
+pub const DEFAULT_PORT: u16 = 8788;
+
+const OVERLAY_PAGE: &str = include_str!("../resource/overlay.html");
+
+// What `GET /state` returns. Board rows go top to bottom, `#` for settled cells, `@` for the
+// falling piece and `.` for empty ones.
+#[derive(Serialize)]
+pub struct LiveState {
+    pub state: &'static str,
+    pub mode: &'static str,
+    pub score: u32,
+    pub combo: u32,
+    pub level: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub keys: u32,
+    pub clears: [u32; 4],
+    pub max_drought: u32,
+    pub play_time_ms: u64,
+    pub board: Vec<String>,
+}
+
+enum Broadcast {
+    Client(TcpStream),
+    Message(String),
+}
+
+// Read-only view of the running game for dashboards and stream widgets, only listening on
+// localhost. The game publishes a fresh snapshot every frame; `GET /state` answers with the
+// latest one, and `/live` is a WebSocket that gets the full state on connect, then only the
+// fields that changed plus game events. Sockets are written from their own thread, so slow
+// clients never hold up the game.
+pub struct ApiServer {
+    snapshot: Arc<Mutex<String>>,
+    last: Map<String, Value>,
+    broadcast: Sender<Broadcast>,
+}
+
+impl ApiServer {
+    pub fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let snapshot = Arc::new(Mutex::new("{}".to_string()));
+        let (broadcast, receiver) = mpsc::channel();
+
+        let shared = Arc::clone(&snapshot);
+        thread::spawn(move || run_broadcast(receiver, &shared));
+        let shared = Arc::clone(&snapshot);
+        let clients = broadcast.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = respond(stream, &shared, &clients) {
+                    eprintln!("api request failed: {}", e);
+                }
+            }
+        });
+        Ok(ApiServer {
+            snapshot,
+            last: Map::new(),
+            broadcast,
+        })
+    }
+
+    pub fn publish(&mut self, state: &LiveState) {
+        let Ok(Value::Object(fields)) = serde_json::to_value(state) else {
+            return;
+        };
+        let changed: Map<String, Value> = fields
+            .iter()
+            .filter(|(name, value)| self.last.get(*name) != Some(value))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        if changed.is_empty() {
+            return;
+        }
+        *self.snapshot.lock().unwrap() = Value::Object(fields.clone()).to_string();
+        self.send(json!({ "type": "state", "state": changed }));
+        self.last = fields;
+    }
+
+    // Something that happened in the game, like lines being cleared. `data` is merged into the
+    // message next to the event name.
+    pub fn event(&self, name: &str, data: Value) {
+        let mut message = json!({ "type": "event", "event": name });
+        if let (Value::Object(message), Value::Object(data)) = (&mut message, data) {
+            message.extend(data);
+        }
+        self.send(message);
+    }
+
+    fn send(&self, message: Value) {
+        let _ = self.broadcast.send(Broadcast::Message(message.to_string()));
+    }
+}
+
+fn run_broadcast(receiver: Receiver<Broadcast>, snapshot: &Mutex<String>) {
+    let mut clients: Vec<TcpStream> = Vec::new();
+    for broadcast in receiver {
+        match broadcast {
+            Broadcast::Client(mut stream) => {
+                let state = format!(r#"{{"type":"state","state":{}}}"#, snapshot.lock().unwrap());
+                if stream.write_all(&websocket::text_frame(&state)).is_ok() {
+                    clients.push(stream);
+                }
+            }
+            Broadcast::Message(message) => {
+                let frame = websocket::text_frame(&message);
+                clients.retain_mut(|stream| stream.write_all(&frame).is_ok());
+            }
+        }
+    }
+}
+
+fn respond(stream: TcpStream, snapshot: &Mutex<String>, clients: &Sender<Broadcast>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut websocket_key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                websocket_key = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let mut words = request_line.split_whitespace();
+    let (status, content_type, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/live")) => {
+            let Some(key) = websocket_key else {
+                return write_response(reader.into_inner(), "426 Upgrade Required", "text/plain", "websocket only");
+            };
+            let mut stream = reader.into_inner();
+            stream.write_all(websocket::handshake_response(&key).as_bytes())?;
+            let _ = clients.send(Broadcast::Client(stream));
+            return Ok(());
+        }
+        (Some("GET"), Some("/state")) => ("200 OK", "application/json", snapshot.lock().unwrap().clone()),
+        (Some("GET"), Some("/overlay")) => ("200 OK", "text/html; charset=utf-8", OVERLAY_PAGE.to_string()),
+        (Some("GET"), _) => ("404 Not Found", "application/json", r#"{"error":"not found"}"#.to_string()),
+        _ => ("405 Method Not Allowed", "application/json", r#"{"error":"read-only"}"#.to_string()),
+    };
+    write_response(reader.into_inner(), status, content_type, &body)
+}
+
+fn write_response(mut stream: TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// Reads asset files on a background thread so the window opens straight away. Only the raw
+// bytes are loaded here: turning them into sounds and images needs the context, which stays on
+// the main thread.
+pub struct AssetLoader {
+    receiver: Receiver<(String, io::Result<Vec<u8>>)>,
+    total: usize,
+    done: usize,
+}
+
+impl AssetLoader {
+    // `paths` are resource paths like "/death.ogg", looked up in `dirs` in order, the same way
+    // ggez's own filesystem resolves them.
+    pub fn start(dirs: Vec<PathBuf>, paths: Vec<String>) -> Self {
+        let total = paths.len();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for path in paths {
+                let bytes = read(&dirs, &path);
+                if sender.send((path, bytes)).is_err() {
+                    return;
+                }
+            }
+        });
+        AssetLoader {
+            receiver,
+            total,
+            done: 0,
+        }
+    }
+
+    // Files read since the last call.
+    pub fn poll(&mut self) -> Vec<(String, io::Result<Vec<u8>>)> {
+        let loaded: Vec<_> = self.receiver.try_iter().collect();
+        self.done += loaded.len();
+        loaded
+    }
+
+    pub fn finished(&self) -> bool {
+        self.done >= self.total
+    }
+
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f32 / self.total as f32
+        }
+    }
+}
+
+fn read(dirs: &[PathBuf], path: &str) -> io::Result<Vec<u8>> {
+    let relative = path.trim_start_matches('/');
+    for dir in dirs {
+        match fs::read(dir.join(relative)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            result => return result,
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path)))
+}
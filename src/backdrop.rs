@@ -0,0 +1,141 @@
+use ggez::graphics::{Canvas, Color, DrawParam, Image, InstanceArray, Rect};
+use ggez::Context;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+use crate::{ui, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Dropped into the resource directory to be used as the "imagem" backdrop, unless the theme
+// brings its own.
+const IMAGE_PATH: &str = "/backdrop.png";
+const STARS: usize = 150;
+// Horizontal bands the gradient is drawn in; few enough to be cheap, enough not to look
+// stepped at low brightness.
+const BANDS: usize = 40;
+
+// What's drawn behind the playfield during a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackdropKind {
+    // Just the background color from the shop.
+    #[default]
+    Plain,
+    // resource/backdrop.png, scaled to cover the screen.
+    Image,
+    // The theme's two colors, dimmed, in a gradient that slowly scrolls down.
+    Gradient,
+    // Stars drifting down, the nearer ones faster and brighter.
+    Starfield,
+}
+
+impl BackdropKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BackdropKind::Plain => "liso",
+            BackdropKind::Image => "imagem",
+            BackdropKind::Gradient => "gradiente",
+            BackdropKind::Starfield => "estrelas",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            BackdropKind::Plain => BackdropKind::Image,
+            BackdropKind::Image => BackdropKind::Gradient,
+            BackdropKind::Gradient => BackdropKind::Starfield,
+            BackdropKind::Starfield => BackdropKind::Plain,
+        }
+    }
+}
+
+struct Star {
+    pos: [f32; 2],
+    // 0 is far away, 1 is close.
+    depth: f32,
+}
+
+// The backdrop layer. It's purely cosmetic, so it animates on wall-clock time and its own
+// randomness rather than the game's.
+pub struct Backdrop {
+    image: Option<Image>,
+    stars: Vec<Star>,
+    batch: RefCell<InstanceArray>,
+}
+
+impl Backdrop {
+    pub fn new(ctx: &Context) -> Self {
+        let image = load_image(ctx, IMAGE_PATH);
+        let mut rng = rand::thread_rng();
+        let stars = (0..STARS)
+            .map(|_| Star {
+                pos: [rng.gen_range(0.0..SCREEN_WIDTH), rng.gen_range(0.0..SCREEN_HEIGHT)],
+                depth: rng.gen_range(0.0f32..1.0).powi(2),
+            })
+            .collect();
+        Backdrop {
+            image,
+            stars,
+            batch: RefCell::new(InstanceArray::new(ctx, None)),
+        }
+    }
+
+    // The image for the "imagem" backdrop: the theme's at `path`, or the default one for None.
+    pub fn set_image(&mut self, ctx: &Context, path: Option<&str>) {
+        self.image = load_image(ctx, path.unwrap_or(IMAGE_PATH));
+    }
+
+    // `brightness` is 0-1, `secs` the time the animation is at, and `palette` the theme's
+    // colors for the gradient.
+    pub fn draw(&self, canvas: &mut Canvas, kind: BackdropKind, brightness: f32, secs: f32, palette: [Color; 2]) {
+        match kind {
+            BackdropKind::Plain => {}
+            BackdropKind::Image => {
+                if let Some(image) = &self.image {
+                    let (w, h) = (image.width() as f32, image.height() as f32);
+                    let scale = (SCREEN_WIDTH / w).max(SCREEN_HEIGHT / h);
+                    let dest = [(SCREEN_WIDTH - w * scale) / 2.0, (SCREEN_HEIGHT - h * scale) / 2.0];
+                    let tint = Color::new(brightness, brightness, brightness, 1.0);
+                    canvas.draw(image, DrawParam::default().dest(dest).scale([scale, scale]).color(tint));
+                }
+            }
+            BackdropKind::Gradient => {
+                let band = SCREEN_HEIGHT / BANDS as f32;
+                for i in 0..BANDS {
+                    let phase = ((i as f32 + 0.5) / BANDS as f32 - secs * 0.05).rem_euclid(1.0);
+                    // A triangle wave, so the colors run into each other and back with no
+                    // seam as the gradient scrolls.
+                    let along = 1.0 - (2.0 * phase - 1.0).abs();
+                    let color = dim(mix(palette[0], palette[1], along), brightness * 0.5);
+                    ui::fill_rect(canvas, Rect::new(0.0, i as f32 * band, SCREEN_WIDTH, band + 1.0), color);
+                }
+            }
+            BackdropKind::Starfield => {
+                let mut batch = self.batch.borrow_mut();
+                batch.clear();
+                for star in &self.stars {
+                    let y = (star.pos[1] + secs * (10.0 + 60.0 * star.depth)) % SCREEN_HEIGHT;
+                    let size = 1.0 + 2.0 * star.depth;
+                    let color = dim(Color::WHITE, brightness * (0.3 + 0.7 * star.depth));
+                    batch.push(DrawParam::default().dest_rect(Rect::new(star.pos[0], y, size, size)).color(color));
+                }
+                canvas.draw(&*batch, DrawParam::default());
+            }
+        }
+    }
+}
+
+fn load_image(ctx: &Context, path: &str) -> Option<Image> {
+    if !ctx.fs.exists(path) {
+        return None;
+    }
+    Image::from_path(ctx, path).map_err(|e| eprintln!("failed to load backdrop {}: {}", path, e)).ok()
+}
+
+fn mix(a: Color, b: Color, t: f32) -> Color {
+    Color::new(a.r + (b.r - a.r) * t, a.g + (b.g - a.g) * t, a.b + (b.b - a.b) * t, 1.0)
+}
+
+fn dim(color: Color, by: f32) -> Color {
+    Color::new(color.r * by, color.g * by, color.b * by, color.a)
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+// The simulation only ever advances in whole ticks and keeps all of its timers in tick counts,
+// so the same inputs on the same ticks play out identically on every platform and frame rate.
+pub const TICKS_PER_SEC: u64 = 60;
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / TICKS_PER_SEC);
+// After a hitch (window dragged, breakpoint) catch up at most this much real time.
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+// Rounds up, so a non-zero delay never collapses to zero ticks.
+pub fn ticks_from_ms(ms: u64) -> u64 {
+    (ms * TICKS_PER_SEC).div_ceil(1000)
+}
+
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_millis(ticks * 1000 / TICKS_PER_SEC)
+}
+
+// Turns real frame times into a number of ticks to run. Only this pacing looks at real time.
+#[derive(Default)]
+pub struct FixedStep {
+    budget: Duration,
+}
+
+impl FixedStep {
+    pub fn ticks_due(&mut self, dt: Duration, speed: f32) -> u64 {
+        self.budget += dt.min(MAX_CATCH_UP).mul_f32(speed);
+        let ticks = (self.budget.as_nanos() / TICK.as_nanos()) as u64;
+        self.budget -= TICK * ticks as u32;
+        ticks
+    }
+
+    pub fn clear(&mut self) {
+        self.budget = Duration::ZERO;
+    }
+
+    // How far into the next tick real time already is, from 0 to 1, for drawing between ticks.
+    pub fn between_ticks(&self) -> f32 {
+        self.budget.as_secs_f32() / TICK.as_secs_f32()
+    }
+}
+
+// Caps how often frames are drawn by sleeping off what's left of each one. The simulation
+// doesn't care, since `FixedStep` runs however many ticks the longer frames add up to.
+pub struct FrameLimiter {
+    next: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new() -> Self {
+        FrameLimiter { next: Instant::now() }
+    }
+
+    // Call once a frame; `fps` of 0 leaves frames uncapped.
+    pub fn wait(&mut self, fps: u32) {
+        let now = Instant::now();
+        if fps == 0 {
+            self.next = now;
+            return;
+        }
+        if self.next > now {
+            std::thread::sleep(self.next - now);
+        }
+        // A frame that ran long starts the schedule over instead of rushing to catch up.
+        self.next = self.next.max(now) + Duration::from_secs(1) / fps;
+    }
+}
@@ -0,0 +1,896 @@
+use ggez::graphics::Color;
+use ggez::input::keyboard::KeyCode;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use crate::backdrop::BackdropKind;
+use crate::hold;
+use crate::piece::PieceKind;
+use crate::randomizer::RandomizerKind;
+use crate::rotation::RotationKind;
+use crate::speed::{LevelGoal, SpeedCurve};
+use crate::stats::GameMode;
+use crate::{storage, PINK, YELLOW};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    // Straight to the floor without locking.
+    SonicDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+    // The second hold slot, when the rules have one.
+    Hold2,
+    Pause,
+    Restart,
+    // Takes back the last placement, in sandbox only.
+    Undo,
+}
+
+impl Action {
+    pub const ALL: [Action; 13] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::SonicDrop,
+        Action::RotateCw,
+        Action::RotateCcw,
+        Action::Rotate180,
+        Action::Hold,
+        Action::Hold2,
+        Action::Pause,
+        Action::Restart,
+        Action::Undo,
+    ];
+
+    // Its key in settings.toml.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::SonicDrop => "sonic_drop",
+            Action::RotateCw => "rotate_cw",
+            Action::RotateCcw => "rotate_ccw",
+            Action::Rotate180 => "rotate_180",
+            Action::Hold => "hold",
+            Action::Hold2 => "hold_2",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+            Action::Undo => "undo",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Esquerda",
+            Action::MoveRight => "Direita",
+            Action::SoftDrop => "Descer",
+            Action::HardDrop => "Largar",
+            Action::SonicDrop => "Descida sônica",
+            Action::RotateCw => "Girar horário",
+            Action::RotateCcw => "Girar anti-horário",
+            Action::Rotate180 => "Girar 180°",
+            Action::Hold => "Guardar",
+            Action::Hold2 => "Guardar 2",
+            Action::Pause => "Pausar",
+            Action::Restart => "Recomeçar",
+            Action::Undo => "Desfazer",
+        }
+    }
+
+    // Clockwise quarter turns for the rotation actions, 0 for everything else.
+    pub fn turns(self) -> usize {
+        match self {
+            Action::RotateCw => 1,
+            Action::Rotate180 => 2,
+            Action::RotateCcw => 3,
+            _ => 0,
+        }
+    }
+
+    // The hold slot for the hold actions.
+    pub fn hold_slot(self) -> Option<usize> {
+        match self {
+            Action::Hold => Some(0),
+            Action::Hold2 => Some(1),
+            _ => None,
+        }
+    }
+}
+
+// What buys a grounded piece more time before it locks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockPolicy {
+    // Every move or rotation restarts the lock delay.
+    Infinite,
+    // Like `Infinite`, but only `MOVE_RESETS` times until the piece reaches a new lowest row.
+    #[default]
+    MoveReset,
+    // Only falling to a new row restarts it.
+    StepReset,
+}
+
+impl LockPolicy {
+    pub const MOVE_RESETS: u32 = 15;
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LockPolicy::Infinite => "infinito",
+            LockPolicy::MoveReset => "15 movimentos",
+            LockPolicy::StepReset => "só ao descer",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            LockPolicy::Infinite => LockPolicy::MoveReset,
+            LockPolicy::MoveReset => LockPolicy::StepReset,
+            LockPolicy::StepReset => LockPolicy::Infinite,
+        }
+    }
+}
+
+// What the blocks above a clear do once its rows are gone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClearGravity {
+    // Everything above shifts down a row per line cleared, holes and overhangs included.
+    #[default]
+    Naive,
+    // Each group of connected blocks falls on its own until it lands, and can clear more rows.
+    Sticky,
+}
+
+impl ClearGravity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ClearGravity::Naive => "simples",
+            ClearGravity::Sticky => "grudenta",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ClearGravity::Naive => ClearGravity::Sticky,
+            ClearGravity::Sticky => ClearGravity::Naive,
+        }
+    }
+}
+
+// What color pieces are drawn in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PieceColors {
+    // The color each piece was dealt from the theme's palette.
+    #[default]
+    Theme,
+    // The guideline's color for each of the seven pieces, so an S and a Z look different in
+    // the stack.
+    Standard,
+    // A color per piece picked to stay apart for red-green color blindness (protanopia and
+    // deuteranopia), from the Okabe-Ito palette.
+    RedGreen,
+    // The same for blue-yellow color blindness (tritanopia): the pieces differ mostly in red
+    // against cyan, and in lightness.
+    BlueYellow,
+}
+
+impl PieceColors {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PieceColors::Theme => "tema",
+            PieceColors::Standard => "padrão",
+            PieceColors::RedGreen => "daltonismo vermelho-verde",
+            PieceColors::BlueYellow => "daltonismo azul-amarelo",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            PieceColors::Theme => PieceColors::Standard,
+            PieceColors::Standard => PieceColors::RedGreen,
+            PieceColors::RedGreen => PieceColors::BlueYellow,
+            PieceColors::BlueYellow => PieceColors::Theme,
+        }
+    }
+
+    // The color to draw a cell of `kind` in, given the color it was dealt. Garbage and cells
+    // that have lost track of their piece have no kind, and neither do custom pieces here.
+    pub fn color(&self, kind: Option<PieceKind>, dealt: Color) -> Color {
+        let palette = match self {
+            PieceColors::Theme => return dealt,
+            PieceColors::Standard => &STANDARD_COLORS,
+            PieceColors::RedGreen => &RED_GREEN_COLORS,
+            PieceColors::BlueYellow => &BLUE_YELLOW_COLORS,
+        };
+        kind.and_then(PieceKind::index).map_or(dealt, |index| palette[index])
+    }
+}
+
+// How the ghost, the outline of where the piece would land, is drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GhostStyle {
+    // The skin's ghost tile in the piece's color.
+    #[default]
+    Filled,
+    // Just a line around the landing spot, which hides less of the stack.
+    Outline,
+    Off,
+}
+
+impl GhostStyle {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GhostStyle::Filled => "preenchido",
+            GhostStyle::Outline => "contorno",
+            GhostStyle::Off => "não",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            GhostStyle::Filled => GhostStyle::Outline,
+            GhostStyle::Outline => GhostStyle::Off,
+            GhostStyle::Off => GhostStyle::Filled,
+        }
+    }
+}
+
+// How blocks are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockStyle {
+    // From the block skin bought in the shop, or the theme's.
+    #[default]
+    Textured,
+    // Plain squares of color, whatever the skin.
+    Flat,
+    // Classic shaded blocks: a light edge along the top and left, a dark one along the bottom
+    // and right.
+    Beveled,
+}
+
+impl BlockStyle {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BlockStyle::Textured => "textura",
+            BlockStyle::Flat => "liso",
+            BlockStyle::Beveled => "chanfrado",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            BlockStyle::Textured => BlockStyle::Flat,
+            BlockStyle::Flat => BlockStyle::Beveled,
+            BlockStyle::Beveled => BlockStyle::Textured,
+        }
+    }
+}
+
+// Per-piece palettes, in `PieceKind::ALL` order (I, O, T, L, J, S, Z). The guideline's: cyan,
+// yellow, purple, orange, blue, green and red.
+const STANDARD_COLORS: [Color; 7] = [
+    Color::new(0.0, 0.9, 1.0, 1.0),
+    Color::new(1.0, 0.85, 0.0, 1.0),
+    Color::new(0.65, 0.2, 0.9, 1.0),
+    Color::new(1.0, 0.55, 0.0, 1.0),
+    Color::new(0.15, 0.35, 1.0, 1.0),
+    Color::new(0.2, 0.85, 0.2, 1.0),
+    Color::new(0.95, 0.15, 0.15, 1.0),
+];
+
+// Sky blue, yellow, reddish purple, orange, blue, bluish green and vermilion.
+const RED_GREEN_COLORS: [Color; 7] = [
+    Color::new(0.34, 0.71, 0.91, 1.0),
+    Color::new(0.94, 0.89, 0.26, 1.0),
+    Color::new(0.8, 0.47, 0.65, 1.0),
+    Color::new(0.9, 0.62, 0.0, 1.0),
+    Color::new(0.0, 0.45, 0.7, 1.0),
+    Color::new(0.0, 0.62, 0.45, 1.0),
+    Color::new(0.84, 0.37, 0.0, 1.0),
+];
+
+// Light cyan, pale pink, maroon, red, dark teal, teal and light gray.
+const BLUE_YELLOW_COLORS: [Color; 7] = [
+    Color::new(0.55, 0.85, 1.0, 1.0),
+    Color::new(1.0, 0.7, 0.75, 1.0),
+    Color::new(0.5, 0.0, 0.2, 1.0),
+    Color::new(0.95, 0.25, 0.2, 1.0),
+    Color::new(0.1, 0.3, 0.45, 1.0),
+    Color::new(0.0, 0.6, 0.55, 1.0),
+    Color::new(0.7, 0.7, 0.7, 1.0),
+];
+
+// How fast a held soft drop pulls the piece down, as a multiple of the current gravity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoftDropSpeed {
+    X6,
+    #[default]
+    X20,
+    // Straight to the floor, without locking.
+    Instant,
+}
+
+impl SoftDropSpeed {
+    // None for `Instant`.
+    pub fn factor(&self) -> Option<u64> {
+        match self {
+            SoftDropSpeed::X6 => Some(6),
+            SoftDropSpeed::X20 => Some(20),
+            SoftDropSpeed::Instant => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SoftDropSpeed::X6 => "6x",
+            SoftDropSpeed::X20 => "20x",
+            SoftDropSpeed::Instant => "instantânea",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            SoftDropSpeed::X6 => SoftDropSpeed::X20,
+            SoftDropSpeed::X20 => SoftDropSpeed::Instant,
+            SoftDropSpeed::Instant => SoftDropSpeed::X6,
+        }
+    }
+}
+
+// Which pieces can score spins. T-spins use the three-corner rule; with `AllSpin` the other
+// pieces (except O) also count when they lock somewhere they couldn't move out of.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpinRule {
+    #[default]
+    TSpin,
+    AllSpin,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpinRules {
+    pub classic: SpinRule,
+    pub sprint: SpinRule,
+    pub sandbox: SpinRule,
+}
+
+impl SpinRules {
+    pub fn for_mode(&self, mode: GameMode) -> SpinRule {
+        match mode {
+            GameMode::Classic => self.classic,
+            GameMode::Sprint => self.sprint,
+            GameMode::Sandbox => self.sandbox,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Handling {
+    pub das_ms: u64,
+    pub arr_ms: u64,
+    // How long a piece can sit on the stack before it locks; 0 locks on contact.
+    pub lock_delay_ms: u64,
+    pub lock_policy: LockPolicy,
+    // Entry delay between a lock and the next piece appearing; 0 spawns it right away.
+    pub are_ms: u64,
+    // How long completed rows flash before they're removed; 0 removes them at once.
+    pub line_clear_ms: u64,
+    pub soft_drop: SoftDropSpeed,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das_ms: 167,
+            arr_ms: 33,
+            lock_delay_ms: 500,
+            lock_policy: LockPolicy::default(),
+            are_ms: 0,
+            line_clear_ms: 0,
+            soft_drop: SoftDropSpeed::default(),
+        }
+    }
+}
+
+// Every action can have several keys. In settings.toml each action takes a key or a list of
+// keys; actions left out keep their default keys.
+#[derive(Clone)]
+pub struct Keybinds(HashMap<Action, Vec<KeyCode>>);
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        ControlPreset::Guideline.keybinds()
+    }
+}
+
+// Keyed by `Action::name`, one list of keys per action.
+impl Serialize for Keybinds {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(Action::ALL.len()))?;
+        for action in Action::ALL {
+            map.serialize_entry(action.name(), self.keys(action))?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Keybinds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Keys {
+            One(KeyCode),
+            Many(Vec<KeyCode>),
+        }
+
+        let mut loaded = HashMap::new();
+        for (name, keys) in HashMap::<String, Keys>::deserialize(deserializer)? {
+            let Some(action) = Action::ALL.into_iter().find(|action| action.name() == name) else {
+                continue;
+            };
+            let keys = match keys {
+                Keys::One(key) => vec![key],
+                Keys::Many(keys) => keys,
+            };
+            loaded.insert(action, keys);
+        }
+        // Actions missing from the file (added since it was saved) get their default keys,
+        // minus any the file already gives to something else.
+        let mut keybinds = Keybinds::default();
+        for keys in keybinds.0.values_mut() {
+            keys.retain(|key| !loaded.values().flatten().any(|taken| taken == key));
+        }
+        keybinds.0.extend(loaded);
+        Ok(keybinds)
+    }
+}
+
+impl Keybinds {
+    pub fn action(&self, keycode: KeyCode) -> Option<Action> {
+        Action::ALL.into_iter().find(|&action| self.keys(action).contains(&keycode))
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // "Up / X", or a dash for an action with no keys.
+    pub fn label(&self, action: Action) -> String {
+        let keys: Vec<String> = self.keys(action).iter().map(|key| format!("{:?}", key)).collect();
+        if keys.is_empty() {
+            "—".to_string()
+        } else {
+            keys.join(" / ")
+        }
+    }
+
+    // Adds `keycode` to `action`, taking it away from whatever action had it before so no key
+    // ends up doing two things.
+    pub fn bind(&mut self, action: Action, keycode: KeyCode) {
+        for keys in self.0.values_mut() {
+            keys.retain(|&key| key != keycode);
+        }
+        self.0.entry(action).or_default().push(keycode);
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.0.remove(&action);
+    }
+
+    // Keys bound to moving and dropping double as menu navigation, so a rebound layout works
+    // in menus too.
+    pub fn menu_key(&self, keycode: KeyCode) -> KeyCode {
+        match self.action(keycode) {
+            Some(Action::RotateCw) => KeyCode::Up,
+            Some(Action::SoftDrop) => KeyCode::Down,
+            Some(Action::HardDrop) => KeyCode::Return,
+            _ => keycode,
+        }
+    }
+}
+
+// Ready-made control schemes: keys, auto-shift timing and soft drop speed in one go.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ControlPreset {
+    Guideline,
+    // Arrows only, with NES-like auto-shift and a slow soft drop.
+    Classic,
+    // Moving with WASD, rotating with J, K and L.
+    Wasd,
+    // The guideline layout mirrored: moving on the left hand, rotating on the arrows.
+    LeftHanded,
+}
+
+impl ControlPreset {
+    pub const ALL: [ControlPreset; 4] =
+        [ControlPreset::Guideline, ControlPreset::Classic, ControlPreset::Wasd, ControlPreset::LeftHanded];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ControlPreset::Guideline => "Guideline",
+            ControlPreset::Classic => "Clássico (só setas)",
+            ControlPreset::Wasd => "WASD",
+            ControlPreset::LeftHanded => "Canhoto",
+        }
+    }
+
+    pub fn keybinds(&self) -> Keybinds {
+        let keys = |action| -> Vec<KeyCode> {
+            match (self, action) {
+                (_, Action::Pause) => vec![KeyCode::P],
+                (_, Action::Restart) => vec![KeyCode::R, KeyCode::F4],
+                (_, Action::Undo) => vec![KeyCode::U],
+                (ControlPreset::Guideline, Action::MoveLeft) => vec![KeyCode::Left],
+                (ControlPreset::Guideline, Action::MoveRight) => vec![KeyCode::Right],
+                (ControlPreset::Guideline, Action::SoftDrop) => vec![KeyCode::Down],
+                (ControlPreset::Guideline, Action::HardDrop) => vec![KeyCode::Space],
+                (ControlPreset::Guideline, Action::SonicDrop) => vec![KeyCode::S],
+                (ControlPreset::Guideline, Action::RotateCw) => vec![KeyCode::Up, KeyCode::X],
+                (ControlPreset::Guideline, Action::RotateCcw) => vec![KeyCode::Z, KeyCode::LControl],
+                (ControlPreset::Guideline, Action::Rotate180) => vec![KeyCode::A],
+                (ControlPreset::Guideline, Action::Hold) => vec![KeyCode::C, KeyCode::LShift],
+                (ControlPreset::Guideline, Action::Hold2) => vec![KeyCode::V],
+                (ControlPreset::Classic, Action::MoveLeft) => vec![KeyCode::Left],
+                (ControlPreset::Classic, Action::MoveRight) => vec![KeyCode::Right],
+                (ControlPreset::Classic, Action::SoftDrop) => vec![KeyCode::Down],
+                (ControlPreset::Classic, Action::RotateCw) => vec![KeyCode::Up],
+                (ControlPreset::Classic, _) => vec![],
+                (ControlPreset::Wasd, Action::MoveLeft) => vec![KeyCode::A],
+                (ControlPreset::Wasd, Action::MoveRight) => vec![KeyCode::D],
+                (ControlPreset::Wasd, Action::SoftDrop) => vec![KeyCode::S],
+                (ControlPreset::Wasd, Action::HardDrop) => vec![KeyCode::W],
+                (ControlPreset::Wasd, Action::SonicDrop) => vec![KeyCode::Space],
+                (ControlPreset::Wasd, Action::RotateCw) => vec![KeyCode::K],
+                (ControlPreset::Wasd, Action::RotateCcw) => vec![KeyCode::J],
+                (ControlPreset::Wasd, Action::Rotate180) => vec![KeyCode::L],
+                (ControlPreset::Wasd, Action::Hold) => vec![KeyCode::I],
+                (ControlPreset::Wasd, Action::Hold2) => vec![KeyCode::O],
+                (ControlPreset::LeftHanded, Action::MoveLeft) => vec![KeyCode::A],
+                (ControlPreset::LeftHanded, Action::MoveRight) => vec![KeyCode::D],
+                (ControlPreset::LeftHanded, Action::SoftDrop) => vec![KeyCode::S],
+                (ControlPreset::LeftHanded, Action::HardDrop) => vec![KeyCode::Space],
+                (ControlPreset::LeftHanded, Action::SonicDrop) => vec![KeyCode::W],
+                (ControlPreset::LeftHanded, Action::RotateCw) => vec![KeyCode::Right],
+                (ControlPreset::LeftHanded, Action::RotateCcw) => vec![KeyCode::Left],
+                (ControlPreset::LeftHanded, Action::Rotate180) => vec![KeyCode::Up],
+                (ControlPreset::LeftHanded, Action::Hold) => vec![KeyCode::RShift],
+                (ControlPreset::LeftHanded, Action::Hold2) => vec![KeyCode::RControl],
+            }
+        };
+        Keybinds(Action::ALL.into_iter().map(|action| (action, keys(action))).collect())
+    }
+
+    // Keys, DAS/ARR and soft drop speed; lock and entry delays are left alone.
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.keybinds = self.keybinds();
+        let handling = &mut settings.handling;
+        (handling.das_ms, handling.arr_ms, handling.soft_drop) = match self {
+            ControlPreset::Classic => (267, 100, SoftDropSpeed::X6),
+            ControlPreset::Guideline | ControlPreset::LeftHanded => (167, 33, SoftDropSpeed::X20),
+            ControlPreset::Wasd => (133, 17, SoftDropSpeed::X20),
+        };
+    }
+}
+
+pub const THEMES: [(&str, [Color; 2]); 9] = [
+    ("lollypop", [PINK, YELLOW]),
+    ("menta", [Color::new(0.4, 1.0, 0.7, 1.0), Color::new(0.7, 0.5, 1.0, 1.0)]),
+    ("luto", [Color::new(0.35, 0.35, 0.35, 1.0), Color::new(0.75, 0.75, 0.75, 1.0)]),
+    ("sangue", [Color::new(0.6, 0.0, 0.05, 1.0), Color::new(1.0, 0.15, 0.1, 1.0)]),
+    ("ouro de tolo", [Color::new(0.85, 0.65, 0.1, 1.0), Color::new(0.55, 0.4, 0.05, 1.0)]),
+    ("retrô", [Color::new(0.55, 0.67, 0.06, 1.0), Color::new(0.19, 0.38, 0.19, 1.0)]),
+    ("tetris", [Color::new(0.0, 0.9, 1.0, 1.0), Color::new(0.1, 0.3, 1.0, 1.0)]),
+    ("relâmpago", [Color::new(1.0, 0.95, 0.5, 1.0), Color::new(0.3, 0.8, 1.0, 1.0)]),
+    ("arco-íris", [Color::new(1.0, 0.3, 0.3, 1.0), Color::new(0.3, 0.6, 1.0, 1.0)]),
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub handling: Handling,
+    pub keybinds: Keybinds,
+    pub theme: String,
+    pub skin: String,
+    pub background: String,
+    pub sfx_pack: String,
+    pub auto_pause_secs: u64,
+    pub scare_level: u32,
+    // Silences every sound, scares included; toggled with M or from the settings.
+    pub muted: bool,
+    // In percent, each on its own; the effects volume covers scares too.
+    pub music_volume: u32,
+    pub sfx_volume: u32,
+    // Speeds the music up with the level and when the stack gets high.
+    pub music_ramp: bool,
+    // Brings in extra stems over the music as the stack grows or a combo builds.
+    pub music_layers: bool,
+    // Name of the sound pack, a folder in resource/soundpacks/, to take sounds from; empty for
+    // none. Not to be confused with `sfx_pack`, the pitch bought in the shop.
+    pub sound_folder: String,
+    pub randomizer: RandomizerKind,
+    // Caps I droughts and S/Z floods on top of the randomizer.
+    pub luck: bool,
+    pub bag_preview: bool,
+    // Lines between the playfield's cells, in percent opacity; 0 hides them.
+    pub grid_opacity: u32,
+    // How hard hard drops and incoming garbage shake the screen, in percent; 0 turns it off.
+    pub shake: u32,
+    pub piece_colors: PieceColors,
+    pub block_style: BlockStyle,
+    pub ghost: GhostStyle,
+    // The ghost's opacity, in percent of what the skin draws it at.
+    pub ghost_opacity: u32,
+    // A pattern over each mino that tells the seven pieces apart without color.
+    pub cell_patterns: bool,
+    // Borderless fullscreen on the monitor the window is on.
+    pub fullscreen: bool,
+    // Read when the window opens, so a change waits for the next start.
+    pub vsync: bool,
+    // Most frames drawn per second; 0 leaves it to vsync.
+    pub fps_cap: u32,
+    pub show_fps: bool,
+    // Draws the falling piece between rows as gravity builds up instead of a row at a time.
+    pub smooth_gravity: bool,
+    // Tints and pulses the board while the stack is up near the top.
+    pub danger_warning: bool,
+    // 3-2-1-GO before a run starts and after a pause.
+    pub countdown: bool,
+    // Drawn behind the playfield during a run, at `backdrop_brightness` percent.
+    pub backdrop: BackdropKind,
+    pub backdrop_brightness: u32,
+    pub clear_gravity: ClearGravity,
+    // Some pieces carry a sparkling cell; clearing its row sets off an item.
+    pub items: bool,
+    // Name of the piece set from resource/pieces/ to play with; empty for the standard seven.
+    pub piece_set: String,
+    // Every mino takes up 2x2 cells, on the same board.
+    pub big: bool,
+    // 20G: pieces drop to the stack the moment they spawn or move.
+    pub instant_gravity: bool,
+    pub speed_curve: SpeedCurve,
+    pub level_goal: LevelGoal,
+    // Hold slots, from none to `hold::MAX_SLOTS`.
+    pub hold_slots: usize,
+    // Click or tap a column to slide the piece there, tap the piece to rotate it, swipe down
+    // to drop it.
+    pub pointer_controls: bool,
+    // Left stick: how far it must be pushed to count, and how strongly diagonals lean towards
+    // left/right over down, both in percent.
+    pub stick_deadzone: u32,
+    pub stick_bias: u32,
+    pub rotation: RotationKind,
+    pub spin_rules: SpinRules,
+    pub updated_at: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            handling: Handling::default(),
+            keybinds: Keybinds::default(),
+            theme: THEMES[0].0.to_string(),
+            skin: "liso".to_string(),
+            background: "preto".to_string(),
+            sfx_pack: "padrão".to_string(),
+            auto_pause_secs: 30,
+            scare_level: 100,
+            muted: false,
+            music_volume: 100,
+            sfx_volume: 100,
+            music_ramp: true,
+            music_layers: true,
+            sound_folder: String::new(),
+            randomizer: RandomizerKind::default(),
+            luck: false,
+            bag_preview: false,
+            grid_opacity: 15,
+            shake: 100,
+            piece_colors: PieceColors::default(),
+            block_style: BlockStyle::default(),
+            ghost: GhostStyle::default(),
+            ghost_opacity: 100,
+            cell_patterns: false,
+            fullscreen: false,
+            vsync: true,
+            fps_cap: 0,
+            show_fps: false,
+            smooth_gravity: false,
+            danger_warning: true,
+            countdown: true,
+            backdrop: BackdropKind::default(),
+            backdrop_brightness: 50,
+            clear_gravity: ClearGravity::default(),
+            items: false,
+            piece_set: String::new(),
+            big: false,
+            instant_gravity: false,
+            speed_curve: SpeedCurve::default(),
+            level_goal: LevelGoal::default(),
+            hold_slots: 1,
+            pointer_controls: false,
+            stick_deadzone: 30,
+            stick_bias: 50,
+            rotation: RotationKind::default(),
+            spin_rules: SpinRules::default(),
+            updated_at: 0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Self {
+        storage::load_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::save_toml(self, path)
+    }
+
+    // Bundles are the same TOML as settings.toml, but reading one must fail loudly
+    // instead of silently falling back to defaults.
+    pub fn import(path: &Path) -> io::Result<Self> {
+        storage::read_toml(path)
+    }
+
+    pub fn palette(&self) -> [Color; 2] {
+        THEMES
+            .iter()
+            .find(|(name, _)| *name == self.theme)
+            .map(|(_, palette)| *palette)
+            .unwrap_or(THEMES[0].1)
+    }
+
+    pub fn next_auto_pause(&mut self) {
+        const STEPS: [u64; 4] = [0, 15, 30, 60];
+        let index = STEPS.iter().position(|&s| s == self.auto_pause_secs).unwrap_or(0);
+        self.auto_pause_secs = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn auto_pause_label(&self) -> String {
+        if self.auto_pause_secs == 0 {
+            "Pausa automática: não".to_string()
+        } else {
+            format!("Pausa automática: {} s", self.auto_pause_secs)
+        }
+    }
+
+    pub fn next_grid_opacity(&mut self) {
+        const STEPS: [u32; 5] = [0, 8, 15, 25, 40];
+        let index = STEPS.iter().position(|&s| s == self.grid_opacity).unwrap_or(0);
+        self.grid_opacity = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_ghost_opacity(&mut self) {
+        const STEPS: [u32; 5] = [25, 50, 100, 200, 300];
+        let index = STEPS.iter().position(|&s| s == self.ghost_opacity).unwrap_or(0);
+        self.ghost_opacity = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_line_clear(&mut self) {
+        const STEPS: [u64; 5] = [0, 100, 200, 300, 400];
+        let index = STEPS.iter().position(|&s| s == self.handling.line_clear_ms).unwrap_or(0);
+        self.handling.line_clear_ms = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_shake(&mut self) {
+        const STEPS: [u32; 4] = [0, 50, 100, 200];
+        let index = STEPS.iter().position(|&s| s == self.shake).unwrap_or(0);
+        self.shake = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_fps_cap(&mut self) {
+        const STEPS: [u32; 5] = [0, 30, 60, 120, 144];
+        let index = STEPS.iter().position(|&s| s == self.fps_cap).unwrap_or(0);
+        self.fps_cap = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_backdrop_brightness(&mut self) {
+        const STEPS: [u32; 4] = [25, 50, 75, 100];
+        let index = STEPS.iter().position(|&s| s == self.backdrop_brightness).unwrap_or(0);
+        self.backdrop_brightness = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_hold_slots(&mut self) {
+        self.hold_slots = (self.hold_slots + 1) % (hold::MAX_SLOTS + 1);
+    }
+
+    pub fn next_stick_deadzone(&mut self) {
+        const STEPS: [u32; 5] = [10, 20, 30, 40, 50];
+        let index = STEPS.iter().position(|&s| s == self.stick_deadzone).unwrap_or(0);
+        self.stick_deadzone = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_stick_bias(&mut self) {
+        const STEPS: [u32; 4] = [0, 25, 50, 100];
+        let index = STEPS.iter().position(|&s| s == self.stick_bias).unwrap_or(0);
+        self.stick_bias = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn next_music_volume(&mut self) {
+        self.music_volume = next_volume(self.music_volume);
+    }
+
+    pub fn next_sfx_volume(&mut self) {
+        self.sfx_volume = next_volume(self.sfx_volume);
+    }
+
+    // Scales every scare in scares.toml; 0 disables them.
+    pub fn scare_intensity(&self) -> f32 {
+        self.scare_level as f32 / 100.0
+    }
+
+    pub fn next_scare_level(&mut self) {
+        const STEPS: [u32; 3] = [0, 50, 100];
+        let index = STEPS.iter().position(|&s| s == self.scare_level).unwrap_or(0);
+        self.scare_level = STEPS[(index + 1) % STEPS.len()];
+    }
+
+    pub fn scare_label(&self) -> String {
+        if self.scare_level == 0 {
+            "Sustos: desligados".to_string()
+        } else {
+            format!("Sustos: {}%", self.scare_level)
+        }
+    }
+
+}
+
+// Volumes go up in tens and wrap around to 0.
+fn next_volume(volume: u32) -> u32 {
+    (volume / 10 + 1) % 11 * 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keybinds_round_trip() {
+        let mut keybinds = ControlPreset::Wasd.keybinds();
+        keybinds.bind(Action::Hold, KeyCode::Q);
+        keybinds.clear(Action::Hold2);
+        let text = toml::to_string(&keybinds).unwrap();
+        let loaded: Keybinds = toml::from_str(&text).unwrap();
+        for action in Action::ALL {
+            assert_eq!(loaded.keys(action), keybinds.keys(action), "{}", action.name());
+        }
+    }
+
+    // A file saved before some actions existed, or edited by hand.
+    #[test]
+    fn missing_actions_get_free_default_keys() {
+        let loaded: Keybinds = toml::from_str("hold = \"Space\"\nmove_left = [\"A\", \"Left\"]\nbogus = \"B\"").unwrap();
+        assert_eq!(loaded.keys(Action::Hold), [KeyCode::Space]);
+        assert_eq!(loaded.keys(Action::MoveLeft), [KeyCode::A, KeyCode::Left]);
+        // Space was hard drop's only default key, and the file gave it to hold.
+        assert!(loaded.keys(Action::HardDrop).is_empty());
+        assert_eq!(loaded.keys(Action::RotateCw), [KeyCode::Up, KeyCode::X]);
+        assert_eq!(loaded.keys(Action::Undo), [KeyCode::U]);
+        assert_eq!(loaded.action(KeyCode::Space), Some(Action::Hold));
+    }
+
+    #[test]
+    fn volumes_wrap() {
+        assert_eq!(next_volume(0), 10);
+        assert_eq!(next_volume(95), 100);
+        assert_eq!(next_volume(100), 0);
+    }
+}
@@ -0,0 +1,96 @@
+use ggez::graphics::{Canvas, Color, Rect};
+use ggez::input::keyboard::KeyCode;
+
+use crate::text::{Align, Style, TextCache};
+use crate::{ui, PINK, SCREEN_WIDTH};
+
+const HEIGHT: f32 = 220.0;
+const LOG_LINES: usize = 10;
+
+pub const HELP: &str = "seed <n> - reinicia o sorteio com a semente\n\
+                        spawn <I|O|T|L|J|S|Z> - troca a peça atual\n\
+                        garbage <n> [messy] - sobe n linhas de lixo (messy: buraco muda a cada linha)\n\
+                        level <n> - muda a velocidade\n\
+                        clearboard - limpa o tabuleiro";
+
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    // Index into `history` while browsing it with Up/Down.
+    browsing: Option<usize>,
+    log: Vec<String>,
+}
+
+impl Console {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.browsing = None;
+    }
+
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            self.log.push(line.to_string());
+        }
+        if self.log.len() > LOG_LINES {
+            self.log.drain(..self.log.len() - LOG_LINES);
+        }
+    }
+
+    pub fn text_input(&mut self, character: char) {
+        if character != '`' && !character.is_control() {
+            self.input.push(character);
+        }
+    }
+
+    // Returns the command line when Return is pressed.
+    pub fn key_down(&mut self, keycode: KeyCode) -> Option<String> {
+        match keycode {
+            KeyCode::Return => {
+                let line = std::mem::take(&mut self.input);
+                self.browsing = None;
+                if line.trim().is_empty() {
+                    return None;
+                }
+                self.print(&format!("> {}", line));
+                self.history.push(line.clone());
+                return Some(line);
+            }
+            KeyCode::Back => {
+                self.input.pop();
+            }
+            KeyCode::Escape => self.toggle(),
+            KeyCode::Up if !self.history.is_empty() => {
+                let index = self.browsing.map_or(self.history.len() - 1, |i| i.saturating_sub(1));
+                self.browsing = Some(index);
+                self.input = self.history[index].clone();
+            }
+            KeyCode::Down => {
+                if let Some(index) = self.browsing {
+                    if index + 1 < self.history.len() {
+                        self.browsing = Some(index + 1);
+                        self.input = self.history[index + 1].clone();
+                    } else {
+                        self.browsing = None;
+                        self.input.clear();
+                    }
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, text: &TextCache) {
+        if !self.open {
+            return;
+        }
+        ui::fill_rect(canvas, Rect::new(0.0, 0.0, SCREEN_WIDTH, HEIGHT), Color::new(0.0, 0.0, 0.0, 0.85));
+        for (i, line) in self.log.iter().enumerate() {
+            text.draw(canvas, line, [6.0, 6.0 + i as f32 * 17.0], Style::new(12.0, Color::WHITE, Align::Left));
+        }
+        let prompt = format!("> {}_", self.input);
+        text.draw(canvas, &prompt, [6.0, HEIGHT - 22.0], Style::new(14.0, PINK, Align::Left));
+    }
+}
@@ -0,0 +1,74 @@
+use ggez::event::{Axis, Button};
+use ggez::input::keyboard::KeyCode;
+
+use crate::config::Action;
+
+// Fixed layout: the d-pad moves and drops, face buttons rotate (going by position, so it reads
+// the same on every brand of controller), the shoulder buttons hold, Start pauses and Select
+// restarts.
+pub fn action(button: Button) -> Option<Action> {
+    let action = match button {
+        Button::DPadLeft => Action::MoveLeft,
+        Button::DPadRight => Action::MoveRight,
+        Button::DPadDown => Action::SoftDrop,
+        Button::DPadUp => Action::HardDrop,
+        Button::East => Action::RotateCw,
+        Button::South => Action::RotateCcw,
+        Button::North => Action::Rotate180,
+        Button::West => Action::SonicDrop,
+        Button::LeftTrigger => Action::Hold,
+        Button::RightTrigger => Action::Hold2,
+        Button::Start => Action::Pause,
+        Button::Select => Action::Restart,
+        _ => return None,
+    };
+    Some(action)
+}
+
+// The key a button stands for on menu screens.
+pub fn menu_key(button: Button) -> Option<KeyCode> {
+    let key = match button {
+        Button::DPadUp => KeyCode::Up,
+        Button::DPadDown => KeyCode::Down,
+        Button::DPadLeft => KeyCode::Left,
+        Button::DPadRight => KeyCode::Right,
+        Button::South => KeyCode::Return,
+        Button::East | Button::Start => KeyCode::Escape,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// The left stick read as a d-pad. Inside the deadzone it's centered. Past it, it points along
+// whichever axis is further out, except that `bias` favors left and right: with a bias of 0.5
+// the stick must be pushed half again as far down as sideways to count as down, so a
+// slightly low sideways push shifts the piece instead of soft dropping it.
+#[derive(Default)]
+pub struct Stick {
+    x: f32,
+    y: f32,
+    direction: Option<Button>,
+}
+
+impl Stick {
+    // The d-pad buttons to release and press when the stick moves into a new direction.
+    pub fn update(&mut self, axis: Axis, value: f32, deadzone: f32, bias: f32) -> Option<(Option<Button>, Option<Button>)> {
+        match axis {
+            Axis::LeftStickX => self.x = value,
+            Axis::LeftStickY => self.y = value,
+            _ => return None,
+        }
+        let direction = if self.x.hypot(self.y) < deadzone {
+            None
+        } else if self.x.abs() * (1.0 + bias) >= self.y.abs() {
+            Some(if self.x < 0.0 { Button::DPadLeft } else { Button::DPadRight })
+        } else {
+            // Up is positive on gilrs sticks.
+            Some(if self.y < 0.0 { Button::DPadDown } else { Button::DPadUp })
+        };
+        if direction == self.direction {
+            return None;
+        }
+        Some((std::mem::replace(&mut self.direction, direction), direction))
+    }
+}
@@ -0,0 +1,95 @@
+use ggez::graphics::{Canvas, Color, Rect};
+
+use crate::layout::Region;
+use crate::replay::Replay;
+use crate::text::{Align, Style, TextCache};
+use crate::{ui, BUFFER_ROWS, CELL_SIZE, GRID_HEIGHT, GRID_WIDTH, PINK, YELLOW};
+
+pub struct Heatmap {
+    counts: [[u32; GRID_WIDTH]; GRID_HEIGHT],
+    pub games: usize,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Heatmap {
+            counts: [[0; GRID_WIDTH]; GRID_HEIGHT],
+            games: 0,
+        }
+    }
+
+    pub fn add_replay(&mut self, replay: &Replay) {
+        for placement in &replay.placements {
+            for (y, row) in placement.shape.iter().enumerate() {
+                for (x, &cell) in row.iter().enumerate() {
+                    let grid_x = placement.x + x as i32;
+                    let grid_y = placement.y + y as i32;
+                    if cell
+                        && (0..GRID_WIDTH as i32).contains(&grid_x)
+                        && (0..GRID_HEIGHT as i32).contains(&grid_y)
+                    {
+                        self.counts[grid_y as usize][grid_x as usize] += 1;
+                    }
+                }
+            }
+        }
+        self.games += 1;
+    }
+
+    fn column_shares(&self) -> [f32; GRID_WIDTH] {
+        let mut columns = [0u32; GRID_WIDTH];
+        for row in &self.counts {
+            for (x, &count) in row.iter().enumerate() {
+                columns[x] += count;
+            }
+        }
+        let total: u32 = columns.iter().sum();
+        let mut shares = [0.0; GRID_WIDTH];
+        if total > 0 {
+            for (share, &count) in shares.iter_mut().zip(&columns) {
+                *share = count as f32 / total as f32;
+            }
+        }
+        shares
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, text: &TextCache) {
+        let board = Region::Board.rect();
+        let max = self.counts.iter().flatten().copied().max().unwrap_or(0);
+        if max > 0 {
+            for (y, row) in self.counts[BUFFER_ROWS..].iter().enumerate() {
+                for (x, &count) in row.iter().enumerate() {
+                    if count == 0 {
+                        continue;
+                    }
+                    let rect = Rect::new(board.x + x as f32 * CELL_SIZE, board.y + y as f32 * CELL_SIZE, CELL_SIZE, CELL_SIZE);
+                    let color = heat_color(count as f32 / max as f32);
+                    ui::fill_rect(canvas, rect, color);
+                }
+            }
+        }
+
+        for (x, share) in self.column_shares().iter().enumerate() {
+            let share = format!("{:.0}%", share * 100.0);
+            let style = Style::new(12.0, Color::WHITE, Align::Center);
+            text.draw(canvas, &share, [board.x + x as f32 * CELL_SIZE + CELL_SIZE / 2.0, 70.0], style);
+        }
+    }
+}
+
+// Cold cells fade in from black to pink, the hottest ones turn yellow.
+fn heat_color(t: f32) -> Color {
+    let lerp = |a: Color, b: Color, t: f32| {
+        Color::new(
+            a.r + (b.r - a.r) * t,
+            a.g + (b.g - a.g) * t,
+            a.b + (b.b - a.b) * t,
+            1.0,
+        )
+    };
+    if t < 0.5 {
+        lerp(Color::new(0.15, 0.0, 0.1, 1.0), PINK, t * 2.0)
+    } else {
+        lerp(PINK, YELLOW, (t - 0.5) * 2.0)
+    }
+}
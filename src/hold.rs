@@ -0,0 +1,75 @@
+// The most hold slots the rules allow. One is the usual hold; two is the community variant
+// where a piece can go into either.
+pub const MAX_SLOTS: usize = 2;
+
+// Pieces set aside to swap back in later, one per slot. Each slot can be swapped once per
+// piece that locks, and the slots keep separate counts: after holding into one, the piece
+// that comes out can still go into the other.
+#[derive(Clone)]
+pub struct Hold<T> {
+    slots: Vec<Slot<T>>,
+}
+
+#[derive(Clone)]
+struct Slot<T> {
+    piece: Option<T>,
+    used: bool,
+}
+
+impl<T> Hold<T> {
+    pub fn new(slots: usize) -> Self {
+        Hold {
+            slots: (0..slots.min(MAX_SLOTS)).map(|_| Slot { piece: None, used: false }).collect(),
+        }
+    }
+
+    // Whether `slot` exists and hasn't been swapped yet for the current piece.
+    pub fn available(&self, slot: usize) -> bool {
+        self.slots.get(slot).is_some_and(|slot| !slot.used)
+    }
+
+    // Puts `piece` in `slot` and hands back what was there, if anything. Check `available`
+    // first.
+    pub fn swap(&mut self, slot: usize, piece: T) -> Option<T> {
+        let slot = &mut self.slots[slot];
+        slot.used = true;
+        slot.piece.replace(piece)
+    }
+
+    // A piece locked: every slot can be swapped again.
+    pub fn unlock(&mut self) {
+        for slot in &mut self.slots {
+            slot.used = false;
+        }
+    }
+
+    // Each slot's piece, and whether it can be swapped right now.
+    pub fn slots(&self) -> impl Iterator<Item = (Option<&T>, bool)> {
+        self.slots.iter().map(|slot| (slot.piece.as_ref(), !slot.used))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_count_separately() {
+        let mut hold = Hold::new(2);
+        assert_eq!(hold.swap(0, 'T'), None);
+        assert!(!hold.available(0));
+        assert!(hold.available(1));
+        assert_eq!(hold.swap(1, 'I'), None);
+        assert!(!hold.available(1));
+        hold.unlock();
+        assert_eq!(hold.swap(0, 'O'), Some('T'));
+        assert_eq!(hold.slots().map(|(piece, _)| piece.copied()).collect::<Vec<_>>(), [Some('O'), Some('I')]);
+    }
+
+    #[test]
+    fn slots_are_capped() {
+        assert_eq!(Hold::<char>::new(0).slots().count(), 0);
+        assert_eq!(Hold::<char>::new(5).slots().count(), MAX_SLOTS);
+        assert!(!Hold::<char>::new(1).available(1));
+    }
+}
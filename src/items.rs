@@ -0,0 +1,59 @@
+use crate::clock;
+
+// With item mode on, about one piece in this many carries a sparkling cell.
+pub const ITEM_CHANCE: usize = 8;
+// Gravity runs this many times slower while the slow gravity item lasts...
+pub const SLOW_FACTOR: u64 = 3;
+// ...which is this long.
+const SLOW_TICKS: u64 = 10 * clock::TICKS_PER_SEC;
+
+// What clearing a line with a sparkling cell in it does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item {
+    ClearBottom,
+    SlowGravity,
+    ShuffleColors,
+}
+
+impl Item {
+    pub const ALL: [Item; 3] = [Item::ClearBottom, Item::SlowGravity, Item::ShuffleColors];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Item::ClearBottom => "Fundo limpo!",
+            Item::SlowGravity => "Gravidade lenta!",
+            Item::ShuffleColors => "Cores embaralhadas!",
+        }
+    }
+}
+
+// Items whose effect lasts a while, with the tick each one wears off. Picking one up again
+// while it's running starts it over.
+#[derive(Default)]
+pub struct Effects {
+    running: Vec<(Item, u64)>,
+}
+
+impl Effects {
+    pub fn start(&mut self, item: Item, now: u64) {
+        let until = match item {
+            Item::SlowGravity => now + SLOW_TICKS,
+            Item::ClearBottom | Item::ShuffleColors => return,
+        };
+        self.running.retain(|&(running, _)| running != item);
+        self.running.push((item, until));
+    }
+
+    pub fn active(&self, item: Item) -> bool {
+        self.running.iter().any(|&(running, _)| running == item)
+    }
+
+    // Drops the effects that have run out by `now`.
+    pub fn update(&mut self, now: u64) {
+        self.running.retain(|&(_, until)| until > now);
+    }
+
+    pub fn clear(&mut self) {
+        self.running.clear();
+    }
+}
@@ -0,0 +1,37 @@
+use ggez::graphics::Rect;
+
+use crate::{BOARD_WIDTH, SCREEN_HEIGHT};
+
+// Width of the panel on each side of the playfield.
+pub const PANEL_WIDTH: f32 = 120.0;
+// Space between a panel's edge and what's in it.
+const MARGIN: f32 = 10.0;
+
+// The parts of the game screen. The playfield is in the middle; hold and the numbers are in
+// the left panel, and what's coming up in the right one.
+#[derive(Clone, Copy)]
+pub enum Region {
+    Board,
+    Hold,
+    Stats,
+    Next,
+    Combo,
+    Bag,
+}
+
+impl Region {
+    // Where the region is, in logical coordinates.
+    pub fn rect(self) -> Rect {
+        let left = MARGIN;
+        let right = PANEL_WIDTH + BOARD_WIDTH + MARGIN;
+        let width = PANEL_WIDTH - 2.0 * MARGIN;
+        match self {
+            Region::Board => Rect::new(PANEL_WIDTH, 0.0, BOARD_WIDTH, SCREEN_HEIGHT),
+            Region::Hold => Rect::new(left, MARGIN, width, 150.0),
+            Region::Stats => Rect::new(left, 180.0, width, SCREEN_HEIGHT - 180.0 - MARGIN),
+            Region::Next => Rect::new(right, MARGIN, width, 250.0),
+            Region::Combo => Rect::new(right, 280.0, width, 70.0),
+            Region::Bag => Rect::new(right, 370.0, width, SCREEN_HEIGHT - 370.0 - MARGIN),
+        }
+    }
+}
@@ -1,101 +1,484 @@
 use ggez::{Context, GameResult};
-use ggez::graphics::{self, Color, DrawMode, DrawParam, Rect, Text};
-use ggez::event::{self, EventHandler};
-use ggez::input::keyboard::{KeyCode, KeyInput};
-use ggez::audio::{self, SoundSource};
+use ggez::conf::FullscreenType;
+use ggez::graphics::{self, Color, Rect};
+use ggez::event::{self, Axis, Button, EventHandler, GamepadId, MouseButton};
+use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
+use ggez::audio;
 use rand::Rng;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use std::process::Command;
+
+mod api;
+mod assets;
+mod backdrop;
+mod clock;
+mod config;
+mod console;
+mod gamepad;
+mod heatmap;
+mod hold;
+mod http;
+mod items;
+mod layout;
+mod missions;
+mod particles;
+mod piece;
+mod pieceset;
+mod progress;
+mod randomizer;
+mod replay;
+mod rotation;
+mod scare;
+mod scoring;
+mod secret;
+mod shop;
+mod sound;
+mod soundpack;
+mod speed;
+mod sprites;
+mod stats;
+mod storage;
+mod sync;
+mod tas;
+mod text;
+mod themes;
+mod ui;
+mod viewport;
+mod websocket;
+
+use api::{ApiServer, LiveState};
+use assets::AssetLoader;
+use backdrop::Backdrop;
+use config::{Action, ClearGravity, ControlPreset, GhostStyle, LockPolicy, Settings, SpinRule};
+use console::Console;
+use heatmap::Heatmap;
+use hold::Hold;
+use items::{Effects, Item};
+use layout::Region;
+use particles::Particles;
+use piece::PieceKind;
+use pieceset::PieceSet;
+use progress::{Achievement, Progress, MILESTONES};
+use randomizer::{Randomizer, RandomizerKind};
+use replay::{Replay, ReplayPlayer};
+use rotation::RotationSystem;
+use scare::{ScareManifest, Scares, Trigger};
+use scoring::ScoreTable;
+use secret::SequenceDetector;
+use shop::Skin;
+use sound::{AudioManager, Music, Sound};
+use soundpack::SoundPack;
+use speed::{Gravity, Speed};
+use sprites::{CellSprites, Tile};
+use viewport::Viewport;
+use stats::{GameMode, GameOutcome, RunStats, StatsHistory};
+use sync::{SyncClient, SyncConfig, SyncPayload, SyncResult};
+use tas::{TasInput, TasPlayer, TasScript};
+use text::TextCache;
+use themes::ThemeFile;
 
 const CELL_SIZE: f32 = 30.0;
 const GRID_WIDTH: usize = 10;
-const GRID_HEIGHT: usize = 20;
+const VISIBLE_ROWS: usize = 20;
+// Rows above the visible board where pieces spawn and can rotate above the stack.
+const BUFFER_ROWS: usize = 20;
+const GRID_HEIGHT: usize = VISIBLE_ROWS + BUFFER_ROWS;
 const PINK: Color = Color::new(1.0, 0.41, 0.71, 1.0);
 const YELLOW: Color = Color::new(1.0, 1.0, 0.0, 1.0);
+const BOARD_WIDTH: f32 = GRID_WIDTH as f32 * CELL_SIZE;
+// The playfield with a panel on each side.
+const SCREEN_WIDTH: f32 = BOARD_WIDTH + 2.0 * layout::PANEL_WIDTH;
+const SCREEN_HEIGHT: f32 = VISIBLE_ROWS as f32 * CELL_SIZE;
+const HISTORY_LIMIT: usize = 50;
+const MISDROP_WINDOW: Duration = Duration::from_millis(200);
+const MENU_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const ATTRACT_DELAY: Duration = Duration::from_secs(20);
+const SPRINT_LINES: u32 = 40;
+const CASCADE_TICKS: u64 = 3;
+// How many drop presses are kept for the next piece while waiting for it.
+const INPUT_BUFFER: usize = 3;
+// Sandbox placements that can be taken back.
+const UNDO_LIMIT: usize = 100;
+// Pieces shown in the next queue, dealt ahead of the one in play.
+const NEXT_PIECES: usize = 5;
+// Option rows on the controls screen between the actions and the presets.
+const CONTROL_OPTIONS: usize = 5;
+const GHOST_ALPHA: f32 = 0.2;
+// How long a piece that locks without clearing anything flashes.
+const LOCK_FLASH_TICKS: u64 = 8;
+const SHAKE_TICKS: u64 = 12;
+// How long each of 3, 2, 1 and GO stays up.
+const COUNTDOWN_STEP: Duration = Duration::from_millis(600);
+const COUNTDOWN_LABELS: [&str; 4] = ["3", "2", "1", "GO!"];
+// How long the game-over curtain takes to cover the board.
+const CURTAIN: Duration = Duration::from_millis(1000);
+// The stack is in danger once it reaches this many rows from the top of the visible board.
+const DANGER_ROWS: usize = 4;
+// The music speeds up a step every this many levels, up to `MUSIC_STEPS` steps.
+const MUSIC_STEP_LEVELS: u32 = 5;
+const MUSIC_STEPS: u32 = 4;
+// Garbage cells are told apart by this color and always drawn in their own style.
+const GARBAGE: Color = Color::new(0.45, 0.45, 0.5, 1.0);
+const RESOURCE_DIR: &str = "resource";
+// Used for all text when it's in the resource directory and the theme has no font of its own.
+const FONT_PATH: &str = "/font.ttf";
+
+// A settled block. One locked from a piece carrying an item keeps it until its row clears.
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    // The color it was dealt, and the piece it came from, so it can be drawn in that piece's
+    // standard color instead. Garbage and cells painted in the editor have no piece.
+    color: Color,
+    kind: Option<PieceKind>,
+    item: Option<Item>,
+    // Pushed up from the floor rather than locked from a piece; drawn in its own style
+    // whatever its color.
+    garbage: bool,
+}
+
+impl Cell {
+    fn plain(color: Color) -> Self {
+        Cell { color, kind: None, item: None, garbage: false }
+    }
+
+    fn garbage() -> Self {
+        Cell { garbage: true, ..Cell::plain(GARBAGE) }
+    }
+}
+
+// Rows live in place; clearing and garbage copy rows around instead of reallocating them.
+type Grid = [[Option<Cell>; GRID_WIDTH]; GRID_HEIGHT];
+
+const EMPTY_GRID: Grid = [[None; GRID_WIDTH]; GRID_HEIGHT];
 
+#[derive(Clone)]
 struct Block {
     x: i32,
     y: i32,
+    kind: PieceKind,
     shape: Vec<Vec<bool>>,
+    // 0 at spawn, then one step per clockwise quarter turn.
+    rotation: usize,
+    color: Color,
+    // Item mode: the piece has a sparkling cell, see `item_cell`.
+    item: Option<Item>,
+    // Board cells per mino across and down: 2 in big mode. Sideways moves and kicks go a
+    // whole mino at a time, falling goes a cell at a time.
+    scale: i32,
+    // The bag it was dealt from, see `Randomizer::bag`.
+    bag: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Screen {
+    Title,
+    Modes,
+    // The run is about to start, or pick up again after a pause, and waits out the countdown.
+    Countdown,
+    Playing,
+    Paused,
+    Results,
+    Settings,
+    History,
+    Replay,
+    Heatmap,
+    Stats,
+    Shame,
+    Themes,
+    Controls,
+    Display,
+    Accessibility,
+    Audio,
+    Shop,
+    Missions,
+}
+
+// Rows on the display screen.
+#[derive(Clone, Copy)]
+enum DisplayOption {
+    BagPreview,
+    BlockStyle,
+    Ghost,
+    GhostOpacity,
+    GridOpacity,
+    Shake,
+    Fullscreen,
+    Backdrop,
+    BackdropBrightness,
+    Vsync,
+    FpsCap,
+    ShowFps,
+    SmoothGravity,
+    DangerWarning,
+    Countdown,
+}
+
+impl DisplayOption {
+    const ALL: [DisplayOption; 15] = [
+        DisplayOption::BagPreview,
+        DisplayOption::BlockStyle,
+        DisplayOption::Ghost,
+        DisplayOption::GhostOpacity,
+        DisplayOption::GridOpacity,
+        DisplayOption::Shake,
+        DisplayOption::Fullscreen,
+        DisplayOption::Backdrop,
+        DisplayOption::BackdropBrightness,
+        DisplayOption::Vsync,
+        DisplayOption::FpsCap,
+        DisplayOption::ShowFps,
+        DisplayOption::SmoothGravity,
+        DisplayOption::DangerWarning,
+        DisplayOption::Countdown,
+    ];
+}
+
+// Rows on the accessibility screen.
+#[derive(Clone, Copy)]
+enum AccessOption {
+    PieceColors,
+    CellPatterns,
+}
+
+impl AccessOption {
+    const ALL: [AccessOption; 2] = [AccessOption::PieceColors, AccessOption::CellPatterns];
+}
+
+// Rows on the audio screen.
+#[derive(Clone, Copy)]
+enum AudioOption {
+    Muted,
+    MusicVolume,
+    SfxVolume,
+    MusicRamp,
+    MusicLayers,
+    SoundPack,
+}
+
+impl AudioOption {
+    const ALL: [AudioOption; 6] = [
+        AudioOption::Muted,
+        AudioOption::MusicVolume,
+        AudioOption::SfxVolume,
+        AudioOption::MusicRamp,
+        AudioOption::MusicLayers,
+        AudioOption::SoundPack,
+    ];
+}
+
+// Why the game is paused. Only a manual pause needs the pause key to resume, and only an idle
+// one times out to the title screen.
+#[derive(Clone, Copy, PartialEq)]
+enum PauseReason {
+    Manual,
+    Idle,
+    FocusLost,
+    PadDisconnected,
+}
+
+// Game state from just before a placement, for undo.
+struct Snapshot {
+    grid: Grid,
+    score: u32,
+    back_to_back: bool,
+    combo: u32,
+    level: u32,
+    lines_cleared_total: u32,
+    // Line credits towards the next level.
+    goal_progress: u32,
+    randomizer: Randomizer,
+    queue: VecDeque<Block>,
+    hold: Hold<Block>,
+    kind: PieceKind,
     color: Color,
+    bag: u64,
+}
+
+// Completed rows waiting to be removed, and what the lock that completed them still has to
+// score once they are.
+struct Clearing {
+    rows: Vec<usize>,
+    from: u64,
+    until: u64,
+    spin: bool,
+    holes_before: u32,
 }
 
 struct GameState {
+    screen: Screen,
+    back: Vec<Screen>,
     block: Block,
-    grid: Vec<Vec<Option<Color>>>,
-    fall_time: Duration,
-    last_update: Duration,
+    randomizer: Randomizer,
+    // Sets found in resource/pieces/, offered in the modes menu.
+    piece_sets: Vec<PieceSet>,
+    // Themes found in resource/themes/, listed after the built-in ones.
+    theme_files: Vec<ThemeFile>,
+    // The theme whose skin, backdrop, font and sounds are loaded. Imports and syncs can
+    // change the theme in the settings too, so `update` catches up whenever they differ.
+    loaded_theme: String,
+    // Folders found in resource/soundpacks/, and the one whose sounds are loaded, which
+    // `update` keeps up with the settings the same way.
+    sound_packs: Vec<SoundPack>,
+    loaded_folder: String,
+    // What the current run's randomizer was seeded with, for restarting on the same pieces,
+    // and a seed picked from the console for the next run.
+    seed: u64,
+    next_seed: Option<u64>,
+    grid: Grid,
+    step: clock::FixedStep,
+    tick: u64,
+    // Gravity built up towards the next row, in `Gravity::rows` per tick. Any downward move,
+    // by gravity or by the player, starts it over.
+    fall_progress: u64,
+    // Tick at which the grounded piece locks, while it's resting on something.
+    lock_at: Option<u64>,
+    // Lock delay resets used up, and the lowest row the piece has reached, for `LockPolicy`.
+    lock_resets: u32,
+    lowest_y: i32,
+    // Tick at which the next piece enters, during the entry delay (ARE) after a lock.
+    spawn_at: Option<u64>,
+    // Initial rotation (IRS): quarter turns pressed during the entry delay, applied on spawn.
+    irs_turns: usize,
+    // Drops pressed between pieces, played on the next one as soon as it enters.
+    input_buffer: Vec<Action>,
+    undo_stack: Vec<Snapshot>,
+    // Blocks left floating by a clear are still falling (sticky clear gravity).
+    cascading: bool,
+    // Item effects still running.
+    effects: Effects,
+    // The piece's last successful move was a rotation, which spins require.
+    last_rotated: bool,
+    // Text shown for a moment after a spin, and the tick it goes away.
+    callout: Option<(String, u64)>,
+    // A lock that completed rows, waiting out the line clear delay.
+    clearing: Option<Clearing>,
+    // How far the screen is thrown, in pixels, and the ticks the shake runs between.
+    shake: Option<(f32, u64, u64)>,
+    // Rows from the floor to the top of the highest locked cell, as of the last update.
+    stack_height: usize,
+    // Time left on the countdown.
+    countdown: Duration,
+    // How the logical screen is scaled and centered in the window.
+    viewport: Viewport,
+    // The piece that just locked, and the tick its flash is over.
+    lock_flash: Option<(Block, u64)>,
     score: u32,
+    score_table: ScoreTable,
+    // The last line clear was a difficult one (tetris or spin), so the next can score back-to-back.
+    back_to_back: bool,
+    // Consecutive placements that each cleared at least one line.
+    combo: u32,
+    // Tick until which the perfect clear banner shows.
+    perfect_clear_until: u64,
     game_over: bool,
-    death_sound: audio::Source,
-    combo_sound: audio::Source,
-    start_sound: audio::Source,
+    loader: Option<AssetLoader>,
+    audio: AudioManager,
     freeze_timer: Option<Duration>,
     freeze_start: Option<Duration>,
+    // Visible rows, from the floor up, the game-over curtain has covered so far.
+    curtain_rows: usize,
     death_count: u32,
-    jumpscare_shown: bool,
+    progress: Progress,
+    scares: Scares,
+    mode: GameMode,
+    run_stats: RunStats,
+    spawn_time: Duration,
+    suspect_lock: Option<(PieceKind, Duration)>,
+    last_input: Duration,
+    replay: Replay,
+    history: StatsHistory,
+    history_list: ui::ScrollList,
+    theme_list: ui::ScrollList,
+    controls_list: ui::ScrollList,
+    display_list: ui::ScrollList,
+    access_list: ui::ScrollList,
+    audio_list: ui::ScrollList,
+    // Action waiting for its new key on the controls screen.
+    rebinding: Option<Action>,
+    shop_list: ui::ScrollList,
+    earned: u32,
+    editing: bool,
+    console: Console,
+    level: u32,
+    lines_cleared_total: u32,
+    goal_progress: u32,
+    // The next pieces, in the order they'll come into play.
+    queue: VecDeque<Block>,
+    hold: Hold<Block>,
+    replay_player: Option<ReplayPlayer>,
+    cells: CellSprites,
+    backdrop: Backdrop,
+    // Whether the window was opened with vsync, which can't change until the next start.
+    vsync: bool,
+    frame_limiter: clock::FrameLimiter,
+    particles: Particles,
+    text: TextCache,
+    overlay: Overlay,
+    api: Option<ApiServer>,
+    tas: Option<TasPlayer>,
+    heatmap: Option<Heatmap>,
+    view_all: bool,
+    idle_since: Duration,
+    attract: Option<ReplayPlayer>,
+    secrets: SequenceDetector,
+    data_dir: PathBuf,
+    config_dir: PathBuf,
+    settings: Settings,
+    sync: SyncClient,
+    sync_status: String,
+    shift_dir: i32,
+    next_shift: u64,
+    // Soft drop key held; gravity speeds up while it is.
+    soft_dropping: bool,
+    stick: gamepad::Stick,
+    // Where the pointer went down on the board, with pointer controls on.
+    press_at: Option<(f32, f32)>,
+    pause_reason: PauseReason,
+    // The controller last used in a run, watched for disconnects.
+    pad: Option<GamepadId>,
+    title_menu: ui::Menu,
+    modes_menu: ui::Menu,
+    results_menu: ui::Menu,
+    settings_menu: ui::Menu,
+    status: Option<String>,
 }
 
 impl Block {
-    fn new() -> Self {
-        let mut rng = rand::thread_rng();
-        let shapes = vec![
-            // I
-            vec![
-                vec![true, true, true, true],
-                vec![false, false, false, false],
-                vec![false, false, false, false],
-                vec![false, false, false, false],
-            ],
-            // O
-            vec![
-                vec![true, true],
-                vec![true, true],
-            ],
-            // T
-            vec![
-                vec![false, true, false],
-                vec![true, true, true],
-                vec![false, false, false],
-            ],
-            // L
-            vec![
-                vec![true, false, false],
-                vec![true, true, true],
-                vec![false, false, false],
-            ],
-            // J
-            vec![
-                vec![false, false, true],
-                vec![true, true, true],
-                vec![false, false, false],
-            ],
-            // S
-            vec![
-                vec![false, true, true],
-                vec![true, true, false],
-                vec![false, false, false],
-            ],
-            // Z
-            vec![
-                vec![true, true, false],
-                vec![false, true, true],
-                vec![false, false, false],
-            ],
-        ];
-
-        let shape = shapes[rng.gen_range(0..shapes.len())].clone();
-        let color = if rng.gen_bool(0.5) { PINK } else { YELLOW };
+    // `shape` is the piece in its spawn orientation, see `GameState::spawn_shape`.
+    fn new(kind: PieceKind, shape: Vec<Vec<bool>>, color: Color, scale: i32) -> Self {
+        let shape = enlarged(&shape, scale);
+        // Spawn in the buffer, resting just above the visible board.
+        let bottom = shape.iter().rposition(|row| row.contains(&true)).unwrap_or(0) as i32;
 
+        // Big pieces stay on even columns, so they can reach both walls.
+        let x = (GRID_WIDTH as i32 - shape[0].len() as i32) / 2;
         Block {
-            x: (GRID_WIDTH as i32 - shape[0].len() as i32) / 2,
-            y: 0,
+            x: x - x.rem_euclid(scale),
+            y: BUFFER_ROWS as i32 - 1 - bottom,
+            kind,
             shape,
+            rotation: 0,
             color,
+            item: None,
+            scale,
+            bag: 0,
         }
     }
 
-    fn can_move(&self, dx: i32, dy: i32, grid: &Vec<Vec<Option<Color>>>) -> bool {
+    // The cell the item sits on: the one nearest the middle of the piece's box, so it stays
+    // about where it was as the piece turns.
+    fn item_cell(&self) -> Option<(usize, usize)> {
+        self.item?;
+        let size = self.shape.len() as i32 - 1;
+        let filled = self.shape.iter().enumerate().flat_map(|(y, row)| {
+            row.iter().enumerate().filter(|(_, &cell)| cell).map(move |(x, _)| (x, y))
+        });
+        filled.min_by_key(|&(x, y)| (2 * x as i32 - size).pow(2) + (2 * y as i32 - size).pow(2))
+    }
+
+    fn can_move(&self, dx: i32, dy: i32, grid: &Grid) -> bool {
         for (y, row) in self.shape.iter().enumerate() {
             for (x, &cell) in row.iter().enumerate() {
                 if cell {
@@ -115,251 +498,3885 @@ impl Block {
         true
     }
 
-    fn rotate(&mut self, grid: &Vec<Vec<Option<Color>>>) {
-        let rows = self.shape.len();
-        let cols = self.shape[0].len();
-        let mut new_shape = vec![vec![false; rows]; cols];
+    // Three-corner rule: how many of the cells diagonal to a T's center are filled or off the
+    // board. The rotation system says where the center is. A big T checks the top left cell of
+    // each corner mino.
+    fn t_corners(&self, grid: &Grid, rotation: &dyn RotationSystem) -> usize {
+        let (cx, cy) = rotation.t_center(self.rotation);
+        [(cx - 1, cy - 1), (cx + 1, cy - 1), (cx - 1, cy + 1), (cx + 1, cy + 1)]
+            .into_iter()
+            .filter(|&(dx, dy)| {
+                let (x, y) = (self.x + dx * self.scale, self.y + dy * self.scale);
+                x < 0 || x >= GRID_WIDTH as i32 || y >= GRID_HEIGHT as i32 || (y >= 0 && grid[y as usize][x as usize].is_some())
+            })
+            .count()
+    }
 
-        for y in 0..rows {
-            for x in 0..cols {
-                new_shape[x][rows - 1 - y] = self.shape[y][x];
-            }
-        }
+    fn above_skyline(&self) -> bool {
+        self.shape
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.contains(&true))
+            .all(|(y, _)| self.y + (y as i32) < BUFFER_ROWS as i32)
+    }
+
+    // Leftmost to rightmost board column the piece covers.
+    fn columns(&self) -> std::ops::RangeInclusive<i32> {
+        let filled = |x: &usize| self.shape.iter().any(|row| row[*x]);
+        let width = self.shape[0].len();
+        let left = (0..width).find(filled).unwrap_or(0) as i32;
+        let right = (0..width).rfind(filled).unwrap_or(0) as i32;
+        self.x + left..=self.x + right
+    }
 
-        let old_shape = self.shape.clone();
-        self.shape = new_shape;
+    fn immobile(&self, grid: &Grid) -> bool {
+        !self.can_move(-self.scale, 0, grid) && !self.can_move(self.scale, 0, grid) && !self.can_move(0, -1, grid)
+    }
+
+    // How many rows the block can fall before it lands, i.e. where a hard drop puts it.
+    fn drop_distance(&self, grid: &Grid) -> i32 {
+        let mut distance = 0;
+        while self.can_move(0, distance + 1, grid) {
+            distance += 1;
+        }
+        distance
+    }
 
-        if !self.can_move(0, 0, grid) {
-            self.shape = old_shape;
+    // Rotates by `turns` clockwise quarter turns, trying the rotation system's kick offsets in
+    // turn if the new shape doesn't fit where it is. Returns whether it rotated.
+    fn rotate(&mut self, turns: usize, rotation: &dyn RotationSystem, grid: &Grid) -> bool {
+        let to = (self.rotation + turns) % 4;
+        // Custom pieces turn their own box, whatever the rotation system.
+        let shape = match self.kind {
+            PieceKind::Custom(_) => rotation::turned(&self.shape, turns),
+            kind => enlarged(&rotation.shape(kind, to), self.scale),
+        };
+        let old_shape = std::mem::replace(&mut self.shape, shape);
+        let scale = self.scale as usize;
+        let kicks = match self.blocked_cell(grid) {
+            None => &[(0, 0)][..],
+            Some((x, y)) => rotation.kicks(self.kind, self.rotation, to, (x / scale, y / scale)),
+        };
+        for &(dx, dy) in kicks {
+            let (dx, dy) = (dx * self.scale, dy * self.scale);
+            if self.can_move(dx, -dy, grid) {
+                self.x += dx;
+                self.y -= dy;
+                self.rotation = to;
+                return true;
+            }
         }
+        self.shape = old_shape;
+        false
+    }
+
+    // First cell of the shape, in reading order, that is off the board or overlaps the stack.
+    fn blocked_cell(&self, grid: &Grid) -> Option<(usize, usize)> {
+        self.shape.iter().enumerate().find_map(|(y, row)| {
+            row.iter().enumerate().find_map(|(x, &cell)| {
+                let (gx, gy) = (self.x + x as i32, self.y + y as i32);
+                let free = gx >= 0
+                    && gx < GRID_WIDTH as i32
+                    && gy < GRID_HEIGHT as i32
+                    && (gy < 0 || grid[gy as usize][gx as usize].is_none());
+                (cell && !free).then_some((x, y))
+            })
+        })
     }
 }
 
 impl GameState {
-    fn new(ctx: &mut Context) -> GameResult<Self> {
-        let death_sound = audio::Source::new(ctx, "/death.ogg")?;
-        let combo_sound = audio::Source::new(ctx, "/atk.ogg")?;
-        let mut start_sound = audio::Source::new(ctx, "/random.mp3")?;
-        start_sound.set_volume(10.0);
+    fn new(ctx: &mut Context, options: Options) -> GameResult<Self> {
+        let data_dir = ctx.fs.user_data_dir().to_path_buf();
+        let config_dir = ctx.fs.user_config_dir().to_path_buf();
+        let history = StatsHistory::load(&data_dir.join("history.toml"));
+        let progress = Progress::load(&data_dir.join("progress.toml"));
+        let settings = Settings::load(&config_dir.join("settings.toml"));
+        let sync = SyncClient::new(SyncConfig::load_or_create(&config_dir.join("sync.toml")));
+        let scares = Scares::new(ScareManifest::load(ctx, &config_dir));
+        let piece_sets = PieceSet::load_all(ctx);
+        let theme_files = ThemeFile::load_all(ctx);
+        let sound_packs = SoundPack::load_all(ctx);
+        let (window_width, window_height) = ctx.gfx.drawable_size();
+        // Same search order as ggez's resource filesystem.
+        let dirs = vec![
+            ctx.fs.resources_dir().to_path_buf(),
+            data_dir.clone(),
+            config_dir.clone(),
+            PathBuf::from(RESOURCE_DIR),
+        ];
+        let theme = theme_files.iter().find(|theme| theme.name == settings.theme);
+        let pack = sound_packs.iter().find(|pack| pack.name == settings.sound_folder);
+        let mut paths: Vec<String> = Sound::ALL.iter().filter_map(|&sound| sound_path(pack, theme, sound)).collect();
+        paths.extend(scares.asset_paths());
+        let loader = AssetLoader::start(dirs, paths);
         
-        Ok(GameState {
-            block: Block::new(),
-            grid: vec![vec![None; GRID_WIDTH]; GRID_HEIGHT],
-            fall_time: Duration::from_secs(1),
-            last_update: Duration::from_secs(0),
+        let mut state = GameState {
+            screen: Screen::Title,
+            back: Vec::new(),
+            block: Block::new(PieceKind::I, settings.rotation.system().shape(PieceKind::I, 0), settings.palette()[0], 1),
+            queue: VecDeque::new(),
+            hold: Hold::new(settings.hold_slots),
+            randomizer: Randomizer::new(),
+            piece_sets,
+            theme_files,
+            loaded_theme: settings.theme.clone(),
+            sound_packs,
+            loaded_folder: settings.sound_folder.clone(),
+            seed: 0,
+            next_seed: None,
+            grid: EMPTY_GRID,
+            step: clock::FixedStep::default(),
+            tick: 0,
+            fall_progress: 0,
+            lock_at: None,
+            lock_resets: 0,
+            lowest_y: 0,
+            spawn_at: None,
+            irs_turns: 0,
+            input_buffer: Vec::new(),
+            undo_stack: Vec::new(),
+            cascading: false,
+            last_rotated: false,
+            callout: None,
+            clearing: None,
+            lock_flash: None,
+            shake: None,
+            stack_height: 0,
+            countdown: Duration::ZERO,
+            viewport: Viewport::fit(window_width, window_height),
+            effects: Effects::default(),
             score: 0,
+            score_table: ScoreTable::default(),
+            back_to_back: false,
+            combo: 0,
+            perfect_clear_until: 0,
             game_over: false,
-            death_sound,
-            combo_sound,
-            start_sound,
+            loader: Some(loader),
+            audio: AudioManager::default(),
             freeze_timer: None,
             freeze_start: None,
+            curtain_rows: 0,
             death_count: 0,
-            jumpscare_shown: false,
-        })
+            progress,
+            scares,
+            mode: GameMode::Classic,
+            run_stats: RunStats::default(),
+            spawn_time: Duration::ZERO,
+            suspect_lock: None,
+            last_input: Duration::ZERO,
+            replay: Replay::new(GameMode::Classic),
+            history,
+            history_list: ui::ScrollList::new(110.0, 44.0, 10),
+            theme_list: ui::ScrollList::new(110.0, 44.0, 10),
+            controls_list: ui::ScrollList::new(110.0, 44.0, 10),
+            display_list: ui::ScrollList::new(110.0, 44.0, 10),
+            access_list: ui::ScrollList::new(110.0, 44.0, 10),
+            audio_list: ui::ScrollList::new(110.0, 44.0, 10),
+            rebinding: None,
+            shop_list: ui::ScrollList::new(110.0, 44.0, 10),
+            earned: 0,
+            editing: false,
+            console: Console::default(),
+            level: 0,
+            lines_cleared_total: 0,
+            goal_progress: 0,
+            replay_player: None,
+            cells: CellSprites::new(ctx),
+            backdrop: Backdrop::new(ctx),
+            vsync: settings.vsync,
+            frame_limiter: clock::FrameLimiter::new(),
+            particles: Particles::new(ctx),
+            text: TextCache::default(),
+            overlay: options.overlay,
+            api: options.api_port.and_then(|port| match ApiServer::start(port) {
+                Ok(api) => Some(api),
+                Err(e) => {
+                    eprintln!("failed to start the API on port {}: {}", port, e);
+                    None
+                }
+            }),
+            tas: None,
+            heatmap: None,
+            view_all: false,
+            idle_since: Duration::ZERO,
+            attract: None,
+            secrets: SequenceDetector::default(),
+            data_dir,
+            config_dir,
+            settings,
+            sync,
+            sync_status: String::new(),
+            shift_dir: 0,
+            next_shift: 0,
+            soft_dropping: false,
+            pause_reason: PauseReason::Manual,
+            pad: None,
+            press_at: None,
+            stick: gamepad::Stick::default(),
+            title_menu: ui::Menu::new(
+                &[
+                    "Jogar",
+                    "Missões",
+                    "Histórico",
+                    "Estatísticas",
+                    "Mapa de calor",
+                    "Hall da vergonha",
+                    "Loja",
+                    "Configurações",
+                ],
+                160.0,
+            ),
+            modes_menu: ui::Menu::new(&["Clássico", "Sprint 40L", "Sandbox", "", "", "", "", "", "", "Voltar"], 180.0).compact(),
+            results_menu: ui::Menu::new(
+                &["Jogar novamente", "Estatísticas", "Mapa de calor", "Exportar CSV", "Menu principal"],
+                300.0,
+            ),
+            settings_menu: ui::Menu::new(
+                &[
+                    "", "", "", "Áudio", "", "", "Visual", "Acessibilidade", "", "", "", "", "Controles",
+                    "Exportar perfil", "Importar perfil", "Sincronizar agora", "Voltar",
+                ],
+                100.0,
+            )
+            .compact(),
+            status: None,
+        };
+        state.apply_theme(ctx);
+        state.apply_volume();
+        state.text.set_density(state.viewport.scale());
+        state.refresh_settings_menu();
+        state.refresh_modes_menu();
+        state.spawn_block();
+        state.start_sync();
+        if let Some(tas) = options.tas {
+            state.start_tas(tas);
+        }
+        Ok(state)
     }
 
-    fn place_block(&mut self) {
-        for (y, row) in self.block.shape.iter().enumerate() {
-            for (x, &cell) in row.iter().enumerate() {
-                if cell {
-                    let grid_y = (self.block.y + y as i32) as usize;
-                    let grid_x = (self.block.x + x as i32) as usize;
-                    if grid_y < GRID_HEIGHT {
-                        self.grid[grid_y][grid_x] = Some(self.block.color);
+    fn reset(&mut self) {
+        self.screen = Screen::Playing;
+        self.game_over = false;
+        self.grid = EMPTY_GRID;
+        self.score = 0;
+        self.back_to_back = false;
+        self.combo = 0;
+        self.perfect_clear_until = 0;
+        self.earned = 0;
+        self.randomizer.reset();
+        self.queue.clear();
+        self.tas = None;
+        self.editing = false;
+        self.level = 0;
+        self.lines_cleared_total = 0;
+        self.goal_progress = 0;
+        self.hold = Hold::new(self.settings.hold_slots);
+        self.tick = 0;
+        self.fall_progress = 0;
+        self.spawn_at = None;
+        self.irs_turns = 0;
+        self.input_buffer.clear();
+        self.undo_stack.clear();
+        self.cascading = false;
+        self.effects.clear();
+        self.callout = None;
+        self.clearing = None;
+        self.lock_flash = None;
+        self.shake = None;
+        self.stack_height = 0;
+        self.curtain_rows = 0;
+        self.particles.clear();
+        self.scares.reset_run();
+        self.run_stats = RunStats::default();
+        self.run_stats.items = self.settings.items;
+        self.suspect_lock = None;
+        self.last_input = Duration::ZERO;
+        self.spawn_block();
+        self.replay = Replay::new(self.mode);
+        self.replay.cascade = self.settings.clear_gravity == ClearGravity::Sticky;
+        self.shift_dir = 0;
+        self.soft_dropping = false;
+        self.status = None;
+        self.start_countdown();
+    }
+
+    // Holds the run on a 3-2-1-GO so the first piece doesn't fall straight away. Scripted
+    // runs and the sandbox, which has no gravity, go straight in.
+    fn start_countdown(&mut self) {
+        if !self.settings.countdown || self.tas.is_some() || self.mode == GameMode::Sandbox {
+            self.screen = Screen::Playing;
+            return;
+        }
+        self.screen = Screen::Countdown;
+        self.countdown = COUNTDOWN_STEP * COUNTDOWN_LABELS.len() as u32;
+    }
+
+    fn save_settings(&mut self) {
+        self.settings.updated_at = stats::unix_now();
+        self.write_settings();
+    }
+
+    fn write_settings(&self) {
+        if let Err(e) = self.settings.save(&self.config_dir.join("settings.toml")) {
+            eprintln!("failed to save settings: {}", e);
+        }
+    }
+
+    fn save_history(&self) {
+        if let Err(e) = self.history.save(&self.data_dir.join("history.toml")) {
+            eprintln!("failed to save history: {}", e);
+        }
+    }
+
+    fn start_sync(&mut self) {
+        if !self.sync.config.enabled {
+            self.sync_status = "Sync desligado (veja sync.toml)".to_string();
+            return;
+        }
+        if self.sync.is_busy() {
+            return;
+        }
+        self.sync_status = "Sincronizando...".to_string();
+        self.sync.start(SyncPayload {
+            settings: self.settings.clone(),
+            // Replay links name files on this machine, so they stay here.
+            history: self.history.games.iter().map(|g| stats::GameRecord { replay: None, ..g.clone() }).collect(),
+        });
+    }
+
+    fn poll_sync(&mut self) {
+        match self.sync.poll() {
+            None => {}
+            Some(SyncResult::Failed(e)) => {
+                self.sync_status = format!("Falha no sync: {}", e);
+            }
+            Some(SyncResult::Done(merged)) => {
+                // Games may have finished while the request was in flight, so merge again
+                // rather than overwriting local state with the server's view.
+                self.history.games = sync::merge_history(&self.history.games, &merged.history);
+                self.save_history();
+                if merged.settings.updated_at > self.settings.updated_at {
+                    self.settings = merged.settings;
+                    self.write_settings();
+                    self.refresh_settings_menu();
+                    self.apply_volume();
+                }
+                self.sync_status = format!("Sincronizado: {} jogos", self.history.games.len());
+            }
+        }
+    }
+
+    fn emit(&self, event: &str, data: serde_json::Value) {
+        if let Some(api) = &self.api {
+            api.event(event, data);
+        }
+    }
+
+    fn live_state(&self) -> LiveState {
+        let in_run = matches!(self.screen, Screen::Countdown | Screen::Playing | Screen::Paused)
+            && !self.game_over
+            && self.piece_active();
+        let state = if self.game_over {
+            "game_over"
+        } else if self.screen == Screen::Countdown {
+            "countdown"
+        } else if self.screen == Screen::Playing {
+            "playing"
+        } else if self.screen == Screen::Paused {
+            "paused"
+        } else {
+            "menu"
+        };
+        let mut board: Vec<Vec<u8>> = self
+            .grid
+            .iter()
+            .map(|row| row.iter().map(|cell| if cell.is_some() { b'#' } else { b'.' }).collect())
+            .collect();
+        if in_run {
+            for (y, row) in self.block.shape.iter().enumerate() {
+                for (x, &cell) in row.iter().enumerate() {
+                    let (bx, by) = (self.block.x + x as i32, self.block.y + y as i32);
+                    if cell && (0..GRID_WIDTH as i32).contains(&bx) && (0..GRID_HEIGHT as i32).contains(&by) {
+                        board[by as usize][bx as usize] = b'@';
                     }
                 }
             }
         }
+        LiveState {
+            state,
+            mode: self.mode.name(),
+            score: self.score,
+            combo: self.combo,
+            level: self.level,
+            lines: self.run_stats.lines(),
+            pieces: self.run_stats.pieces,
+            keys: self.run_stats.keys,
+            clears: self.run_stats.clears,
+            max_drought: self.run_stats.max_drought,
+            play_time_ms: self.run_stats.play_time.as_millis() as u64,
+            board: board.into_iter().skip(BUFFER_ROWS).map(|row| String::from_utf8_lossy(&row).into_owned()).collect(),
+        }
     }
 
-    fn clear_lines(&mut self, ctx: &mut Context) -> GameResult {
-        let mut lines_cleared = 0;
-        
-        for y in (0..GRID_HEIGHT).rev() {
-            if self.grid[y].iter().all(|cell| cell.is_some()) {
-                self.grid.remove(y);
-                self.grid.insert(0, vec![None; GRID_WIDTH]);
-                lines_cleared += 1;
-                self.combo_sound.play_detached(ctx)?;
+    fn poll_assets(&mut self, ctx: &Context) {
+        let Some(mut loader) = self.loader.take() else {
+            return;
+        };
+        let sounds = Sound::ALL.map(|sound| sound_path(self.sound_pack(), self.theme_file(), sound));
+        for (path, bytes) in loader.poll() {
+            let bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    eprintln!("failed to load {}: {}", path, e);
+                    continue;
+                }
+            };
+            let Some(index) = sounds.iter().position(|sound| sound.as_ref() == Some(&path)) else {
+                self.scares.preload(ctx, &path, &bytes);
+                continue;
+            };
+            let data = audio::SoundData::from_bytes(&bytes);
+            if let Err(e) = self.audio.set_source(ctx, Sound::ALL[index], Some(data)) {
+                eprintln!("failed to decode {}: {}", path, e);
             }
         }
-        
-        if lines_cleared > 0 {
-            self.score += lines_cleared * 100;
-            self.fall_time = Duration::from_millis((1000.0 * 0.9f32.powi(self.score as i32 / 1000)) as u64);
+        if !loader.finished() {
+            self.loader = Some(loader);
         }
-        Ok(())
     }
 
-    fn check_game_over(&mut self, ctx: &mut Context) -> GameResult {
-        if self.grid[0].iter().any(|cell| cell.is_some()) {
-            self.game_over = true;
-            self.death_count += 1;
-            let _ = self.death_sound.play_detached(ctx)?;
-            self.freeze_timer = Some(Duration::from_secs(5));
-            self.freeze_start = Some(ctx.time.time_since_start());
-            let _ = self.start_sound.play_detached(ctx)?;
+    fn refresh_modes_menu(&mut self) {
+        let name = self.piece_set().map_or("padrão", |set| set.name.as_str());
+        let label = format!("Peças: {}", name);
+        self.modes_menu.set_label(3, &label);
+        self.modes_menu.set_label(4, &format!("Grande: {}", if self.settings.big { "sim" } else { "não" }));
+        self.modes_menu.set_label(5, &format!("20G: {}", if self.settings.instant_gravity { "sim" } else { "não" }));
+        self.modes_menu.set_label(6, &format!("Velocidade: {}", self.settings.speed_curve.label()));
+        self.modes_menu.set_label(7, &format!("Meta de nível: {}", self.settings.level_goal.label()));
+        let hold = match self.settings.hold_slots {
+            0 => "não".to_string(),
+            1 => "1 espaço".to_string(),
+            slots => format!("{} espaços", slots),
+        };
+        self.modes_menu.set_label(8, &format!("Guardar: {}", hold));
+    }
 
-            if self.death_count == 1 && !self.jumpscare_shown {
-                self.jumpscare_shown = true;
-                if let Ok(resource_path) = std::env::current_dir() {
-                    let image_path = resource_path.join("resource").join("buuh.png");
-                    let _ = Command::new("cmd")
-                        .args(["/C", "start", "", image_path.to_str().unwrap_or("")])
-                        .spawn();
-                }
+    // Standard pieces, then each loaded piece set in turn.
+    fn next_piece_set(&mut self) {
+        let current = self.piece_sets.iter().position(|set| set.name == self.settings.piece_set);
+        let next = match current {
+            None => self.piece_sets.first(),
+            Some(index) => self.piece_sets.get(index + 1),
+        };
+        self.settings.piece_set = next.map(|set| set.name.clone()).unwrap_or_default();
+        self.save_settings();
+        self.refresh_modes_menu();
+    }
+
+    // The piece set picked in the modes menu, or none for the standard seven.
+    fn piece_set(&self) -> Option<&PieceSet> {
+        self.piece_sets.iter().find(|set| set.name == self.settings.piece_set)
+    }
+
+    fn piece_kinds(&self) -> Vec<PieceKind> {
+        self.piece_set().map_or_else(|| PieceKind::ALL.to_vec(), PieceSet::kinds)
+    }
+
+    fn spawn_shape(&self, kind: PieceKind) -> Vec<Vec<bool>> {
+        match (kind, self.piece_set()) {
+            (PieceKind::Custom(index), Some(set)) => set.shape(index),
+            _ => self.settings.rotation.system().shape(kind, 0),
+        }
+    }
+
+    fn piece_scale(&self) -> i32 {
+        if self.settings.big {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn piece_name(&self, kind: PieceKind) -> &str {
+        match (kind, self.piece_set()) {
+            (PieceKind::Custom(index), Some(set)) => set.piece_name(index),
+            _ => kind.name(),
+        }
+    }
+
+    fn refresh_settings_menu(&mut self) {
+        self.settings_menu.set_label(0, &format!("Tema: {}", self.settings.theme));
+        self.settings_menu.set_label(1, &self.settings.auto_pause_label());
+        self.settings_menu.set_label(2, &self.settings.scare_label());
+        self.settings_menu.set_label(4, &format!("Sorteio: {}", self.settings.randomizer.label()));
+        self.settings_menu.set_label(5, &format!("Sorte: {}", if self.settings.luck { "sim" } else { "não" }));
+        self.settings_menu.set_label(8, &format!("Travamento: {}", self.settings.handling.lock_policy.label()));
+        self.settings_menu.set_label(9, &format!("Rotação: {}", self.settings.rotation.label()));
+        self.settings_menu.set_label(10, &format!("Gravidade de linha: {}", self.settings.clear_gravity.label()));
+        self.settings_menu.set_label(11, &format!("Itens: {}", if self.settings.items { "sim" } else { "não" }));
+    }
+
+    fn pause(&mut self, ctx: &Context, reason: PauseReason) {
+        self.screen = Screen::Paused;
+        self.pause_reason = reason;
+        self.idle_since = ctx.time.time_since_start();
+    }
+
+    // Pauses a live run when the player can't be playing it: the window lost focus or the
+    // controller went away.
+    fn pause_unattended(&mut self, ctx: &Context, reason: PauseReason) {
+        let running = matches!(self.screen, Screen::Countdown | Screen::Playing);
+        let live = running && !self.game_over && self.freeze_timer.is_none();
+        if live && self.tas.is_none() {
+            self.pause(ctx, reason);
+        }
+    }
+
+    fn check_pad(&mut self, ctx: &Context) {
+        let Some(pad) = self.pad else {
+            return;
+        };
+        if !ctx.gamepad.gamepads().any(|(id, _)| id == pad) {
+            self.pad = None;
+            self.pause_unattended(ctx, PauseReason::PadDisconnected);
+        }
+    }
+
+    fn resume(&mut self) {
+        self.start_countdown();
+        self.last_input = self.run_stats.play_time;
+        self.shift_dir = 0;
+        self.soft_dropping = false;
+    }
+
+    // A sideways move or rotation of a grounded piece may restart its lock delay.
+    fn moved(&mut self) {
+        self.last_rotated = false;
+        if self.lock_at.is_none() {
+            return;
+        }
+        let reset = match self.settings.handling.lock_policy {
+            LockPolicy::Infinite => true,
+            LockPolicy::MoveReset => self.lock_resets < LockPolicy::MOVE_RESETS,
+            LockPolicy::StepReset => false,
+        };
+        if reset {
+            self.lock_resets += 1;
+            self.lock_at = None;
+        }
+    }
+
+    // Every downward move goes through here. Dropping the piece cancels a spin set up by
+    // rotating it and restarts the gravity timer, so a soft drop is never followed straight
+    // away by a gravity step.
+    fn fall(&mut self, rows: i32) {
+        if rows > 0 {
+            self.block.y += rows;
+            self.last_rotated = false;
+            self.fall_progress = 0;
+            if !self.block.can_move(0, 1, &self.grid) {
+                self.audio.cue(Sound::Land);
             }
         }
-        Ok(())
     }
 
-    fn draw_jumpscare(&mut self) -> GameResult {
-        Ok(())
+    // False between pieces: during the entry delay, the line clear delay or while a cascade
+    // settles.
+    fn piece_active(&self) -> bool {
+        self.spawn_at.is_none() && self.clearing.is_none() && !self.cascading
     }
-}
 
-impl EventHandler<ggez::GameError> for GameState {
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
-        if let (Some(freeze_timer), Some(freeze_start)) = (self.freeze_timer, self.freeze_start) {
-            let now = ctx.time.time_since_start();
-            if now - freeze_start < freeze_timer {
-                return Ok(());
-            } else {
-                self.freeze_timer = None;
-                self.freeze_start = None;
-                self.game_over = false;
-                self.grid = vec![vec![None; GRID_WIDTH]; GRID_HEIGHT];
-                self.block = Block::new();
-                self.score = 0;
-                self.jumpscare_shown = false;
+    fn rotate(&mut self, turns: usize) {
+        if self.block.rotate(turns, self.settings.rotation.system(), &self.grid) {
+            self.moved();
+            self.last_rotated = true;
+            self.audio.cue(Sound::Rotate);
+        }
+    }
+
+    fn start_shift(&mut self, dir: i32) {
+        let step = dir * self.block.scale;
+        if self.piece_active() && self.block.can_move(step, 0, &self.grid) {
+            self.block.x += step;
+            self.moved();
+            self.audio.cue(Sound::Move);
+        }
+        self.shift_dir = dir;
+        self.next_shift = self.tick + clock::ticks_from_ms(self.settings.handling.das_ms);
+    }
+
+    fn update_shift(&mut self) {
+        if self.shift_dir == 0 || self.tick < self.next_shift {
+            return;
+        }
+        let arr = clock::ticks_from_ms(self.settings.handling.arr_ms);
+        if arr == 0 {
+            let distance = self.shift_distance();
+            if distance != 0 {
+                self.block.x += distance;
+                self.moved();
+                self.audio.cue(Sound::Move);
             }
+            return;
         }
+        let step = self.shift_dir * self.block.scale;
+        if self.block.can_move(step, 0, &self.grid) {
+            self.block.x += step;
+            self.moved();
+            self.audio.cue(Sound::Move);
+        }
+        self.next_shift = self.tick + arr;
+    }
 
-        if self.game_over {
-            return Ok(());
+    // How far the piece can slide in the held direction.
+    fn shift_distance(&self) -> i32 {
+        let step = self.shift_dir * self.block.scale;
+        let mut distance = 0;
+        while self.block.can_move(distance + step, 0, &self.grid) {
+            distance += step;
         }
+        distance
+    }
 
-        let now = ctx.time.time_since_start();
-        if now - self.last_update >= self.fall_time {
-            if self.block.can_move(0, 1, &self.grid) {
-                self.block.y += 1;
-            } else {
-                self.place_block();
-                self.clear_lines(ctx)?;
-                self.check_game_over(ctx)?;
-                self.block = Block::new();
+    // Takes the front of the queue and deals another onto the back. Pieces are dealt in the
+    // same order as they come into play, so a seed gives the same pieces however long the
+    // queue is.
+    fn spawn_block(&mut self) {
+        while self.queue.len() <= NEXT_PIECES {
+            let block = self.deal();
+            self.queue.push_back(block);
+        }
+        let block = self.queue.pop_front().expect("queue was just filled");
+        self.put_in_play(block);
+        self.run_stats.record_spawn(self.block.kind);
+        self.spawn_time = self.run_stats.play_time;
+    }
+
+    fn deal(&mut self) -> Block {
+        let kinds = self.piece_kinds();
+        let kind = self.randomizer.next(self.settings.randomizer, self.settings.luck, &kinds);
+        let mut block = self.new_block(kind);
+        block.bag = self.randomizer.bag();
+        if self.settings.items && self.randomizer.below(items::ITEM_CHANCE) == 0 {
+            block.item = Some(Item::ALL[self.randomizer.below(Item::ALL.len())]);
+        }
+        block
+    }
+
+    fn put_in_play(&mut self, block: Block) {
+        self.block = block;
+        // Like the guideline, a new piece drops into view straight away if it can.
+        if self.block.can_move(0, 1, &self.grid) {
+            self.block.y += 1;
+        }
+        self.lock_at = None;
+        self.last_rotated = false;
+        self.lock_resets = 0;
+        self.lowest_y = self.block.y;
+        self.fall_progress = 0;
+    }
+
+    // Sandbox only: takes back the last placement, restoring the board, score and piece queue
+    // to how they were just before it and handing that piece back from the top.
+    fn undo(&mut self) {
+        let Some(snapshot) = self.undo_stack.pop() else {
+            self.callout = Some(("Nada para desfazer".to_string(), self.tick + clock::ticks_from_ms(1000)));
+            return;
+        };
+        self.grid = snapshot.grid;
+        self.score = snapshot.score;
+        self.back_to_back = snapshot.back_to_back;
+        self.combo = snapshot.combo;
+        self.level = snapshot.level;
+        self.lines_cleared_total = snapshot.lines_cleared_total;
+        self.goal_progress = snapshot.goal_progress;
+        self.randomizer = snapshot.randomizer;
+        self.queue = snapshot.queue;
+        self.hold = snapshot.hold;
+        self.replay.placements.pop();
+        self.spawn_at = None;
+        self.clearing = None;
+        self.cascading = false;
+        self.input_buffer.clear();
+        let mut block = Block::new(snapshot.kind, self.spawn_shape(snapshot.kind), snapshot.color, self.piece_scale());
+        block.bag = snapshot.bag;
+        self.put_in_play(block);
+    }
+
+    // Colors come from the seeded randomizer too, so a seed reproduces the board exactly.
+    fn new_block(&mut self, kind: PieceKind) -> Block {
+        let palette = self.palette();
+        let mut color = palette[self.randomizer.below(palette.len())];
+        if let (PieceKind::Custom(index), Some(set)) = (kind, self.piece_set()) {
+            color = set.color(index).unwrap_or(color);
+        }
+        Block::new(kind, self.spawn_shape(kind), color, self.piece_scale())
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            grid: self.grid,
+            score: self.score,
+            back_to_back: self.back_to_back,
+            combo: self.combo,
+            level: self.level,
+            lines_cleared_total: self.lines_cleared_total,
+            goal_progress: self.goal_progress,
+            randomizer: self.randomizer.clone(),
+            queue: self.queue.clone(),
+            hold: self.hold.clone(),
+            kind: self.block.kind,
+            color: self.block.color,
+            bag: self.block.bag,
+        }
+    }
+
+    fn place_block(&mut self) {
+        lock_cells(&mut self.grid, &self.block);
+        self.replay.record(self.run_stats.play_time, &self.block);
+    }
+
+    // Returns how many lines were cleared. In big mode a line is a row of minos, two board rows
+    // high, and a row left over on its own still counts as one.
+    fn clear_lines(&mut self, ctx: &mut Context, spin: bool) -> GameResult<u32> {
+        self.spark_full_rows();
+        let items = full_row_items(&self.grid);
+        let lines_cleared = remove_full_rows(&mut self.grid).div_ceil(self.block.scale as u32);
+        let pitch = shop::sfx_pitch(&self.settings.sfx_pack);
+        if lines_cleared == 4 && self.audio.has(Sound::Tetris) {
+            self.audio.play(ctx, Sound::Tetris, pitch)?;
+        } else {
+            for _ in 0..lines_cleared {
+                self.audio.play(ctx, Sound::Clear, pitch)?;
             }
-            self.last_update = now;
         }
-        Ok(())
+        self.earned += shop::clear_reward(lines_cleared);
+
+        self.run_stats.record_clear(lines_cleared as usize);
+        if lines_cleared == 4 {
+            self.award(Achievement::FirstTetris);
+        }
+        let table = self.score_table;
+        let mut points = table.clear(lines_cleared, spin, self.level);
+        let difficult = table.difficult(lines_cleared, spin);
+        let back_to_back = difficult && self.back_to_back;
+        if difficult {
+            if back_to_back {
+                points = table.back_to_back(points);
+            }
+            self.back_to_back = true;
+        } else if lines_cleared > 0 {
+            self.back_to_back = false;
+        }
+        self.score += points;
+        if lines_cleared > 0 {
+            self.score += table.combo(self.combo, self.level);
+            self.combo += 1;
+            self.emit("clear", json!({ "lines": lines_cleared, "combo": self.combo, "score": self.score }));
+            if self.grid == EMPTY_GRID {
+                self.perfect_clear(ctx, lines_cleared)?;
+            }
+            self.lines_cleared_total += lines_cleared;
+            let level = self.settings.scare_intensity();
+            self.scares.check(ctx, Trigger::Score, self.score, level, &self.audio)?;
+            self.scares.check(ctx, Trigger::Lines, self.run_stats.lines(), level, &self.audio)?;
+        } else {
+            self.combo = 0;
+        }
+        self.reach_goal(self.settings.level_goal.credit(lines_cleared, spin, back_to_back));
+        for item in items {
+            self.use_item(item);
+        }
+        Ok(lines_cleared)
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        let mut canvas = graphics::Canvas::from_frame(ctx, Color::BLACK);
-        
-        for (y, row) in self.grid.iter().enumerate() {
-            for (x, cell) in row.iter().enumerate() {
-                if let Some(color) = cell {
-                    let rect = Rect::new(
-                        x as f32 * CELL_SIZE,
-                        y as f32 * CELL_SIZE,
-                        CELL_SIZE,
-                        CELL_SIZE,
-                    );
-                    let mesh = graphics::Mesh::new_rectangle(
-                        ctx,
-                        DrawMode::fill(),
-                        rect,
-                        *color,
-                    )?;
-                    canvas.draw(&mesh, DrawParam::default());
+    fn reach_goal(&mut self, credit: u32) {
+        let goal = self.settings.level_goal;
+        self.goal_progress += credit;
+        let level = self.level;
+        while self.goal_progress >= goal.goal(self.level) {
+            self.goal_progress -= goal.goal(self.level);
+            self.level += 1;
+        }
+        if self.level > level {
+            self.emit("level", json!({ "level": self.level }));
+        }
+    }
+
+    // `pixels` at full intensity; the shake setting scales it.
+    fn start_shake(&mut self, pixels: f32) {
+        let strength = pixels * self.settings.shake as f32 / 100.0;
+        if strength > 0.0 {
+            self.shake = Some((strength, self.tick, self.tick + SHAKE_TICKS));
+        }
+    }
+
+    // Where the shake has thrown the screen right now: mostly up and down, dying away.
+    fn shake_offset(&self) -> Option<[f32; 2]> {
+        let (strength, from, until) = self.shake?;
+        if self.tick >= until {
+            return None;
+        }
+        let fade = (until - self.tick) as f32 / (until - from) as f32;
+        let phase = (self.tick - from) as f32 * 2.4;
+        Some([phase.cos() * strength * fade * 0.5, phase.sin() * strength * fade])
+    }
+
+    // Every cell of the rows about to clear throws sparks in its color, more of them and
+    // faster for a tetris.
+    fn spark_full_rows(&mut self) {
+        let rows = full_rows(&self.grid);
+        let (count, speed) = if rows.len() >= 4 * self.block.scale as usize { (5, 420.0) } else { (2, 240.0) };
+        for y in rows {
+            for (x, cell) in self.grid[y].iter().enumerate() {
+                if let Some(cell) = cell {
+                    let rect = cell_rect(x as f32, y as f32);
+                    let center = [rect.x + CELL_SIZE / 2.0, rect.y + CELL_SIZE / 2.0];
+                    let color = self.settings.piece_colors.color(cell.kind, cell.color);
+                    self.particles.burst(center, color, count, speed);
                 }
             }
         }
-        
-        for (y, row) in self.block.shape.iter().enumerate() {
-            for (x, &cell) in row.iter().enumerate() {
-                if cell {
-                    let rect = Rect::new(
-                        (self.block.x + x as i32) as f32 * CELL_SIZE,
-                        (self.block.y + y as i32) as f32 * CELL_SIZE,
-                        CELL_SIZE,
-                        CELL_SIZE,
-                    );
-                    let mesh = graphics::Mesh::new_rectangle(
-                        ctx,
-                        DrawMode::fill(),
-                        rect,
-                        self.block.color,
-                    )?;
-                    canvas.draw(&mesh, DrawParam::default());
-                }
+    }
+
+    fn use_item(&mut self, item: Item) {
+        match item {
+            Item::ClearBottom => {
+                self.grid.copy_within(..GRID_HEIGHT - 1, 1);
+                self.grid[0] = [None; GRID_WIDTH];
             }
+            Item::SlowGravity => self.effects.start(item, self.tick),
+            Item::ShuffleColors => shuffle_colors(&mut self.grid, &mut self.randomizer),
         }
-        
-        if self.game_over && self.death_count == 1 {
-            let screen_width = GRID_WIDTH as f32 * CELL_SIZE;
-            let screen_height = GRID_HEIGHT as f32 * CELL_SIZE;
-            let text = Text::new("Jogue mais uma vez para liberar um easter egg");
-            let text_pos = [
-                screen_width / 2.0 - 150.0,
-                screen_height / 2.0 + 100.0,
-            ];
-            canvas.draw(&text, DrawParam::default().dest(text_pos).color(Color::WHITE));
+        self.callout = Some((item.label().to_string(), self.tick + clock::ticks_from_ms(1500)));
+    }
+
+    fn speed(&self) -> Speed {
+        self.settings.speed_curve.speed(self.level, self.lines_cleared_total)
+    }
+
+    // Gravity right now, slowed down while the slow gravity item lasts.
+    fn gravity(&self) -> Gravity {
+        let gravity = self.speed().gravity;
+        if self.effects.active(Item::SlowGravity) {
+            gravity.scaled(1, items::SLOW_FACTOR)
+        } else {
+            gravity
         }
-        
-        canvas.finish(ctx)?;
+    }
+
+    // One tick of `gravity`: the piece falls the whole rows built up so far, as far as it can,
+    // and the rest carries over to the next tick. Returns the rows it fell.
+    fn pull(&mut self, gravity: Gravity) -> i32 {
+        let progress = self.fall_progress + gravity.rows;
+        let rows = ((progress / gravity.ticks) as i32).min(self.block.drop_distance(&self.grid));
+        self.fall(rows);
+        self.fall_progress = progress % gravity.ticks;
+        rows
+    }
+
+    fn is_spin(&self) -> bool {
+        if !self.last_rotated {
+            return false;
+        }
+        match self.block.kind {
+            PieceKind::T => self.block.t_corners(&self.grid, self.settings.rotation.system()) >= 3,
+            PieceKind::O => false,
+            _ => {
+                self.settings.spin_rules.for_mode(self.mode) == SpinRule::AllSpin && self.block.immobile(&self.grid)
+            }
+        }
+    }
+
+    fn announce_spin(&mut self, lines_cleared: u32) {
+        const NAMES: [&str; 4] = ["", " SINGLE", " DOUBLE", " TRIPLE"];
+        let lines = (lines_cleared as usize).min(3);
+        let piece = self.piece_name(self.block.kind).to_string();
+        self.callout = Some((format!("{}-SPIN{}", piece, NAMES[lines]), self.tick + clock::ticks_from_ms(1500)));
+        self.emit("spin", json!({ "piece": piece, "lines": lines_cleared, "score": self.score }));
+    }
+
+    // The clear emptied the whole board.
+    fn perfect_clear(&mut self, ctx: &mut Context, lines_cleared: u32) -> GameResult {
+        self.score += self.score_table.perfect_clear(lines_cleared, self.level);
+        self.perfect_clear_until = self.tick + clock::ticks_from_ms(2000);
+        let mut colors = self.palette().to_vec();
+        colors.extend([Color::WHITE, PINK, YELLOW]);
+        self.particles.confetti(Region::Board.rect(), &colors, 200);
+        self.audio.play(ctx, Sound::Clear, shop::sfx_pitch(&self.settings.sfx_pack) * 1.5)?;
+        self.emit("perfect_clear", json!({ "lines": lines_cleared, "score": self.score }));
         Ok(())
     }
 
-    fn key_down_event(&mut self, _ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
-        if self.freeze_timer.is_some() {
+    fn finish_run(&mut self, outcome: GameOutcome) {
+        let mut record = self.run_stats.to_record(self.mode, self.score, outcome);
+        self.emit("finish", json!({ "outcome": format!("{:?}", outcome).to_lowercase(), "score": self.score }));
+
+        if outcome != GameOutcome::Abandoned {
+            self.earned += shop::completion_reward(self.score);
+        }
+        let completed = self.progress.record_missions(&record);
+        for mission in &completed {
+            self.earned += mission.reward;
+        }
+        if let Some(mission) = completed.last() {
+            self.status = Some(format!("Missão concluída: {} (+{})", mission.goal.description(), mission.reward));
+        }
+        self.progress.lollipops += self.earned;
+        if self.progress.missions_done.iter().all(|&done| done) {
+            self.award(Achievement::WeeklySweep);
+        }
+        self.save_progress();
+
+        // Items change the stack outside the placements, so a replay of the run would drift
+        // from what happened.
+        if !self.replay.placements.is_empty() && !self.run_stats.items {
+            let name = format!("{}-{}.toml", record.timestamp, self.history.games.len());
+            match self.replay.save(&self.data_dir.join("replays").join(&name)) {
+                Ok(()) => record.replay = Some(name),
+                Err(e) => eprintln!("failed to save replay: {}", e),
+            }
+        }
+        self.history.games.push(record);
+        self.prune_replays();
+        self.save_history();
+        self.start_sync();
+    }
+
+    // Locks the active piece where it is, clears lines and spawns the next one.
+    fn lock_piece(&mut self, ctx: &mut Context) -> GameResult {
+        if self.mode == GameMode::Sandbox {
+            if self.undo_stack.len() >= UNDO_LIMIT {
+                self.undo_stack.remove(0);
+            }
+            self.undo_stack.push(self.snapshot());
+        }
+        let holes_before = count_holes(&self.grid);
+        let spin = self.is_spin();
+        let locked_out = self.block.above_skyline();
+        self.audio.cue(Sound::Lock);
+        self.place_block();
+        self.hold.unlock();
+        let place_time = self.run_stats.play_time - self.spawn_time;
+        self.run_stats.record_placement(self.block.kind, place_time);
+        let delay = clock::ticks_from_ms(self.settings.handling.line_clear_ms);
+        let rows = full_rows(&self.grid);
+        if delay > 0 && !rows.is_empty() {
+            self.clearing = Some(Clearing {
+                rows,
+                from: self.tick,
+                until: self.tick + delay,
+                spin,
+                holes_before,
+            });
             return Ok(());
         }
+        self.finish_lock(ctx, spin, locked_out, holes_before)
+    }
 
-        if let Some(keycode) = input.keycode {
-            match keycode {
-                KeyCode::Left => {
-                    if self.block.can_move(-1, 0, &self.grid) {
-                        self.block.x -= 1;
-                    }
-                }
-                KeyCode::Right => {
-                    if self.block.can_move(1, 0, &self.grid) {
-                        self.block.x += 1;
-                    }
-                }
-                KeyCode::Down => {
-                    if self.block.can_move(0, 1, &self.grid) {
-                        self.block.y += 1;
-                    }
-                }
-                KeyCode::Up => {
-                    self.block.rotate(&self.grid);
-                }
-                KeyCode::Space => {
-                    while self.block.can_move(0, 1, &self.grid) {
-                        self.block.y += 1;
-                    }
-                }
-                _ => {}
+    // Clears the rows the lock completed, once any line clear delay is over, and moves on to
+    // the next piece.
+    fn finish_lock(&mut self, ctx: &mut Context, spin: bool, locked_out: bool, holes_before: u32) -> GameResult {
+        let lines_cleared = self.clear_lines(ctx, spin)?;
+        if spin {
+            self.announce_spin(lines_cleared);
+        }
+        if count_holes(&self.grid) > holes_before {
+            self.suspect_lock = Some((self.block.kind, self.run_stats.play_time));
+        }
+        // Lock-out: the whole piece came to rest out of sight, above the board.
+        if locked_out && lines_cleared == 0 {
+            self.top_out(ctx)?;
+            if self.game_over {
+                return Ok(());
             }
         }
-        Ok(())
+        if lines_cleared == 0 {
+            self.lock_flash = Some((self.block.clone(), self.tick + LOCK_FLASH_TICKS));
+        }
+        if self.settings.clear_gravity == ClearGravity::Sticky && lines_cleared > 0 {
+            self.cascading = true;
+            return Ok(());
+        }
+        self.next_piece(ctx)
     }
-}
 
-fn main() -> GameResult {
-    let cb = ggez::ContextBuilder::new("lollypop", "cascade")
-        .window_setup(ggez::conf::WindowSetup::default().title("Lollypop Tetris"))
-        .window_mode(ggez::conf::WindowMode::default().dimensions(
-            GRID_WIDTH as f32 * CELL_SIZE,
-            GRID_HEIGHT as f32 * CELL_SIZE,
-        ))
-        .add_resource_path("resource");
+    // After a lock (and any cascade) settles: ends a finished sprint, or brings in the next
+    // piece, after the entry delay if there is one.
+    fn next_piece(&mut self, ctx: &mut Context) -> GameResult {
+        if self.mode == GameMode::Sprint && self.run_stats.lines() >= SPRINT_LINES {
+            self.complete_sprint(ctx);
+            return Ok(());
+        }
+        let are = clock::ticks_from_ms(self.settings.handling.are_ms);
+        if are > 0 {
+            self.spawn_at = Some(self.tick + are);
+            return Ok(());
+        }
+        self.enter_piece(ctx)
+    }
+
+    // The next piece appears, already turned if a rotation was held for it, then plays the
+    // drops pressed while it was on its way.
+    fn enter_piece(&mut self, ctx: &mut Context) -> GameResult {
+        self.spawn_block();
+        if self.irs_turns > 0 {
+            self.block.rotate(std::mem::take(&mut self.irs_turns), self.settings.rotation.system(), &self.grid);
+        }
+        self.instant_gravity();
+        self.check_game_over(ctx)?;
+        for action in std::mem::take(&mut self.input_buffer) {
+            if self.game_over {
+                break;
+            }
+            if self.piece_active() {
+                self.perform(ctx, action, false)?;
+            } else {
+                // A buffered hard drop started another entry delay; the rest waits for that piece.
+                self.input_buffer.push(action);
+            }
+        }
+        Ok(())
+    }
+
+    fn edit_cell(&mut self, x: f32, y: f32, fill: bool) {
+        let (col, row) = cell_at(x, y);
+        if (0..GRID_WIDTH as i32).contains(&col) && (0..VISIBLE_ROWS as i32).contains(&row) {
+            self.grid[row as usize + BUFFER_ROWS][col as usize] = fill.then_some(Cell::plain(Color::new(0.5, 0.5, 0.5, 1.0)));
+        }
+    }
+
+    // Block-out: a freshly spawned piece has no room.
+    fn check_game_over(&mut self, ctx: &mut Context) -> GameResult {
+        if self.block.can_move(0, 0, &self.grid) {
+            return Ok(());
+        }
+        self.top_out(ctx)
+    }
+
+    // Sandbox boards just start over; anywhere else the run is lost.
+    fn top_out(&mut self, ctx: &mut Context) -> GameResult {
+        if self.mode == GameMode::Sandbox {
+            self.grid = EMPTY_GRID;
+            return Ok(());
+        }
+        self.game_over = true;
+        self.death_count += 1;
+        self.audio.play(ctx, Sound::Death, shop::sfx_pitch(&self.settings.sfx_pack))?;
+        self.freeze_timer = Some(Duration::from_secs(5));
+        self.freeze_start = Some(ctx.time.time_since_start());
+        self.audio.play(ctx, Sound::Start, 1.0)?;
+
+        self.finish_run(GameOutcome::ToppedOut);
+        let reached = self.progress.record_death();
+        self.save_progress();
+        if let Some(milestone) = reached {
+            self.status = Some(format!("{} mortes: {} desbloqueado!", milestone.deaths, milestone.description));
+        }
+
+        let level = self.settings.scare_intensity();
+        self.scares.check(ctx, Trigger::Death, self.death_count, level, &self.audio)?;
+        Ok(())
+    }
+
+    fn export_csv(&mut self) {
+        let path = self.data_dir.join("stats.csv");
+        self.status = Some(match self.history.export_csv(&path) {
+            Ok(()) => format!("CSV exportado ({} jogos) para {}", self.history.games.len(), path.display()),
+            Err(e) => format!("Falha ao exportar CSV: {}", e),
+        });
+    }
+
+    // Only the most recent games keep their replay file around; older records stay in the
+    // history (for stats and CSV) but lose the link.
+    fn prune_replays(&mut self) {
+        let replays_dir = self.data_dir.join("replays");
+        let old = self
+            .history
+            .games
+            .iter_mut()
+            .rev()
+            .filter(|game| game.replay.is_some())
+            .skip(HISTORY_LIMIT);
+        for game in old {
+            if let Some(name) = game.replay.take() {
+                let _ = std::fs::remove_file(replays_dir.join(name));
+            }
+        }
+    }
+
+    // Sub-pages opened from the title or results menus return to whichever one opened them.
+    fn open(&mut self, screen: Screen) {
+        self.status = None;
+        self.back.push(self.screen);
+        self.screen = screen;
+    }
+
+    fn close(&mut self) {
+        self.status = None;
+        self.screen = self.back.pop().unwrap_or(Screen::Title);
+    }
+
+    fn start_run(&mut self, mode: GameMode) {
+        let seed = self.next_seed.take().unwrap_or_else(|| rand::thread_rng().gen());
+        self.start_seeded(mode, seed);
+    }
+
+    fn start_seeded(&mut self, mode: GameMode, seed: u64) {
+        self.mode = mode;
+        self.seed = seed;
+        self.randomizer.reseed(seed);
+        self.reset();
+        self.emit("start", json!({ "mode": mode.name() }));
+    }
+
+    // Scripted runs are classic games on the script's seed.
+    fn start_tas(&mut self, tas: TasPlayer) {
+        self.start_seeded(GameMode::Classic, tas.seed());
+        self.tas = Some(tas);
+        self.screen = Screen::Playing;
+    }
+
+    // Throws away the run in progress, or the death freeze after one, for a fresh run in the
+    // same mode; `same_seed` deals the same pieces again.
+    fn quick_restart(&mut self, ctx: &mut Context, same_seed: bool) -> GameResult {
+        if !self.game_over {
+            self.abandon_run();
+        }
+        self.audio.stop(ctx, Sound::Start)?;
+        self.freeze_timer = None;
+        self.freeze_start = None;
+        let seed = if same_seed { self.seed } else { rand::thread_rng().gen() };
+        self.start_seeded(self.mode, seed);
+        Ok(())
+    }
+
+    // Sandbox runs keep no record.
+    fn abandon_run(&mut self) {
+        if self.mode != GameMode::Sandbox && self.run_stats.pieces > 0 {
+            self.finish_run(GameOutcome::Abandoned);
+        }
+    }
+
+    fn save_progress(&self) {
+        if let Err(e) = self.progress.save(&self.data_dir.join("progress.toml")) {
+            eprintln!("failed to save progress: {}", e);
+        }
+    }
+
+    fn award(&mut self, achievement: Achievement) {
+        if self.progress.award(achievement) {
+            self.save_progress();
+            self.status = Some(format!("Conquista: {}! Tema {} desbloqueado", achievement.name(), achievement.theme()));
+        }
+    }
+
+    fn complete_sprint(&mut self, ctx: &Context) {
+        self.game_over = true;
+        self.screen = Screen::Results;
+        self.idle_since = ctx.time.time_since_start();
+        self.finish_run(GameOutcome::Completed);
+        if self.run_stats.play_time < Duration::from_secs(120) {
+            self.award(Achievement::SprintUnderTwoMinutes);
+        }
+    }
+
+    // The built-in themes, then the ones from files.
+    fn theme_names(&self) -> Vec<&str> {
+        let files = self.theme_files.iter().map(|theme| theme.name.as_str());
+        config::THEMES.iter().map(|(name, _)| *name).chain(files).collect()
+    }
+
+    // The theme in use, if it came from a file.
+    fn theme_file(&self) -> Option<&ThemeFile> {
+        self.theme_files.iter().find(|theme| theme.name == self.settings.theme)
+    }
+
+    // The sound pack picked in the audio settings, if any.
+    fn sound_pack(&self) -> Option<&SoundPack> {
+        self.sound_packs.iter().find(|pack| pack.name == self.settings.sound_folder)
+    }
+
+    fn next_sound_pack(&mut self) {
+        let current = self.sound_packs.iter().position(|pack| pack.name == self.settings.sound_folder);
+        let next = match current {
+            None => self.sound_packs.first(),
+            Some(index) => self.sound_packs.get(index + 1),
+        };
+        self.settings.sound_folder = next.map(|pack| pack.name.clone()).unwrap_or_default();
+    }
+
+    fn palette(&self) -> [Color; 2] {
+        self.theme_file().map_or_else(|| self.settings.palette(), ThemeFile::palette)
+    }
+
+    fn open_themes(&mut self) {
+        let names = self.theme_names();
+        let (selected, len) = (names.iter().position(|name| *name == self.settings.theme).unwrap_or(0), names.len());
+        self.theme_list.selected = selected;
+        self.theme_list.set_len(len);
+        self.open(Screen::Themes);
+    }
+
+    fn pick_theme(&mut self, index: usize) {
+        let name = self.theme_names()[index].to_string();
+        if !self.progress.theme_available(&name) {
+            self.status = Some("Tema bloqueado".to_string());
+            return;
+        }
+        self.settings.theme = name;
+        self.save_settings();
+        self.refresh_settings_menu();
+        self.close();
+    }
+
+    // Points the block skin, backdrop image and font at the theme's files, or back at the
+    // usual ones for a built-in theme. Sounds go through the asset loader at startup, so
+    // they're reloaded separately when the theme changes.
+    fn apply_theme(&mut self, ctx: &mut Context) {
+        let (skin, backdrop, font) = match self.theme_file() {
+            Some(theme) => (theme.skin.clone(), theme.backdrop.clone(), theme.font.clone()),
+            None => (None, None, None),
+        };
+        self.cells.set_theme_skin(ctx, skin.as_deref());
+        self.backdrop.set_image(ctx, backdrop.as_deref());
+        let font = font.or_else(|| ctx.fs.exists(FONT_PATH).then(|| FONT_PATH.to_string()));
+        let font = font.and_then(|path| match graphics::FontData::from_path(ctx, &path) {
+            Ok(data) => {
+                ctx.gfx.add_font(&path, data);
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("failed to load font {}: {}", path, e);
+                None
+            }
+        });
+        self.text.set_font(font);
+    }
+
+    fn reload_sounds(&mut self, ctx: &mut Context) {
+        for sound in Sound::ALL {
+            let path = sound_path(self.sound_pack(), self.theme_file(), sound);
+            let data = path.as_ref().and_then(|path| match audio::SoundData::new(ctx, path) {
+                Ok(data) => Some(data),
+                Err(e) => {
+                    eprintln!("failed to load {}: {}", path, e);
+                    None
+                }
+            });
+            if let Err(e) = self.audio.set_source(ctx, sound, data) {
+                eprintln!("failed to decode {}: {}", path.unwrap_or_default(), e);
+            }
+        }
+    }
+
+    fn apply_volume(&mut self) {
+        let settings = &self.settings;
+        self.audio.set_volumes(settings.music_volume, settings.sfx_volume, settings.muted);
+    }
+
+    fn toggle_mute(&mut self) {
+        self.settings.muted = !self.settings.muted;
+        self.save_settings();
+        self.apply_volume();
+        let label = if self.settings.muted { "Som desligado" } else { "Som ligado" }.to_string();
+        if matches!(self.screen, Screen::Countdown | Screen::Playing | Screen::Paused) {
+            self.callout = Some((label, self.tick + clock::ticks_from_ms(1000)));
+        } else {
+            self.status = Some(label);
+        }
+    }
+
+    fn open_controls(&mut self) {
+        self.rebinding = None;
+        self.controls_list.selected = 0;
+        self.controls_list.set_len(Action::ALL.len() + CONTROL_OPTIONS + ControlPreset::ALL.len());
+        self.open(Screen::Controls);
+    }
+
+    // After the actions come `CONTROL_OPTIONS` rows of options, then the presets.
+    fn pick_control(&mut self, index: usize) {
+        if let Some(&action) = Action::ALL.get(index) {
+            self.rebinding = Some(action);
+            return;
+        }
+        match index - Action::ALL.len() {
+            0 => self.settings.handling.soft_drop = self.settings.handling.soft_drop.next(),
+            1 => self.settings.pointer_controls = !self.settings.pointer_controls,
+            2 => self.settings.next_stick_deadzone(),
+            3 => self.settings.next_stick_bias(),
+            4 => self.settings.next_line_clear(),
+            option => {
+                let preset = ControlPreset::ALL[option - CONTROL_OPTIONS];
+                preset.apply(&mut self.settings);
+                self.status = Some(format!("Controles: {}", preset.label()));
+            }
+        }
+        self.save_settings();
+    }
+
+    fn open_display(&mut self) {
+        self.display_list.selected = 0;
+        self.display_list.set_len(DisplayOption::ALL.len());
+        self.open(Screen::Display);
+    }
+
+    fn pick_display(&mut self, ctx: &mut Context, index: usize) {
+        match DisplayOption::ALL[index] {
+            DisplayOption::BagPreview => self.settings.bag_preview = !self.settings.bag_preview,
+            DisplayOption::BlockStyle => self.settings.block_style = self.settings.block_style.next(),
+            DisplayOption::Ghost => self.settings.ghost = self.settings.ghost.next(),
+            DisplayOption::GhostOpacity => self.settings.next_ghost_opacity(),
+            DisplayOption::GridOpacity => self.settings.next_grid_opacity(),
+            DisplayOption::Shake => self.settings.next_shake(),
+            DisplayOption::Fullscreen => return self.toggle_fullscreen(ctx),
+            DisplayOption::Backdrop => self.settings.backdrop = self.settings.backdrop.next(),
+            DisplayOption::BackdropBrightness => self.settings.next_backdrop_brightness(),
+            DisplayOption::Vsync => self.settings.vsync = !self.settings.vsync,
+            DisplayOption::FpsCap => self.settings.next_fps_cap(),
+            DisplayOption::ShowFps => self.settings.show_fps = !self.settings.show_fps,
+            DisplayOption::SmoothGravity => self.settings.smooth_gravity = !self.settings.smooth_gravity,
+            DisplayOption::DangerWarning => self.settings.danger_warning = !self.settings.danger_warning,
+            DisplayOption::Countdown => self.settings.countdown = !self.settings.countdown,
+        }
+        self.save_settings();
+    }
+
+    fn toggle_fullscreen(&mut self, ctx: &mut Context) {
+        self.settings.fullscreen = !self.settings.fullscreen;
+        self.save_settings();
+        self.apply_fullscreen(ctx);
+    }
+
+    // Puts the window in or out of fullscreen to match the settings. The overlay always stays
+    // a window, since it's meant to sit over something else. Either way the window resizes,
+    // and the viewport follows.
+    fn apply_fullscreen(&self, ctx: &mut Context) {
+        let mode = if self.settings.fullscreen && self.overlay == Overlay::Off {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Windowed
+        };
+        if let Err(e) = ctx.gfx.set_fullscreen(mode) {
+            eprintln!("failed to switch fullscreen: {}", e);
+        }
+    }
+
+    fn draw_display(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "VISUAL", 50.0, 28.0, PINK);
+        self.text.draw_centered(canvas, "Enter: mudar  Esc: voltar", 85.0, 14.0, YELLOW);
+        let labels: Vec<String> = DisplayOption::ALL
+            .iter()
+            .map(|option| match option {
+                DisplayOption::BagPreview => {
+                    format!("Mostrar saco\n{}", if self.settings.bag_preview { "sim" } else { "não" })
+                }
+                DisplayOption::BlockStyle => format!("Blocos\n{}", self.settings.block_style.label()),
+                DisplayOption::Ghost => format!("Fantasma\n{}", self.settings.ghost.label()),
+                DisplayOption::GhostOpacity => format!("Opacidade do fantasma\n{}%", self.settings.ghost_opacity),
+                DisplayOption::GridOpacity => match self.settings.grid_opacity {
+                    0 => "Grade\nnão".to_string(),
+                    opacity => format!("Grade\n{}%", opacity),
+                },
+                DisplayOption::Shake => match self.settings.shake {
+                    0 => "Tremor da tela\nnão".to_string(),
+                    shake => format!("Tremor da tela\n{}%", shake),
+                },
+                DisplayOption::Fullscreen => {
+                    format!("Tela cheia\n{}", if self.settings.fullscreen { "sim" } else { "não" })
+                }
+                DisplayOption::Backdrop => format!("Fundo da partida\n{}", self.settings.backdrop.label()),
+                DisplayOption::BackdropBrightness => format!("Brilho do fundo\n{}%", self.settings.backdrop_brightness),
+                DisplayOption::Vsync => {
+                    let restart = if self.settings.vsync != self.vsync { " (ao reiniciar)" } else { "" };
+                    format!("Sincronia vertical\n{}{}", if self.settings.vsync { "sim" } else { "não" }, restart)
+                }
+                DisplayOption::FpsCap => match self.settings.fps_cap {
+                    0 => "Limite de FPS\nnão".to_string(),
+                    fps => format!("Limite de FPS\n{}", fps),
+                },
+                DisplayOption::ShowFps => {
+                    format!("Mostrar FPS\n{}", if self.settings.show_fps { "sim" } else { "não" })
+                }
+                DisplayOption::SmoothGravity => {
+                    format!("Queda suave\n{}", if self.settings.smooth_gravity { "sim" } else { "não" })
+                }
+                DisplayOption::DangerWarning => {
+                    format!("Aviso de perigo\n{}", if self.settings.danger_warning { "sim" } else { "não" })
+                }
+                DisplayOption::Countdown => {
+                    format!("Contagem regressiva\n{}", if self.settings.countdown { "sim" } else { "não" })
+                }
+            })
+            .collect();
+        self.display_list.draw(canvas, &self.text, &labels);
+    }
+
+    fn open_accessibility(&mut self) {
+        self.access_list.selected = 0;
+        self.access_list.set_len(AccessOption::ALL.len());
+        self.open(Screen::Accessibility);
+    }
+
+    fn pick_accessibility(&mut self, index: usize) {
+        match AccessOption::ALL[index] {
+            AccessOption::PieceColors => self.settings.piece_colors = self.settings.piece_colors.next(),
+            AccessOption::CellPatterns => self.settings.cell_patterns = !self.settings.cell_patterns,
+        }
+        self.save_settings();
+    }
+
+    fn draw_accessibility(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "ACESSIBILIDADE", 50.0, 28.0, PINK);
+        self.text.draw_centered(canvas, "Enter: mudar  Esc: voltar", 85.0, 14.0, YELLOW);
+        let labels: Vec<String> = AccessOption::ALL
+            .iter()
+            .map(|option| match option {
+                AccessOption::PieceColors => format!("Cores das peças\n{}", self.settings.piece_colors.label()),
+                AccessOption::CellPatterns => {
+                    format!("Padrões nas peças\n{}", if self.settings.cell_patterns { "sim" } else { "não" })
+                }
+            })
+            .collect();
+        self.access_list.draw(canvas, &self.text, &labels);
+    }
+
+    fn open_audio(&mut self) {
+        self.audio_list.selected = 0;
+        self.audio_list.set_len(AudioOption::ALL.len());
+        self.open(Screen::Audio);
+    }
+
+    fn pick_audio(&mut self, index: usize) {
+        match AudioOption::ALL[index] {
+            AudioOption::Muted => self.settings.muted = !self.settings.muted,
+            AudioOption::MusicVolume => self.settings.next_music_volume(),
+            AudioOption::SfxVolume => self.settings.next_sfx_volume(),
+            AudioOption::MusicRamp => self.settings.music_ramp = !self.settings.music_ramp,
+            AudioOption::MusicLayers => self.settings.music_layers = !self.settings.music_layers,
+            AudioOption::SoundPack => self.next_sound_pack(),
+        }
+        self.save_settings();
+        self.apply_volume();
+    }
+
+    fn draw_audio(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "ÁUDIO", 50.0, 28.0, PINK);
+        self.text.draw_centered(canvas, "Enter: mudar  M: mudo  Esc: voltar", 85.0, 14.0, YELLOW);
+        let labels: Vec<String> = AudioOption::ALL
+            .iter()
+            .map(|option| match option {
+                AudioOption::Muted => format!("Som\n{}", if self.settings.muted { "mudo" } else { "ligado" }),
+                AudioOption::MusicVolume => format!("Música\n{}%", self.settings.music_volume),
+                AudioOption::SfxVolume => format!("Efeitos\n{}%", self.settings.sfx_volume),
+                AudioOption::MusicRamp => {
+                    format!("Música acelera\n{}", if self.settings.music_ramp { "sim" } else { "não" })
+                }
+                AudioOption::MusicLayers => {
+                    format!("Camadas na música\n{}", if self.settings.music_layers { "sim" } else { "não" })
+                }
+                AudioOption::SoundPack => {
+                    format!("Pasta de sons\n{}", self.sound_pack().map_or("padrão", |pack| pack.name.as_str()))
+                }
+            })
+            .collect();
+        self.audio_list.draw(canvas, &self.text, &labels);
+    }
+
+    fn held(&self, ctx: &Context, action: Action) -> bool {
+        self.settings.keybinds.keys(action).iter().any(|&key| ctx.keyboard.is_key_pressed(key))
+    }
+
+    // Delete on an action's row unbinds all its keys.
+    fn unbind_control(&mut self) {
+        if let Some(&action) = Action::ALL.get(self.controls_list.selected) {
+            self.settings.keybinds.clear(action);
+            self.save_settings();
+        }
+    }
+
+    fn rebind(&mut self, keycode: KeyCode) {
+        let Some(action) = self.rebinding.take() else {
+            return;
+        };
+        // Esc cancels, and the console key can't be taken over.
+        if matches!(keycode, KeyCode::Escape | KeyCode::Grave) {
+            return;
+        }
+        self.settings.keybinds.bind(action, keycode);
+        self.save_settings();
+        self.status = None;
+    }
+
+    fn draw_controls(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "CONTROLES", 50.0, 28.0, PINK);
+        let hint = match self.rebinding {
+            Some(action) => format!("Aperte uma tecla para {}  Esc: cancelar", action.label()),
+            None => "Enter: adicionar tecla  Delete: limpar  Esc: voltar".to_string(),
+        };
+        self.text.draw_centered(canvas, &hint, 85.0, 14.0, YELLOW);
+        let mut labels: Vec<String> = Action::ALL
+            .iter()
+            .map(|&action| match self.rebinding {
+                Some(waiting) if waiting == action => format!("{}\n...", action.label()),
+                _ => format!("{}\n{}", action.label(), self.settings.keybinds.label(action)),
+            })
+            .collect();
+        labels.push(format!("Descida suave\n{}", self.settings.handling.soft_drop.label()));
+        let pointer = if self.settings.pointer_controls { "sim" } else { "não" };
+        labels.push(format!("Mouse e toque\n{}", pointer));
+        labels.push(format!("Zona morta do analógico\n{}%", self.settings.stick_deadzone));
+        labels.push(format!("Viés horizontal do analógico\n{}%", self.settings.stick_bias));
+        labels.push(match self.settings.handling.line_clear_ms {
+            0 => "Atraso de linha\nnão".to_string(),
+            ms => format!("Atraso de linha\n{} ms", ms),
+        });
+        labels.extend(ControlPreset::ALL.iter().map(|preset| format!("Predefinição\n{}", preset.label())));
+        self.controls_list.draw(canvas, &self.text, &labels);
+        if let Some(status) = &self.status {
+            self.text.draw_centered(canvas, status, 565.0, 14.0, Color::WHITE);
+        }
+    }
+
+    // A side panel's backdrop and heading. Returns where the panel is.
+    fn draw_panel(&self, canvas: &mut graphics::Canvas, region: Region, title: &str) -> Rect {
+        let rect = region.rect();
+        ui::fill_rect(canvas, rect, Color::new(0.0, 0.0, 0.0, 0.5));
+        let style = text::Style::new(14.0, YELLOW, text::Align::Center);
+        self.text.draw(canvas, title, [rect.x + rect.w / 2.0, rect.y + 12.0], style);
+        rect
+    }
+
+    // What's left of the current 7-bag, in fixed piece order so it reads as contents, not a queue.
+    fn draw_bag(&self, canvas: &mut graphics::Canvas) {
+        let panel = self.draw_panel(canvas, Region::Bag, "SACO");
+        // The queue runs ahead of the piece in play, so what's left of its bag is partly dealt
+        // already.
+        let bag = self.block.bag;
+        let mut remaining: Vec<PieceKind> =
+            self.queue.iter().filter(|block| block.bag == bag).map(|block| block.kind).collect();
+        if self.randomizer.bag() == bag {
+            remaining.extend_from_slice(self.randomizer.remaining_bag());
+        }
+        remaining.sort_by_key(|kind| kind.index());
+        let row = (panel.h - 24.0) / 7.0;
+        for (i, kind) in remaining.iter().enumerate() {
+            let area = Rect::new(panel.x, panel.y + 24.0 + i as f32 * row, panel.w, row);
+            let shape = self.settings.rotation.system().shape(*kind, 0);
+            draw_mini_piece(canvas, &shape, area, Color::new(1.0, 1.0, 1.0, 0.7));
+        }
+    }
+
+    // Each hold slot, one under the other. A slot already used for this piece is dimmed.
+    fn draw_hold(&self, canvas: &mut graphics::Canvas) {
+        if self.hold.slots().next().is_none() {
+            return;
+        }
+        let panel = self.draw_panel(canvas, Region::Hold, "GUARDAR");
+        for (i, (piece, available)) in self.hold.slots().enumerate() {
+            let area = Rect::new(panel.x + 4.0, panel.y + 24.0 + i as f32 * 60.0, panel.w - 8.0, 56.0);
+            ui::fill_rect(canvas, area, Color::new(0.0, 0.0, 0.0, 0.4));
+            if let Some(piece) = piece {
+                let alpha = if available { 0.9 } else { 0.3 };
+                let color = self.block_color(piece);
+                let color = Color::new(color.r, color.g, color.b, alpha);
+                draw_mini_piece(canvas, &self.spawn_shape(piece.kind), area, color);
+            }
+        }
+    }
+
+    fn draw_next(&self, canvas: &mut graphics::Canvas) {
+        let panel = self.draw_panel(canvas, Region::Next, "PRÓXIMAS");
+        let row = (panel.h - 24.0) / NEXT_PIECES as f32;
+        for (i, block) in self.queue.iter().take(NEXT_PIECES).enumerate() {
+            let area = Rect::new(panel.x, panel.y + 24.0 + i as f32 * row, panel.w, row);
+            draw_mini_piece(canvas, &block.shape, area, self.block_color(block));
+        }
+    }
+
+    // Score up top, then the run's numbers with their labels on the left and values on the
+    // right.
+    fn draw_scoreboard(&self, canvas: &mut graphics::Canvas) {
+        let panel = self.draw_panel(canvas, Region::Stats, "PONTOS");
+        let center = panel.x + panel.w / 2.0;
+        let score = text::Style::new(22.0, Color::WHITE, text::Align::Center);
+        self.text.draw(canvas, &self.score.to_string(), [center, panel.y + 40.0], score);
+        let lines = if self.mode == GameMode::Sprint {
+            format!("{}/{}", self.run_stats.lines(), SPRINT_LINES)
+        } else {
+            self.lines_cleared_total.to_string()
+        };
+        let mut rows = vec![
+            ("Nível", self.level.to_string()),
+            ("Linhas", lines),
+            ("Meta", (self.settings.level_goal.goal(self.level) - self.goal_progress).to_string()),
+            ("KPP", format!("{:.2}", self.run_stats.kpp())),
+            ("KPS", format!("{:.2}", self.run_stats.kps())),
+            ("Sem I", self.run_stats.drought.to_string()),
+        ];
+        if self.mode == GameMode::Sprint {
+            rows.push(("Tempo", format!("{:.1}s", self.run_stats.play_time.as_secs_f32())));
+        }
+        let labels: Vec<&str> = rows.iter().map(|(label, _)| *label).collect();
+        let values: Vec<&str> = rows.iter().map(|(_, value)| value.as_str()).collect();
+        self.text.draw(canvas, &labels.join("\n"), [panel.x + 6.0, panel.y + 64.0], text::HUD);
+        self.text.draw(canvas, &values.join("\n"), [panel.right() - 6.0, panel.y + 64.0], text::HUD_RIGHT);
+    }
+
+    // The running combo, and whether the next difficult clear scores back-to-back.
+    fn draw_combo(&self, canvas: &mut graphics::Canvas) {
+        let panel = self.draw_panel(canvas, Region::Combo, "COMBO");
+        let center = panel.x + panel.w / 2.0;
+        let (combo, color) = if self.combo >= 2 {
+            (format!("x{}", self.combo), PINK)
+        } else {
+            ("-".to_string(), Color::new(1.0, 1.0, 1.0, 0.4))
+        };
+        self.text.draw(canvas, &combo, [center, panel.y + 36.0], text::Style::new(22.0, color, text::Align::Center));
+        if self.back_to_back {
+            self.text.draw(canvas, "B2B", [center, panel.y + 58.0], text::Style::new(14.0, YELLOW, text::Align::Center));
+        }
+    }
+
+    fn block_color(&self, block: &Block) -> Color {
+        self.settings.piece_colors.color(Some(block.kind), block.color)
+    }
+
+    fn skin(&self) -> Skin {
+        shop::skin(&self.settings.skin)
+    }
+
+    fn open_shop(&mut self) {
+        self.shop_list.selected = 0;
+        self.shop_list.set_len(shop::ITEMS.len());
+        self.open(Screen::Shop);
+    }
+
+    fn equipped(&self, item: &shop::ShopItem) -> bool {
+        let current = match item.category() {
+            shop::Category::Skin => &self.settings.skin,
+            shop::Category::Background => &self.settings.background,
+            shop::Category::SfxPack => &self.settings.sfx_pack,
+        };
+        current == item.id
+    }
+
+    // Buying an item equips it straight away; owned items are just equipped.
+    fn shop_choice(&mut self, index: usize) {
+        let item = &shop::ITEMS[index];
+        if !self.progress.owns(item) {
+            if !self.progress.buy(item) {
+                self.status = Some(format!("Faltam {} pirulitos", item.price - self.progress.lollipops));
+                return;
+            }
+            self.save_progress();
+        }
+        let slot = match item.category() {
+            shop::Category::Skin => &mut self.settings.skin,
+            shop::Category::Background => &mut self.settings.background,
+            shop::Category::SfxPack => &mut self.settings.sfx_pack,
+        };
+        *slot = item.id.to_string();
+        self.save_settings();
+        self.status = Some(format!("{} equipado", item.id));
+    }
+
+    fn draw_shop(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "LOJA", 40.0, 28.0, PINK);
+        self.text.draw_centered(canvas, &format!("{} pirulitos", self.progress.lollipops), 75.0, 16.0, YELLOW);
+        let labels: Vec<String> = shop::ITEMS
+            .iter()
+            .map(|item| {
+                let state = if self.equipped(item) {
+                    "Equipado".to_string()
+                } else if self.progress.owns(item) {
+                    "Comprado".to_string()
+                } else {
+                    format!("{} pirulitos", item.price)
+                };
+                format!("{} ({})\n{}", item.id, item.category().label(), state)
+            })
+            .collect();
+        let locked: Vec<bool> = shop::ITEMS
+            .iter()
+            .map(|item| !self.progress.owns(item) && self.progress.lollipops < item.price)
+            .collect();
+        self.shop_list.draw_rows(canvas, &self.text, &labels, &locked);
+        let status = self.status.as_deref().unwrap_or("Enter: comprar/equipar  Esc: voltar");
+        self.text.draw_centered(canvas, status, 565.0, 14.0, Color::WHITE);
+    }
+
+    fn draw_missions(&self, canvas: &mut graphics::Canvas) {
+        let now = stats::unix_now();
+        self.text.draw_centered(canvas, "MISSÕES DA SEMANA", 60.0, 24.0, PINK);
+        self.text.draw_centered(canvas, &format!("Renovam em {} dias", missions::days_left(now)), 95.0, 14.0, YELLOW);
+        for (i, mission) in missions::weekly(self.progress.mission_week).iter().enumerate() {
+            let y = 150.0 + i as f32 * 110.0;
+            let target = mission.goal.target();
+            let progress = self.progress.mission_progress[i].min(target);
+            let done = self.progress.missions_done[i];
+            let color = if done { PINK } else { Color::WHITE };
+            self.text.draw_centered(canvas, &mission.goal.description(), y, 18.0, color);
+            let bar = Rect::new(40.0, y + 30.0, SCREEN_WIDTH - 80.0, 12.0);
+            ui::fill_rect(canvas, bar, Color::new(0.2, 0.2, 0.2, 1.0));
+            if progress > 0 {
+                let filled = Rect::new(bar.x, bar.y, bar.w * progress as f32 / target as f32, bar.h);
+                ui::fill_rect(canvas, filled, PINK);
+            }
+            let detail = if done {
+                format!("Concluída! +{} pirulitos", mission.reward)
+            } else {
+                format!("{}/{}  Prêmio: {} pirulitos", progress, target, mission.reward)
+            };
+            self.text.draw_centered(canvas, &detail, y + 52.0, 14.0, YELLOW);
+        }
+        self.text.draw_centered(canvas, "Esc: voltar", 580.0, 14.0, YELLOW);
+    }
+
+    fn draw_themes(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "TEMAS", 50.0, 28.0, PINK);
+        self.text.draw_centered(canvas, "Enter: escolher  Esc: voltar", 85.0, 14.0, YELLOW);
+        let mut labels = Vec::new();
+        let mut locked = Vec::new();
+        for name in &self.theme_names() {
+            let available = self.progress.theme_available(name);
+            let detail = match progress::theme_requirement(name) {
+                Some(requirement) if !available => format!("Bloqueado: {}", requirement.label()),
+                _ if *name == self.settings.theme => "Em uso".to_string(),
+                _ => String::new(),
+            };
+            labels.push(format!("{}\n{}", name, detail));
+            locked.push(!available);
+        }
+        self.theme_list.draw_rows(canvas, &self.text, &labels, &locked);
+        if let Some(status) = &self.status {
+            self.text.draw_centered(canvas, status, 565.0, 14.0, Color::WHITE);
+        }
+    }
+
+    fn go_to_title(&mut self, ctx: &mut Context) -> GameResult {
+        if self.screen == Screen::Paused {
+            self.abandon_run();
+        }
+        self.audio.stop(ctx, Sound::Start)?;
+        self.status = None;
+        self.screen = Screen::Title;
+        self.back.clear();
+        self.idle_since = ctx.time.time_since_start();
+        Ok(())
+    }
+
+    fn note_activity(&mut self, ctx: &Context) {
+        self.idle_since = ctx.time.time_since_start();
+        self.attract = None;
+    }
+
+    fn start_attract(&mut self) {
+        let replays: Vec<String> = self.recent_games().filter_map(|game| game.replay.clone()).collect();
+        if replays.is_empty() {
+            return;
+        }
+        let name = &replays[rand::thread_rng().gen_range(0..replays.len())];
+        if let Ok(replay) = Replay::load(&self.data_dir.join("replays").join(name)) {
+            self.attract = Some(ReplayPlayer::new(replay));
+        }
+    }
+
+    fn update_idle(&mut self, ctx: &mut Context) -> GameResult {
+        let idle = ctx.time.time_since_start() - self.idle_since;
+        match self.screen {
+            Screen::Results if idle >= MENU_IDLE_TIMEOUT => self.go_to_title(ctx)?,
+            Screen::Paused if idle >= MENU_IDLE_TIMEOUT && self.pause_reason == PauseReason::Idle => self.go_to_title(ctx)?,
+            Screen::Title if idle >= ATTRACT_DELAY => {
+                let finished = self.attract.as_ref().is_none_or(|player| player.finished());
+                if finished {
+                    self.start_attract();
+                }
+                if let Some(player) = &mut self.attract {
+                    // Demo playback runs at double speed so it doesn't drag.
+                    player.update(ctx.time.delta() * 2);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn unlock_secret(&mut self, secret: &secret::Secret) {
+        if self.progress.unlock(secret.theme) {
+            self.save_progress();
+        }
+        self.settings.theme = secret.theme.to_string();
+        self.save_settings();
+        self.refresh_settings_menu();
+        self.status = Some(format!("{}! Tema {} ativado", secret.name, secret.theme));
+    }
+
+    fn draw_title(&self, canvas: &mut graphics::Canvas) {
+        if let Some(player) = &self.attract {
+            draw_grid(&self.cells, &player.grid, &self.settings);
+            self.cells.flush(canvas);
+            ui::fill_rect(canvas, Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT), Color::new(0.0, 0.0, 0.0, 0.6));
+        }
+        self.text.draw_centered(canvas, "LOLLYPOP", 50.0, 44.0, PINK);
+        self.text.draw_centered(canvas, "TETRIS", 95.0, 32.0, YELLOW);
+        self.title_menu.draw(canvas, &self.text);
+        if let Some(status) = &self.status {
+            self.text.draw_centered(canvas, status, 565.0, 14.0, YELLOW);
+        }
+        if self.attract.is_some() {
+            self.text.draw_centered(canvas, "DEMO", 585.0, 12.0, Color::WHITE);
+        }
+        if let Some(loader) = &self.loader {
+            let bar = Rect::new(60.0, 548.0, SCREEN_WIDTH - 120.0, 6.0);
+            ui::fill_rect(canvas, bar, Color::new(0.2, 0.2, 0.2, 1.0));
+            ui::fill_rect(canvas, Rect::new(bar.x, bar.y, bar.w * loader.progress(), bar.h), PINK);
+            self.text.draw_centered(canvas, "Carregando...", 528.0, 12.0, Color::WHITE);
+        }
+    }
+
+    fn open_history(&mut self) {
+        self.history_list.selected = 0;
+        self.history_list.set_len(self.history.games.len().min(HISTORY_LIMIT));
+        self.open(Screen::History);
+    }
+
+    // The history page lists the most recent games first.
+    fn recent_games(&self) -> impl Iterator<Item = &stats::GameRecord> {
+        self.history.games.iter().rev().take(HISTORY_LIMIT)
+    }
+
+    fn open_replay(&mut self, index: usize) {
+        let Some(game) = self.recent_games().nth(index) else {
+            return;
+        };
+        let Some(name) = game.replay.clone() else {
+            let reason = if game.items { "Jogos com itens não salvam replay" } else { "Este jogo não tem replay salvo" };
+            self.status = Some(reason.to_string());
+            return;
+        };
+        match Replay::load(&self.data_dir.join("replays").join(name)) {
+            Ok(replay) => {
+                self.replay_player = Some(ReplayPlayer::new(replay));
+                self.screen = Screen::Replay;
+            }
+            Err(e) => self.status = Some(format!("Falha ao abrir replay: {}", e)),
+        }
+    }
+
+    fn draw_history(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "HISTÓRICO", 50.0, 28.0, PINK);
+        self.text.draw_centered(canvas, "Enter: ver replay  Esc: voltar", 85.0, 14.0, YELLOW);
+        let labels: Vec<String> = self
+            .recent_games()
+            .map(|game| {
+                let secs = game.duration_ms / 1000;
+                format!(
+                    "{}  {}\n{} pts  {}:{:02}  {}{}",
+                    stats::format_timestamp(game.timestamp),
+                    game.mode.name(),
+                    game.score,
+                    secs / 60,
+                    secs % 60,
+                    game.outcome.label(),
+                    match (&game.replay, game.items) {
+                        (Some(_), _) => "",
+                        (None, true) => "  (sem replay: itens)",
+                        (None, false) => "  (sem replay)",
+                    }
+                )
+            })
+            .collect();
+        if labels.is_empty() {
+            self.text.draw_centered(canvas, "Nenhum jogo ainda", 300.0, 18.0, Color::WHITE);
+        }
+        self.history_list.draw(canvas, &self.text, &labels);
+        if let Some(status) = &self.status {
+            self.text.draw_centered(canvas, status, 565.0, 14.0, Color::WHITE);
+        }
+    }
+
+    fn draw_replay(&self, canvas: &mut graphics::Canvas) {
+        let Some(player) = &self.replay_player else {
+            return;
+        };
+        draw_grid(&self.cells, &player.grid, &self.settings);
+        self.cells.flush(canvas);
+        let elapsed = player.elapsed.min(player.replay.duration()).as_secs();
+        let total = player.replay.duration().as_secs();
+        self.text.draw_centered(
+            canvas,
+            &format!("REPLAY  {}:{:02} / {}:{:02}", elapsed / 60, elapsed % 60, total / 60, total % 60),
+            20.0,
+            16.0,
+            Color::WHITE,
+        );
+        let hint = if player.finished() { "Fim do replay - Esc: voltar" } else { "Esc: voltar" };
+        self.text.draw_centered(canvas, hint, 580.0, 14.0, YELLOW);
+    }
+
+    fn open_heatmap(&mut self, all: bool) {
+        let mut heatmap = Heatmap::new();
+        if all {
+            let replays_dir = self.data_dir.join("replays");
+            for name in self.recent_games().filter_map(|game| game.replay.as_ref()) {
+                if let Ok(replay) = Replay::load(&replays_dir.join(name)) {
+                    heatmap.add_replay(&replay);
+                }
+            }
+        } else {
+            heatmap.add_replay(&self.replay);
+        }
+        self.heatmap = Some(heatmap);
+        self.view_all = all;
+        if self.screen != Screen::Heatmap {
+            self.open(Screen::Heatmap);
+        }
+    }
+
+    fn draw_stats(&self, canvas: &mut graphics::Canvas) {
+        let (title, pieces) = if self.view_all {
+            ("ESTATÍSTICAS - total", self.history.piece_totals())
+        } else {
+            ("ESTATÍSTICAS - partida", self.run_stats.by_piece)
+        };
+        self.text.draw_centered(canvas, title, 60.0, 22.0, PINK);
+        self.text.draw_centered(canvas, "Peça Dadas Postas Tempo Erros", 120.0, 14.0, YELLOW);
+        for (i, (kind, piece)) in PieceKind::ALL.iter().zip(&pieces).enumerate() {
+            self.text.draw_centered(
+                canvas,
+                &format!(
+                    "{:<4}{:>6}{:>7}{:>5.1}s{:>5.0}%",
+                    kind.name(),
+                    piece.dealt,
+                    piece.placed,
+                    piece.average_place_secs(),
+                    piece.misdrop_rate() * 100.0
+                ),
+                150.0 + i as f32 * 28.0,
+                14.0,
+                Color::WHITE,
+            );
+        }
+        self.text.draw_centered(
+            canvas,
+            "Erros: peças que abriram buracos e foram corrigidas logo depois de travar.",
+            380.0,
+            12.0,
+            Color::WHITE,
+        );
+        self.text.draw_centered(canvas, "Tab: partida/total  Esc: voltar", 580.0, 14.0, YELLOW);
+    }
+
+    fn draw_shame(&self, canvas: &mut graphics::Canvas) {
+        self.text.draw_centered(canvas, "HALL DA VERGONHA", 60.0, 24.0, PINK);
+        self.text.draw_centered(canvas, &format!("{}", self.progress.deaths), 130.0, 64.0, Color::WHITE);
+        self.text.draw_centered(canvas, "mortes no total", 200.0, 16.0, YELLOW);
+        for (i, milestone) in MILESTONES.iter().enumerate() {
+            let y = 260.0 + i as f32 * 60.0;
+            let (color, reward) = if self.progress.deaths >= milestone.deaths {
+                (PINK, milestone.description.to_string())
+            } else {
+                (Color::new(0.5, 0.5, 0.5, 1.0), "???".to_string())
+            };
+            self.text.draw_centered(canvas, &format!("{} mortes - {}", milestone.deaths, milestone.name), y, 18.0, color);
+            self.text.draw_centered(canvas, &reward, y + 24.0, 14.0, color);
+        }
+        if let Some(next) = self.progress.next_milestone() {
+            let left = next.deaths - self.progress.deaths;
+            self.text.draw_centered(canvas, &format!("Faltam {} mortes para o próximo prêmio", left), 470.0, 14.0, Color::WHITE);
+        }
+        self.text.draw_centered(canvas, "Esc: voltar", 580.0, 14.0, YELLOW);
+    }
+
+    fn draw_heatmap(&self, canvas: &mut graphics::Canvas) {
+        let Some(heatmap) = &self.heatmap else {
+            return;
+        };
+        heatmap.draw(canvas, &self.text);
+        let title = if self.view_all {
+            format!("MAPA DE CALOR - {} jogos", heatmap.games)
+        } else {
+            "MAPA DE CALOR - esta partida".to_string()
+        };
+        self.text.draw_centered(canvas, &title, 20.0, 18.0, Color::WHITE);
+        self.text.draw_centered(canvas, "Peças por coluna:", 50.0, 12.0, YELLOW);
+        self.text.draw_centered(canvas, "Tab: partida/histórico  Esc: voltar", 580.0, 14.0, YELLOW);
+    }
+
+    fn bundle_path(&self) -> PathBuf {
+        self.data_dir.join("lollypop-perfil.toml")
+    }
+
+    fn export_settings(&mut self) {
+        let path = self.bundle_path();
+        self.status = Some(match self.settings.save(&path) {
+            Ok(()) => format!("Perfil exportado para {}", path.display()),
+            Err(e) => format!("Falha ao exportar perfil: {}", e),
+        });
+    }
+
+    fn import_settings(&mut self) {
+        let path = self.bundle_path();
+        self.status = Some(match Settings::import(&path) {
+            Ok(settings) => {
+                self.settings = settings;
+                self.save_settings();
+                self.refresh_settings_menu();
+                self.apply_volume();
+                format!("Perfil importado de {}", path.display())
+            }
+            Err(e) => format!("Falha ao importar {}: {}", path.display(), e),
+        });
+    }
+
+    fn draw_settings(&self, canvas: &mut graphics::Canvas) {
+        let handling = &self.settings.handling;
+        self.text.draw_centered(canvas, "CONFIGURAÇÕES", 30.0, 28.0, PINK);
+        self.text.draw_centered(
+            canvas,
+            &format!("DAS: {} ms  ARR: {} ms", handling.das_ms, handling.arr_ms),
+            62.0,
+            18.0,
+            Color::WHITE,
+        );
+        self.text.draw_centered(canvas, &self.sync_status, 84.0, 14.0, Color::WHITE);
+        self.text.draw_centered(
+            canvas,
+            "O perfil inclui controles, handling e tema em um único arquivo.",
+            580.0,
+            12.0,
+            YELLOW,
+        );
+        self.settings_menu.draw(canvas, &self.text);
+        if let Some(status) = &self.status {
+            self.text.draw_centered(canvas, status, 594.0, 14.0, Color::WHITE);
+        }
+    }
+
+    fn run_command(&mut self, line: &str) -> String {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let arg = words.next();
+        let number = arg.and_then(|arg| arg.parse::<u64>().ok());
+        match (command, arg, number) {
+            ("help", _, _) => console::HELP.to_string(),
+            ("seed", _, Some(seed)) if !matches!(self.screen, Screen::Countdown | Screen::Playing | Screen::Paused) => {
+                self.next_seed = Some(seed);
+                format!("seed {} na próxima partida", seed)
+            }
+            ("seed", _, Some(seed)) => {
+                self.seed = seed;
+                self.randomizer.reseed(seed);
+                self.queue.clear();
+                format!("seed {}", seed)
+            }
+            ("spawn", Some(name), _) => match PieceKind::from_name(name) {
+                Some(kind) => {
+                    self.block = self.new_block(kind);
+                    format!("peça {}", kind.name())
+                }
+                None => format!("peça desconhecida: {}", name),
+            },
+            ("garbage", _, Some(rows)) => {
+                let pattern = if words.next() == Some("messy") { HolePattern::Messy } else { HolePattern::Clean };
+                self.grid.add_garbage(rows as usize, pattern, &mut self.randomizer);
+                self.start_shake((rows as f32 * 3.0).min(10.0));
+                format!("{} linhas de lixo", rows)
+            }
+            ("level", _, Some(level)) => {
+                self.level = level as u32;
+                self.goal_progress = 0;
+                let gravity = self.gravity();
+                format!("nível {} ({} linhas a cada {} ticks)", self.level, gravity.rows, gravity.ticks)
+            }
+            ("clearboard", _, _) => {
+                self.grid = EMPTY_GRID;
+                "tabuleiro limpo".to_string()
+            }
+            _ => format!("comando inválido: {} (digite help)", line),
+        }
+    }
+
+    // One simulation step: scripted inputs, gravity, auto-shift and the idle/misdrop timers.
+    fn advance(&mut self, ctx: &mut Context) -> GameResult {
+        self.tick += 1;
+        self.run_stats.play_time = clock::ticks_to_duration(self.tick);
+        if let Some(tas) = &mut self.tas {
+            for input in tas.inputs_until(self.tick) {
+                match input {
+                    TasInput::Press(action) => self.apply_action(ctx, Some(action), false)?,
+                    TasInput::Release => self.shift_dir = 0,
+                }
+            }
+            if self.game_over || self.screen != Screen::Playing {
+                return Ok(());
+            }
+        }
+        let idle_limit = Duration::from_secs(self.settings.auto_pause_secs);
+        let sandbox = self.mode == GameMode::Sandbox;
+        // Scripts often leave long gaps between inputs; they must not trip the auto-pause.
+        let idle = self.tas.is_none() && self.run_stats.play_time - self.last_input >= idle_limit;
+        if !sandbox && !idle_limit.is_zero() && idle {
+            self.pause(ctx, PauseReason::Idle);
+            return Ok(());
+        }
+        if let Some((_, locked_at)) = self.suspect_lock {
+            if self.run_stats.play_time - locked_at > MISDROP_WINDOW {
+                self.suspect_lock = None;
+            }
+        }
+        if let Some(clearing) = &self.clearing {
+            if self.tick < clearing.until {
+                return Ok(());
+            }
+            let Clearing { spin, holes_before, .. } = self.clearing.take().expect("checked above");
+            if self.cascading {
+                return self.chain_clear(ctx);
+            }
+            // Rows were cleared, so this can't be a lock-out.
+            self.finish_lock(ctx, spin, false, holes_before)?;
+            if self.game_over || !self.piece_active() {
+                return Ok(());
+            }
+        }
+        if self.cascading {
+            return self.update_cascade(ctx);
+        }
+        if let Some(spawn_at) = self.spawn_at {
+            if self.tick < spawn_at {
+                return Ok(());
+            }
+            self.spawn_at = None;
+            self.enter_piece(ctx)?;
+            if self.game_over || !self.piece_active() {
+                return Ok(());
+            }
+        }
+        self.update_shift();
+        if self.soft_dropping {
+            self.update_soft_drop();
+        }
+        if sandbox {
+            return Ok(());
+        }
+        self.effects.update(self.tick);
+        if self.settings.instant_gravity {
+            self.instant_gravity();
+        } else if !self.soft_dropping {
+            self.pull(self.gravity());
+        }
+        self.update_lock(ctx)
+    }
+
+    // Held soft drop stands in for gravity, sped up by the configured factor.
+    fn update_soft_drop(&mut self) {
+        let rows = match self.settings.handling.soft_drop.factor() {
+            None => {
+                let rows = self.block.drop_distance(&self.grid);
+                self.fall(rows);
+                rows
+            }
+            Some(factor) => self.pull(self.gravity().scaled(factor, 1)),
+        };
+        self.score += self.score_table.soft_drop(rows as u32);
+    }
+
+    // Floating blocks fall a row every few ticks; once everything has landed, rows they
+    // completed clear as a chain and the fall starts over.
+    fn update_cascade(&mut self, ctx: &mut Context) -> GameResult {
+        if !self.tick.is_multiple_of(CASCADE_TICKS) || cascade_step(&mut self.grid) {
+            return Ok(());
+        }
+        // Chain clears flash like any other.
+        let delay = clock::ticks_from_ms(self.settings.handling.line_clear_ms);
+        let rows = full_rows(&self.grid);
+        if delay > 0 && !rows.is_empty() {
+            self.clearing = Some(Clearing {
+                rows,
+                from: self.tick,
+                until: self.tick + delay,
+                spin: false,
+                holes_before: 0,
+            });
+            return Ok(());
+        }
+        self.chain_clear(ctx)
+    }
+
+    // Clears what the cascade completed and lets it carry on, or ends it if nothing was.
+    fn chain_clear(&mut self, ctx: &mut Context) -> GameResult {
+        if self.clear_lines(ctx, false)? == 0 {
+            self.cascading = false;
+            return self.next_piece(ctx);
+        }
+        Ok(())
+    }
+
+    // Starts the lock delay once the piece lands and locks it when the delay runs out. Sliding
+    // off a ledge lets it fall again and cancels the delay.
+    fn update_lock(&mut self, ctx: &mut Context) -> GameResult {
+        if self.block.y > self.lowest_y {
+            self.lowest_y = self.block.y;
+            self.lock_resets = 0;
+        }
+        if self.block.can_move(0, 1, &self.grid) {
+            self.lock_at = None;
+            return Ok(());
+        }
+        let delay = self.speed().lock_ticks;
+        let delay = delay.unwrap_or_else(|| clock::ticks_from_ms(self.settings.handling.lock_delay_ms));
+        let lock_at = *self.lock_at.get_or_insert(self.tick + delay);
+        if self.tick >= lock_at {
+            self.lock_piece(ctx)?;
+        }
+        Ok(())
+    }
+
+    // Live key presses and TAS scripts both come through here.
+    fn apply_action(&mut self, ctx: &mut Context, action: Option<Action>, repeat: bool) -> GameResult {
+        self.last_input = self.run_stats.play_time;
+        if action.is_some() && !repeat {
+            self.run_stats.keys += 1;
+        }
+        // A hole-making lock followed right away by a steering input means the player was
+        // still aiming the piece that had already locked: count it as a probable misdrop.
+        let steering = matches!(
+            action,
+            Some(Action::MoveLeft | Action::MoveRight | Action::RotateCw | Action::RotateCcw | Action::Rotate180)
+        );
+        if steering && !repeat {
+            if let Some((kind, _)) = self.suspect_lock.take() {
+                self.run_stats.record_misdrop(kind);
+            }
+        }
+        let Some(action) = action else {
+            return Ok(());
+        };
+        // Between pieces left/right charge the auto-shift for the next one, rotations are held
+        // for it to enter already turned and drops wait in the input buffer.
+        if !self.piece_active() {
+            match action {
+                Action::MoveLeft | Action::MoveRight => {}
+                Action::SoftDrop | Action::SonicDrop | Action::HardDrop if !repeat => {
+                    if self.input_buffer.len() < INPUT_BUFFER {
+                        self.input_buffer.push(action);
+                    }
+                    return Ok(());
+                }
+                _ => {
+                    if action.turns() > 0 {
+                        self.irs_turns = action.turns();
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        self.perform(ctx, action, repeat)
+    }
+
+    fn perform(&mut self, ctx: &mut Context, action: Action, repeat: bool) -> GameResult {
+        let sandbox = self.mode == GameMode::Sandbox;
+        match action {
+            Action::MoveLeft if !repeat => self.start_shift(-1),
+            Action::MoveRight if !repeat => self.start_shift(1),
+            // Holding it is handled by `update_soft_drop`; the press itself moves one row.
+            Action::SoftDrop if repeat => {}
+            Action::SoftDrop if self.block.can_move(0, 1, &self.grid) => {
+                self.fall(1);
+                self.score += self.score_table.soft_drop(1);
+            }
+            // Without gravity nothing else would lock a piece that has landed.
+            Action::SoftDrop if sandbox => self.lock_piece(ctx)?,
+            Action::RotateCw | Action::RotateCcw | Action::Rotate180 => self.rotate(action.turns()),
+            Action::Hold | Action::Hold2 if !repeat => {
+                if let Some(slot) = action.hold_slot() {
+                    self.hold_piece(ctx, slot)?;
+                }
+            }
+            // Lands the piece and leaves it to the lock delay, like gravity would.
+            Action::SonicDrop => {
+                let rows = self.block.drop_distance(&self.grid);
+                self.fall(rows);
+                self.score += self.score_table.soft_drop(rows as u32);
+            }
+            Action::HardDrop => {
+                let rows = self.block.drop_distance(&self.grid);
+                self.fall(rows);
+                self.score += self.score_table.hard_drop(rows as u32);
+                // A thud, harder the further the piece fell.
+                self.start_shake(2.0 + (rows as f32 / 4.0).min(4.0));
+                self.audio.cue(Sound::HardDrop);
+                self.lock_piece(ctx)?;
+            }
+            _ => {}
+        }
+        self.instant_gravity();
+        Ok(())
+    }
+
+    // Swaps the piece in play into a hold slot. What comes out, or the next piece if the slot
+    // was empty, starts over from the top in its spawn orientation.
+    fn hold_piece(&mut self, ctx: &mut Context, slot: usize) -> GameResult {
+        if !self.hold.available(slot) {
+            return Ok(());
+        }
+        match self.hold.swap(slot, self.block.clone()) {
+            Some(held) => {
+                let mut block = Block::new(held.kind, self.spawn_shape(held.kind), held.color, self.piece_scale());
+                block.item = held.item;
+                self.put_in_play(block);
+            }
+            None => self.spawn_block(),
+        }
+        self.check_game_over(ctx)
+    }
+
+    // 20G: the piece is pulled straight down to the stack as soon as it appears or moves, so
+    // it only ever slides and turns along the surface. The lock delay runs as usual once it's
+    // down. There's no gravity in the sandbox.
+    fn instant_gravity(&mut self) {
+        if self.settings.instant_gravity && self.piece_active() && self.mode != GameMode::Sandbox {
+            let rows = self.block.drop_distance(&self.grid);
+            self.fall(rows);
+        }
+    }
+
+    // A tap on the piece rotates it; anywhere else slides it over until it covers the tapped
+    // column. A swipe down of at least two cells hard drops.
+    fn pointer_released(&mut self, ctx: &mut Context, (from_x, from_y): (f32, f32), x: f32, y: f32) -> GameResult {
+        let (dx, dy) = (x - from_x, y - from_y);
+        if dy >= 2.0 * CELL_SIZE && dy > dx.abs() {
+            return self.apply_action(ctx, Some(Action::HardDrop), false);
+        }
+        let (column, _) = cell_at(from_x, from_y);
+        let covered = self.block.columns();
+        if covered.contains(&column) {
+            return self.apply_action(ctx, Some(Action::RotateCw), false);
+        }
+        if !self.piece_active() {
+            return Ok(());
+        }
+        self.run_stats.keys += 1;
+        self.last_input = self.run_stats.play_time;
+        let dir = if column < *covered.start() { -self.block.scale } else { self.block.scale };
+        let mut moved = false;
+        while !self.block.columns().contains(&column) && self.block.can_move(dir, 0, &self.grid) {
+            self.block.x += dir;
+            moved = true;
+        }
+        if moved {
+            self.moved();
+            self.audio.cue(Sound::Move);
+            self.instant_gravity();
+        }
+        Ok(())
+    }
+
+    // Cleared rows flash white for the first half of the line clear delay, then shrink away.
+    fn draw_clearing(&self, canvas: &mut graphics::Canvas, clearing: &Clearing) {
+        let left = clearing.until.saturating_sub(self.tick) as f32 / (clearing.until - clearing.from) as f32;
+        let height = (left * 2.0).min(1.0) * CELL_SIZE;
+        for &y in &clearing.rows {
+            let row = cell_rect(0.0, y as f32);
+            ui::fill_rect(canvas, Rect::new(row.x, row.y + (CELL_SIZE - height) / 2.0, BOARD_WIDTH, height), Color::WHITE);
+        }
+    }
+
+    // How far below its row to draw the falling piece, in rows, with smooth gravity on: the
+    // gravity built up towards the next row, plus what it gains over the part of a tick real
+    // time is into. Only the drawing moves; the piece stays on its row until gravity moves it.
+    fn fall_offset(&self) -> f32 {
+        let falls = !self.settings.instant_gravity && self.mode != GameMode::Sandbox;
+        if !self.settings.smooth_gravity || !falls || self.screen != Screen::Playing || !self.block.can_move(0, 1, &self.grid) {
+            return 0.0;
+        }
+        let mut gravity = self.gravity();
+        if self.soft_dropping {
+            match self.settings.handling.soft_drop.factor() {
+                Some(factor) => gravity = gravity.scaled(factor, 1),
+                None => return 0.0,
+            }
+        }
+        let per_tick = gravity.rows as f32 / gravity.ticks as f32;
+        let built_up = self.fall_progress as f32 / gravity.ticks as f32;
+        (built_up + per_tick * self.step.between_ticks()).min(0.99)
+    }
+
+    // Each number pops up large over the board and shrinks and fades until the next one.
+    fn draw_countdown(&self, canvas: &mut graphics::Canvas) {
+        let elapsed = (COUNTDOWN_STEP * COUNTDOWN_LABELS.len() as u32).saturating_sub(self.countdown);
+        let step = elapsed.as_millis() / COUNTDOWN_STEP.as_millis();
+        let Some(label) = COUNTDOWN_LABELS.get(step as usize) else {
+            return;
+        };
+        let t = (elapsed.as_millis() % COUNTDOWN_STEP.as_millis()) as f32 / COUNTDOWN_STEP.as_millis() as f32;
+        let color = if step as usize == COUNTDOWN_LABELS.len() - 1 { PINK } else { YELLOW };
+        let color = Color::new(color.r, color.g, color.b, 1.0 - t * t);
+        let scale = 64.0 + 64.0 * (1.0 - t).powi(2);
+        self.text.draw_centered(canvas, label, SCREEN_HEIGHT / 2.0 - 40.0, scale.round(), color);
+    }
+
+    // What the run's music should be doing this frame.
+    fn music(&self) -> Music {
+        let live = matches!(self.screen, Screen::Countdown | Screen::Playing) && !self.game_over;
+        if self.screen == Screen::Paused {
+            Music::Paused
+        } else if live {
+            Music::Playing { speed: self.music_speed(), layers: self.music_layers() }
+        } else {
+            Music::Stopped
+        }
+    }
+
+    // A little faster every few levels, and a lot faster with the stack up near the top, like
+    // the Game Boy's. Steps rather than a smooth ramp, since every change restarts the track.
+    fn music_speed(&self) -> f32 {
+        if !self.settings.music_ramp || self.mode == GameMode::Sandbox {
+            return 1.0;
+        }
+        let steps = (self.level / MUSIC_STEP_LEVELS).min(MUSIC_STEPS);
+        let high = self.stack_height > VISIBLE_ROWS - DANGER_ROWS;
+        1.0 + 0.05 * steps as f32 + if high { 0.2 } else { 0.0 }
+    }
+
+    // The drums come in as the stack passes six rows or on a combo of two, and the arpeggio
+    // past eleven rows or on a combo of four. Each builds up over a few rows or clears.
+    fn music_layers(&self) -> [f32; 2] {
+        if !self.settings.music_layers {
+            return [0.0; 2];
+        }
+        let ramp = |value: f32, from: f32, to: f32| ((value - from) / (to - from)).clamp(0.0, 1.0);
+        let (height, combo) = (self.stack_height as f32, self.combo as f32);
+        [
+            ramp(height, 6.0, 10.0).max(ramp(combo, 1.0, 3.0)),
+            ramp(height, 11.0, 15.0).max(ramp(combo, 3.0, 6.0)),
+        ]
+    }
+
+    // The stack is close enough to the top that the next few pieces could end the run.
+    fn in_danger(&self) -> bool {
+        self.settings.danger_warning && self.mode != GameMode::Sandbox && self.stack_height > VISIBLE_ROWS - DANGER_ROWS
+    }
+
+    // A red glow behind the stack that pulses about once a second, and red walls that pulse
+    // with it.
+    fn draw_danger(&self, canvas: &mut graphics::Canvas) {
+        let board = Region::Board.rect();
+        let phase = self.tick as f32 / clock::TICKS_PER_SEC as f32 * std::f32::consts::TAU;
+        let pulse = 0.5 + 0.5 * phase.sin();
+        ui::fill_rect(canvas, board, Color::new(1.0, 0.0, 0.0, 0.06 + 0.12 * pulse));
+        let edge = Color::new(1.0, 0.1, 0.1, 0.5 + 0.5 * pulse);
+        ui::fill_rect(canvas, Rect::new(board.x - 4.0, board.y, 6.0, board.h), edge);
+        ui::fill_rect(canvas, Rect::new(board.right() - 2.0, board.y, 6.0, board.h), edge);
+        ui::fill_rect(canvas, Rect::new(board.x - 4.0, board.bottom() - 2.0, board.w + 8.0, 2.0), edge);
+    }
+
+    // The active piece and its ghost. An outlined ghost goes straight to the canvas, under the
+    // queued cells.
+    fn draw_block(&self, canvas: &mut graphics::Canvas) {
+        let color = self.block_color(&self.block);
+        let offset = self.fall_offset();
+        let ghost_y = self.block.y + self.block.drop_distance(&self.grid);
+        let opacity = self.settings.ghost_opacity as f32 / 100.0;
+        for (y, row) in self.block.shape.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if !cell {
+                    continue;
+                }
+                let (cell_x, cell_y) = ((self.block.x + x as i32) as f32, (ghost_y + y as i32) as f32);
+                match self.settings.ghost {
+                    GhostStyle::Filled => {
+                        let tint = Color::new(color.r, color.g, color.b, color.a * opacity);
+                        draw_ghost_cell(&self.cells, cell_x, cell_y, tint, self.skin());
+                    }
+                    GhostStyle::Outline => {
+                        let filled = |dx: i32, dy: i32| {
+                            let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                            nx >= 0 && ny >= 0 && self.block.shape.get(ny as usize).and_then(|row| row.get(nx as usize)) == Some(&true)
+                        };
+                        let line = Color::new(color.r, color.g, color.b, (0.6 * opacity).min(1.0));
+                        draw_outline_cell(canvas, cell_x, cell_y, [filled(0, -1), filled(1, 0), filled(0, 1), filled(-1, 0)], line);
+                    }
+                    GhostStyle::Off => {}
+                }
+            }
+        }
+        let item_cell = self.block.item_cell();
+        for (y, row) in self.block.shape.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                if cell {
+                    let cell_x = (self.block.x + x as i32) as f32;
+                    let cell_y = (self.block.y + y as i32) as f32 + offset;
+                    draw_cell(&self.cells, cell_x, cell_y, color, self.skin());
+                    if self.settings.cell_patterns {
+                        draw_pattern(&self.cells, cell_x, cell_y, Some(self.block.kind));
+                    }
+                    if item_cell == Some((x, y)) {
+                        draw_sparkle(&self.cells, cell_x, cell_y);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_playfield(&self, ctx: &Context, canvas: &mut graphics::Canvas) -> GameResult {
+        // The streaming overlay keeps the empty playfield clear.
+        if self.overlay == Overlay::Off {
+            if self.in_danger() {
+                self.draw_danger(canvas);
+            }
+            draw_frame(canvas, self.settings.grid_opacity);
+        }
+        let mut grid = self.grid;
+        if let Some(clearing) = &self.clearing {
+            for &y in &clearing.rows {
+                grid[y] = [None; GRID_WIDTH];
+            }
+        }
+        // After a top out the board fills up with gray blocks from the floor, row by row.
+        for row in &mut grid[GRID_HEIGHT - self.curtain_rows..] {
+            *row = [Some(Cell::garbage()); GRID_WIDTH];
+        }
+        draw_grid(&self.cells, &grid, &self.settings);
+        if let Some((block, until)) = &self.lock_flash {
+            if self.tick < *until {
+                let fade = (until - self.tick) as f32 / LOCK_FLASH_TICKS as f32;
+                for (y, row) in block.shape.iter().enumerate() {
+                    for (x, &cell) in row.iter().enumerate() {
+                        if cell {
+                            let (cell_x, cell_y) = ((block.x + x as i32) as f32, (block.y + y as i32) as f32);
+                            draw_flash_cell(&self.cells, cell_x, cell_y, fade, self.skin());
+                        }
+                    }
+                }
+            }
+        }
+        if self.piece_active() {
+            self.draw_block(canvas);
+        }
+        self.cells.flush(canvas);
+        if let Some(clearing) = &self.clearing {
+            self.draw_clearing(canvas, clearing);
+        }
+        self.particles.draw(canvas);
+        
+        self.draw_hold(canvas);
+        self.draw_scoreboard(canvas);
+        self.draw_next(canvas);
+        self.draw_combo(canvas);
+        if self.settings.bag_preview && self.settings.randomizer == RandomizerKind::Bag && self.piece_set().is_none() {
+            self.draw_bag(canvas);
+        }
+
+        let mut hud = String::new();
+        if let Some(tas) = &self.tas {
+            let state = if tas.finished() { "fim do script" } else { "Esc: assumir" };
+            hud += &format!("TAS  quadro {}  {}\n", self.tick, state);
+        }
+        if self.mode == GameMode::Sandbox {
+            hud += &if self.editing {
+                "EDITOR  Clique: pintar/apagar  C: limpar  E: jogar".to_string()
+            } else {
+                let keys = &self.settings.keybinds;
+                format!(
+                    "SANDBOX  E: editor  {}: desfazer  {}: pausar  Esc: sair",
+                    keys.label(Action::Undo),
+                    keys.label(Action::Pause)
+                )
+            };
+        }
+        let board = Region::Board.rect();
+        self.text.draw(canvas, hud.trim_end(), [board.x + 6.0, board.y + 6.0], text::HUD);
+
+        if let Some((callout, until)) = &self.callout {
+            if self.tick < *until {
+                self.text.draw_centered(canvas, callout, SCREEN_HEIGHT / 2.0 - 110.0, 24.0, PINK);
+            }
+        }
+
+        if self.tick < self.perfect_clear_until {
+            // Blinks twice a second.
+            let blink = (self.perfect_clear_until - self.tick) / (clock::TICKS_PER_SEC / 4);
+            let color = if blink.is_multiple_of(2) { YELLOW } else { PINK };
+            self.text.draw_centered(canvas, "PERFECT CLEAR!", SCREEN_HEIGHT / 2.0 - 60.0, 32.0, color);
+        }
+
+        if self.screen == Screen::Countdown {
+            self.draw_countdown(canvas);
+        }
+
+        // The board is covered completely so a pause can't be used to study the stack.
+        if self.screen == Screen::Paused {
+            ui::fill_rect(canvas, Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT), Color::BLACK);
+            self.text.draw_centered(canvas, "PAUSADO", SCREEN_HEIGHT / 2.0 - 30.0, 32.0, PINK);
+            let hint = match self.pause_reason {
+                PauseReason::Manual => {
+                    format!("{} ou Esc para continuar, Q para desistir.", self.settings.keybinds.label(Action::Pause))
+                }
+                PauseReason::Idle => "Sem atividade. Pressione qualquer tecla para continuar.".to_string(),
+                PauseReason::FocusLost => "A janela perdeu o foco. Pressione qualquer tecla para continuar.".to_string(),
+                PauseReason::PadDisconnected => {
+                    "Controle desconectado. Reconecte ou pressione qualquer tecla para continuar.".to_string()
+                }
+            };
+            self.text.draw_centered(
+                canvas,
+                &hint,
+                SCREEN_HEIGHT / 2.0 + 20.0,
+                14.0,
+                Color::WHITE,
+            );
+        }
+
+        if self.game_over && self.death_count == 1 {
+            const HINT: &str = "Jogue mais uma vez para liberar um easter egg";
+            let style = text::Style::new(16.0, Color::WHITE, text::Align::Center);
+            let pos = [SCREEN_WIDTH / 2.0, SCREEN_HEIGHT / 2.0 + 100.0];
+            self.text.draw_boxed(ctx, canvas, HINT, pos, style, Color::new(0.0, 0.0, 0.0, 0.6))?;
+        }
+
+        self.scares.draw(canvas, self.viewport.screen());
+        Ok(())
+    }
+
+    fn draw_results(&self, canvas: &mut graphics::Canvas) {
+        let stats = &self.run_stats;
+        let secs = stats.play_time.as_secs();
+        let completed = self.history.games.last().map(|game| game.outcome) == Some(GameOutcome::Completed);
+        let title = if completed {
+            "SPRINT COMPLETO"
+        } else {
+            "FIM DE JOGO"
+        };
+        self.text.draw_centered(canvas, title, 80.0, 32.0, PINK);
+        self.text.draw_centered(canvas, &format!("Pontos: {}", self.score), 150.0, 22.0, Color::WHITE);
+        self.text.draw_centered(canvas, &format!("Tempo: {}:{:02}", secs / 60, secs % 60), 185.0, 18.0, Color::WHITE);
+        self.text.draw_centered(canvas, &format!("Peças: {}  PPS: {:.2}", stats.pieces, stats.pps()), 210.0, 18.0, Color::WHITE);
+        self.text.draw_centered(
+            canvas,
+            &format!(
+                "Teclas: {}  KPP: {:.2}  Misdrops: {}",
+                stats.keys,
+                stats.kpp(),
+                stats.misdrops()
+            ),
+            235.0,
+            14.0,
+            Color::WHITE,
+        );
+        self.text.draw_centered(
+            canvas,
+            &format!(
+                "1x: {}  2x: {}  3x: {}  Tetris: {}",
+                stats.clears[0], stats.clears[1], stats.clears[2], stats.clears[3]
+            ),
+            255.0,
+            14.0,
+            YELLOW,
+        );
+        self.text.draw_centered(
+            canvas,
+            &format!("Maior seca de I: {} peças (recorde {})", stats.max_drought, self.history.max_drought()),
+            275.0,
+            14.0,
+            Color::WHITE,
+        );
+        self.results_menu.draw(canvas, &self.text);
+        self.text.draw_centered(canvas, &format!("+{} pirulitos", self.earned), 535.0, 16.0, PINK);
+        if let Some(status) = &self.status {
+            self.text.draw_centered(canvas, status, 560.0, 14.0, Color::WHITE);
+        }
+    }
+
+    // Scares, the death freeze, the countdown, replays and the simulation's ticks for this
+    // frame.
+    fn run_frame(&mut self, ctx: &mut Context) -> GameResult {
+        let now = ctx.time.time_since_start();
+        self.scares.update(now);
+        if self.scares.is_active() {
+            self.step.clear();
+            return Ok(());
+        }
+
+        if let (Some(freeze_timer), Some(freeze_start)) = (self.freeze_timer, self.freeze_start) {
+            if now - freeze_start < freeze_timer {
+                let covered = (now - freeze_start).as_secs_f32() / CURTAIN.as_secs_f32();
+                self.curtain_rows = ((covered * VISIBLE_ROWS as f32).ceil() as usize).min(VISIBLE_ROWS);
+                return Ok(());
+            } else {
+                self.freeze_timer = None;
+                self.freeze_start = None;
+                self.screen = Screen::Results;
+                self.idle_since = now;
+            }
+        }
+
+        if self.screen == Screen::Countdown && !self.console.open {
+            self.countdown = self.countdown.saturating_sub(ctx.time.delta());
+            if self.countdown.is_zero() {
+                self.screen = Screen::Playing;
+            }
+        }
+
+        if self.screen == Screen::Replay {
+            if let Some(player) = &mut self.replay_player {
+                player.update(ctx.time.delta());
+            }
+            return Ok(());
+        }
+
+        if self.screen == Screen::Playing {
+            self.particles.update(ctx.time.delta());
+        }
+
+        // Time spent anywhere but live play never reaches the simulation.
+        if self.game_over || self.screen != Screen::Playing || self.console.open {
+            self.step.clear();
+            return Ok(());
+        }
+
+        let speed = self.tas.as_ref().map_or(1.0, |tas| tas.speed);
+        for _ in 0..self.step.ticks_due(ctx.time.delta(), speed) {
+            self.advance(ctx)?;
+            if self.game_over || self.screen != Screen::Playing || self.scares.is_active() {
+                break;
+            }
+        }
+        self.stack_height = stack_height(&self.grid);
+        Ok(())
+    }
+}
+
+impl EventHandler<ggez::GameError> for GameState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.poll_assets(ctx);
+        // Both can change at once, from a profile import or a sync; sounds load only once.
+        let theme_changed = self.loaded_theme != self.settings.theme;
+        if theme_changed || self.loaded_folder != self.settings.sound_folder {
+            self.loaded_theme = self.settings.theme.clone();
+            self.loaded_folder = self.settings.sound_folder.clone();
+            if theme_changed {
+                self.apply_theme(ctx);
+            }
+            self.reload_sounds(ctx);
+        }
+        self.poll_sync();
+        self.update_idle(ctx)?;
+        self.check_pad(ctx);
+        if self.api.is_some() {
+            let live = self.live_state();
+            if let Some(api) = &mut self.api {
+                api.publish(&live);
+            }
+        }
+
+        self.run_frame(ctx)?;
+        // Cued by input since the last frame, and by this frame's ticks.
+        self.audio.play_cued(ctx, shop::sfx_pitch(&self.settings.sfx_pack))?;
+        self.audio.update_music(ctx, self.music(), ctx.time.delta())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        // Menus keep their background even in overlay mode so they stay readable.
+        let playing = matches!(self.screen, Screen::Countdown | Screen::Playing | Screen::Paused);
+        let background = match self.overlay {
+            Overlay::Transparent if playing => Color::new(0.0, 0.0, 0.0, 0.0),
+            Overlay::ChromaKey if playing => Color::GREEN,
+            _ => self
+                .theme_file()
+                .and_then(ThemeFile::background)
+                .unwrap_or_else(|| shop::background(&self.settings.background)),
+        };
+        let mut canvas = graphics::Canvas::from_frame(ctx, background);
+        self.cells.set_style(self.settings.block_style);
+        // Everything below draws in logical coordinates.
+        let mut view = self.viewport.screen();
+        canvas.set_screen_coordinates(view);
+        if playing && self.overlay == Overlay::Off {
+            let brightness = self.settings.backdrop_brightness as f32 / 100.0;
+            let secs = ctx.time.time_since_start().as_secs_f32();
+            self.backdrop.draw(&mut canvas, self.settings.backdrop, brightness, secs, self.palette());
+        }
+        // Shaking moves the whole view, so everything drawn after the backdrop goes with it.
+        if let (true, Some([dx, dy])) = (playing, self.shake_offset()) {
+            view.x -= dx;
+            view.y -= dy;
+        }
+        canvas.set_screen_coordinates(view);
+
+        match self.screen {
+            Screen::Title => self.draw_title(&mut canvas),
+            Screen::Modes => {
+                self.text.draw_centered(&mut canvas, "MODO DE JOGO", 120.0, 28.0, PINK);
+                self.modes_menu.draw(&mut canvas, &self.text);
+            }
+            Screen::Results => self.draw_results(&mut canvas),
+            Screen::Settings => self.draw_settings(&mut canvas),
+            Screen::History => self.draw_history(&mut canvas),
+            Screen::Replay => self.draw_replay(&mut canvas),
+            Screen::Heatmap => self.draw_heatmap(&mut canvas),
+            Screen::Stats => self.draw_stats(&mut canvas),
+            Screen::Shame => self.draw_shame(&mut canvas),
+            Screen::Themes => self.draw_themes(&mut canvas),
+            Screen::Controls => self.draw_controls(&mut canvas),
+            Screen::Display => self.draw_display(&mut canvas),
+            Screen::Accessibility => self.draw_accessibility(&mut canvas),
+            Screen::Audio => self.draw_audio(&mut canvas),
+            Screen::Shop => self.draw_shop(&mut canvas),
+            Screen::Missions => self.draw_missions(&mut canvas),
+            Screen::Countdown | Screen::Playing | Screen::Paused => self.draw_playfield(ctx, &mut canvas)?,
+        }
+
+        if self.settings.show_fps {
+            let style = text::Style::new(12.0, Color::new(1.0, 1.0, 1.0, 0.7), text::Align::Right);
+            self.text.draw(&mut canvas, &format!("{:.0} FPS", ctx.time.fps()), [SCREEN_WIDTH - 4.0, 0.0], style);
+        }
+        self.console.draw(&mut canvas, &self.text);
+        self.text.end_frame();
+        canvas.finish(ctx)?;
+        self.frame_limiter.wait(self.settings.fps_cap);
+        Ok(())
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeat: bool) -> GameResult {
+        self.note_activity(ctx);
+        let Some(keycode) = input.keycode else {
+            return Ok(());
+        };
+        let alt_enter = keycode == KeyCode::Return && input.mods.contains(KeyMods::ALT);
+        if (keycode == KeyCode::F11 || alt_enter) && !repeat {
+            self.toggle_fullscreen(ctx);
+            return Ok(());
+        }
+        if keycode == KeyCode::Grave {
+            self.console.toggle();
+            return Ok(());
+        }
+        if self.console.open {
+            if let Some(line) = self.console.key_down(keycode) {
+                let reply = self.run_command(&line);
+                self.console.print(&reply);
+            }
+            return Ok(());
+        }
+        // M mutes everywhere, unless it's been bound to an action or is being bound to one.
+        let unbound = self.settings.keybinds.action(keycode).is_none() && self.rebinding.is_none();
+        if keycode == KeyCode::M && unbound && !repeat {
+            self.toggle_mute();
+            return Ok(());
+        }
+        // Restarting works mid-run, while paused, during the death freeze and on the results.
+        let restart = self.settings.keybinds.action(keycode) == Some(Action::Restart);
+        let after_run = matches!(self.screen, Screen::Countdown | Screen::Playing | Screen::Paused | Screen::Results);
+        if restart && after_run && self.tas.is_none() && !self.scares.is_active() && !repeat {
+            return self.quick_restart(ctx, input.mods.contains(KeyMods::SHIFT));
+        }
+        if self.freeze_timer.is_some() || self.scares.is_active() {
+            return Ok(());
+        }
+
+        let menu_key = self.settings.keybinds.menu_key(keycode);
+        match self.screen {
+            Screen::Playing => {}
+            // Pieces can't move yet; the countdown can only be paused.
+            Screen::Countdown => {
+                let pause_key = keycode == KeyCode::Escape || self.settings.keybinds.action(keycode) == Some(Action::Pause);
+                if pause_key && !repeat {
+                    self.pause(ctx, PauseReason::Manual);
+                }
+                return Ok(());
+            }
+            Screen::Paused => {
+                let pause_key = keycode == KeyCode::Escape || self.settings.keybinds.action(keycode) == Some(Action::Pause);
+                if self.pause_reason != PauseReason::Manual || pause_key {
+                    self.resume();
+                } else if keycode == KeyCode::Q {
+                    return self.go_to_title(ctx);
+                }
+                return Ok(());
+            }
+            Screen::History => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back) {
+                    self.close();
+                } else if let Some(index) = self.history_list.key_down(menu_key) {
+                    self.open_replay(index);
+                }
+                return Ok(());
+            }
+            Screen::Replay => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back | KeyCode::Return) {
+                    self.replay_player = None;
+                    self.screen = Screen::History;
+                }
+                return Ok(());
+            }
+            Screen::Heatmap => {
+                match keycode {
+                    KeyCode::Tab => self.open_heatmap(!self.view_all),
+                    KeyCode::Escape | KeyCode::Back | KeyCode::Return => {
+                        self.heatmap = None;
+                        self.close();
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Screen::Stats => {
+                match keycode {
+                    KeyCode::Tab => self.view_all = !self.view_all,
+                    KeyCode::Escape | KeyCode::Back | KeyCode::Return => self.close(),
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Screen::Shame | Screen::Missions => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back | KeyCode::Return) {
+                    self.close();
+                }
+                return Ok(());
+            }
+            Screen::Themes => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back) {
+                    self.close();
+                } else if let Some(index) = self.theme_list.key_down(menu_key) {
+                    self.pick_theme(index);
+                }
+                return Ok(());
+            }
+            Screen::Controls => {
+                if self.rebinding.is_some() {
+                    self.rebind(keycode);
+                } else if matches!(keycode, KeyCode::Escape | KeyCode::Back) {
+                    self.close();
+                } else if keycode == KeyCode::Delete {
+                    self.unbind_control();
+                } else if let Some(index) = self.controls_list.key_down(menu_key) {
+                    self.pick_control(index);
+                }
+                return Ok(());
+            }
+            Screen::Display => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back) {
+                    self.close();
+                } else if let Some(index) = self.display_list.key_down(menu_key) {
+                    self.pick_display(ctx, index);
+                }
+                return Ok(());
+            }
+            Screen::Accessibility => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back) {
+                    self.close();
+                } else if let Some(index) = self.access_list.key_down(menu_key) {
+                    self.pick_accessibility(index);
+                }
+                return Ok(());
+            }
+            Screen::Audio => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back) {
+                    self.close();
+                } else if let Some(index) = self.audio_list.key_down(menu_key) {
+                    self.pick_audio(index);
+                }
+                return Ok(());
+            }
+            Screen::Shop => {
+                if matches!(keycode, KeyCode::Escape | KeyCode::Back) {
+                    self.close();
+                } else if let Some(index) = self.shop_list.key_down(menu_key) {
+                    self.shop_choice(index);
+                }
+                return Ok(());
+            }
+            Screen::Title | Screen::Modes | Screen::Results | Screen::Settings => {
+                if self.screen == Screen::Title {
+                    if let Some(secret) = self.secrets.key(keycode) {
+                        self.unlock_secret(secret);
+                        return Ok(());
+                    }
+                }
+                if let Some(choice) = self.active_menu().and_then(|menu| menu.key_down(menu_key)) {
+                    self.menu_choice(ctx, choice)?;
+                }
+                return Ok(());
+            }
+        }
+
+        if self.tas.is_some() {
+            // Esc hands the run back to the player; every other key is ignored during playback.
+            if keycode == KeyCode::Escape {
+                self.tas = None;
+                self.shift_dir = 0;
+                self.last_input = self.run_stats.play_time;
+            }
+            return Ok(());
+        }
+        if self.mode == GameMode::Sandbox {
+            match keycode {
+                // Sandbox runs keep no record; they just leave for the title screen.
+                KeyCode::Escape => return self.go_to_title(ctx),
+                KeyCode::E => {
+                    self.editing = !self.editing;
+                    return Ok(());
+                }
+                KeyCode::C if self.editing => {
+                    self.grid = EMPTY_GRID;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        let action = if keycode == KeyCode::Escape { Some(Action::Pause) } else { self.settings.keybinds.action(keycode) };
+        self.press(ctx, action, repeat)
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if self.console.open {
+            self.console.text_input(character);
+        }
+        Ok(())
+    }
+
+    fn key_up_event(&mut self, ctx: &mut Context, input: KeyInput) -> GameResult {
+        let Some(keycode) = input.keycode else {
+            return Ok(());
+        };
+        if self.tas.is_some() {
+            return Ok(());
+        }
+        let action = self.settings.keybinds.action(keycode);
+        // Another key for the same action may still be down.
+        if action.is_some_and(|action| self.held(ctx, action)) {
+            return Ok(());
+        }
+        self.release(ctx, action);
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(&mut self, ctx: &mut Context, button: Button, id: GamepadId) -> GameResult {
+        self.note_activity(ctx);
+        if self.screen == Screen::Paused && self.pause_reason != PauseReason::Manual {
+            self.pad = Some(id);
+            self.resume();
+            return Ok(());
+        }
+        let action = gamepad::action(button);
+        let blocked = self.freeze_timer.is_some() || self.scares.is_active() || self.console.open;
+        if self.screen == Screen::Playing && !self.game_over && self.tas.is_none() && !blocked {
+            self.pad = Some(id);
+            if action == Some(Action::Restart) {
+                return self.quick_restart(ctx, false);
+            }
+            return self.press(ctx, action, false);
+        }
+        // Everywhere else the pad stands in for the menu keys.
+        match gamepad::menu_key(button) {
+            Some(keycode) => {
+                let input = KeyInput {
+                    scancode: 0,
+                    keycode: Some(keycode),
+                    mods: KeyMods::empty(),
+                };
+                self.key_down_event(ctx, input, false)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn gamepad_button_up_event(&mut self, ctx: &mut Context, button: Button, _id: GamepadId) -> GameResult {
+        if self.screen == Screen::Playing && self.tas.is_none() {
+            self.release(ctx, gamepad::action(button));
+        }
+        Ok(())
+    }
+
+    fn gamepad_axis_event(&mut self, ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) -> GameResult {
+        let deadzone = self.settings.stick_deadzone as f32 / 100.0;
+        let bias = self.settings.stick_bias as f32 / 100.0;
+        let Some((released, pressed)) = self.stick.update(axis, value, deadzone, bias) else {
+            return Ok(());
+        };
+        if let Some(button) = released {
+            self.gamepad_button_up_event(ctx, button, id)?;
+        }
+        if let Some(button) = pressed {
+            self.gamepad_button_down_event(ctx, button, id)?;
+        }
+        Ok(())
+    }
+
+    fn focus_event(&mut self, ctx: &mut Context, gained: bool) -> GameResult {
+        if !gained {
+            self.pause_unattended(ctx, PauseReason::FocusLost);
+        }
+        Ok(())
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> GameResult {
+        let (x, y) = self.viewport.to_logical(x, y);
+        self.note_activity(ctx);
+        if self.screen == Screen::Paused {
+            if self.pause_reason != PauseReason::Manual {
+                self.resume();
+            }
+            return Ok(());
+        }
+        // Board editor: left click paints a cell, right click erases it.
+        if self.screen == Screen::Playing && self.editing {
+            match button {
+                MouseButton::Left => self.edit_cell(x, y, true),
+                MouseButton::Right => self.edit_cell(x, y, false),
+                _ => {}
+            }
+            return Ok(());
+        }
+        if button != MouseButton::Left {
+            return Ok(());
+        }
+        if self.screen == Screen::Playing {
+            if self.settings.pointer_controls {
+                self.press_at = Some((x, y));
+            }
+            return Ok(());
+        }
+        if self.screen == Screen::History {
+            if let Some(index) = self.history_list.mouse_down(x, y) {
+                self.open_replay(index);
+            }
+            return Ok(());
+        }
+        if self.screen == Screen::Themes {
+            if let Some(index) = self.theme_list.mouse_down(x, y) {
+                self.pick_theme(index);
+            }
+            return Ok(());
+        }
+        if self.screen == Screen::Controls {
+            if let Some(index) = self.controls_list.mouse_down(x, y) {
+                self.pick_control(index);
+            }
+            return Ok(());
+        }
+        if self.screen == Screen::Display {
+            if let Some(index) = self.display_list.mouse_down(x, y) {
+                self.pick_display(ctx, index);
+            }
+            return Ok(());
+        }
+        if self.screen == Screen::Accessibility {
+            if let Some(index) = self.access_list.mouse_down(x, y) {
+                self.pick_accessibility(index);
+            }
+            return Ok(());
+        }
+        if self.screen == Screen::Audio {
+            if let Some(index) = self.audio_list.mouse_down(x, y) {
+                self.pick_audio(index);
+            }
+            return Ok(());
+        }
+        if self.screen == Screen::Shop {
+            if let Some(index) = self.shop_list.mouse_down(x, y) {
+                self.shop_choice(index);
+            }
+            return Ok(());
+        }
+        if let Some(choice) = self.active_menu().and_then(|menu| menu.mouse_down(x, y)) {
+            self.menu_choice(ctx, choice)?;
+        }
+        Ok(())
+    }
+
+    fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> GameResult {
+        let (x, y) = self.viewport.to_logical(x, y);
+        if button != MouseButton::Left {
+            return Ok(());
+        }
+        let Some(from) = self.press_at.take() else {
+            return Ok(());
+        };
+        let blocked = self.freeze_timer.is_some() || self.scares.is_active() || self.console.open;
+        if self.screen != Screen::Playing || self.game_over || self.tas.is_some() || blocked {
+            return Ok(());
+        }
+        self.pointer_released(ctx, from, x, y)
+    }
+
+    fn mouse_wheel_event(&mut self, ctx: &mut Context, _x: f32, y: f32) -> GameResult {
+        self.note_activity(ctx);
+        match self.screen {
+            Screen::History => self.history_list.scroll(y),
+            Screen::Themes => self.theme_list.scroll(y),
+            Screen::Controls => self.controls_list.scroll(y),
+            Screen::Display => self.display_list.scroll(y),
+            Screen::Accessibility => self.access_list.scroll(y),
+            Screen::Audio => self.audio_list.scroll(y),
+            Screen::Shop => self.shop_list.scroll(y),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resize_event(&mut self, _ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        self.viewport = Viewport::fit(width, height);
+        self.text.set_density(self.viewport.scale());
+        Ok(())
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) -> GameResult {
+        let (x, y) = self.viewport.to_logical(x, y);
+        self.note_activity(ctx);
+        if self.screen == Screen::Playing && self.editing {
+            if ctx.mouse.button_pressed(MouseButton::Left) {
+                self.edit_cell(x, y, true);
+            } else if ctx.mouse.button_pressed(MouseButton::Right) {
+                self.edit_cell(x, y, false);
+            }
+        }
+        if let Some(menu) = self.active_menu() {
+            menu.mouse_motion(x, y);
+        }
+        Ok(())
+    }
+}
+
+impl GameState {
+    // A gameplay action from a key or a gamepad button going down.
+    fn press(&mut self, ctx: &mut Context, action: Option<Action>, repeat: bool) -> GameResult {
+        if action == Some(Action::Pause) {
+            if !self.game_over && !repeat {
+                self.pause(ctx, PauseReason::Manual);
+            }
+            return Ok(());
+        }
+        if action == Some(Action::Undo) {
+            if self.mode == GameMode::Sandbox && !repeat {
+                self.undo();
+            }
+            return Ok(());
+        }
+        self.soft_dropping |= action == Some(Action::SoftDrop);
+        self.apply_action(ctx, action, repeat)
+    }
+
+    fn release(&mut self, ctx: &Context, action: Option<Action>) {
+        let released = match action {
+            Some(Action::MoveLeft) => -1,
+            Some(Action::MoveRight) => 1,
+            Some(Action::SoftDrop) => {
+                self.soft_dropping = false;
+                return;
+            }
+            _ => return,
+        };
+        if released == self.shift_dir {
+            // Fall back to the opposite direction if it's still held, like most modern clients.
+            let other = if released < 0 { Action::MoveRight } else { Action::MoveLeft };
+            if self.held(ctx, other) {
+                self.start_shift(-released);
+            } else {
+                self.shift_dir = 0;
+            }
+        }
+    }
+
+    fn active_menu(&mut self) -> Option<&mut ui::Menu> {
+        match self.screen {
+            Screen::Countdown
+            | Screen::Playing
+            | Screen::Paused
+            | Screen::History
+            | Screen::Replay
+            | Screen::Heatmap
+            | Screen::Stats
+            | Screen::Shame
+            | Screen::Themes
+            | Screen::Controls
+            | Screen::Display
+            | Screen::Accessibility
+            | Screen::Audio
+            | Screen::Shop
+            | Screen::Missions => None,
+            Screen::Title => Some(&mut self.title_menu),
+            Screen::Modes => Some(&mut self.modes_menu),
+            Screen::Results => Some(&mut self.results_menu),
+            Screen::Settings => Some(&mut self.settings_menu),
+        }
+    }
+
+    fn menu_choice(&mut self, ctx: &mut Context, choice: usize) -> GameResult {
+        match self.screen {
+            Screen::Countdown
+            | Screen::Playing
+            | Screen::Paused
+            | Screen::History
+            | Screen::Replay
+            | Screen::Heatmap
+            | Screen::Stats
+            | Screen::Shame
+            | Screen::Themes
+            | Screen::Controls
+            | Screen::Display
+            | Screen::Accessibility
+            | Screen::Audio
+            | Screen::Shop
+            | Screen::Missions => {}
+            Screen::Title => match choice {
+                // Runs need their sounds, so wait for the loader before letting one start.
+                0 if self.loader.is_some() => self.status = Some("Aguarde o carregamento".to_string()),
+                0 => self.open(Screen::Modes),
+                1 => {
+                    self.progress.roll_week(missions::week_number(stats::unix_now()));
+                    self.open(Screen::Missions);
+                }
+                2 => self.open_history(),
+                3 => {
+                    self.view_all = true;
+                    self.open(Screen::Stats);
+                }
+                4 => self.open_heatmap(true),
+                5 => self.open(Screen::Shame),
+                6 => self.open_shop(),
+                _ => self.open(Screen::Settings),
+            },
+            Screen::Modes => match choice {
+                0 => self.start_run(GameMode::Classic),
+                1 => self.start_run(GameMode::Sprint),
+                2 => self.start_run(GameMode::Sandbox),
+                3 => self.next_piece_set(),
+                4 => {
+                    self.settings.big = !self.settings.big;
+                    self.save_settings();
+                    self.refresh_modes_menu();
+                }
+                5 => {
+                    self.settings.instant_gravity = !self.settings.instant_gravity;
+                    self.save_settings();
+                    self.refresh_modes_menu();
+                }
+                6 => {
+                    self.settings.speed_curve = self.settings.speed_curve.next();
+                    self.save_settings();
+                    self.refresh_modes_menu();
+                }
+                7 => {
+                    self.settings.level_goal = self.settings.level_goal.next();
+                    self.save_settings();
+                    self.refresh_modes_menu();
+                }
+                8 => {
+                    self.settings.next_hold_slots();
+                    self.save_settings();
+                    self.refresh_modes_menu();
+                }
+                _ => self.close(),
+            },
+            Screen::Results => match choice {
+                0 => self.start_run(self.mode),
+                1 => {
+                    self.view_all = false;
+                    self.open(Screen::Stats);
+                }
+                2 => self.open_heatmap(false),
+                3 => self.export_csv(),
+                _ => self.go_to_title(ctx)?,
+            },
+            Screen::Settings => match choice {
+                0 => self.open_themes(),
+                1 => {
+                    self.settings.next_auto_pause();
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                2 => {
+                    self.settings.next_scare_level();
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                3 => self.open_audio(),
+                4 => {
+                    self.settings.randomizer = self.settings.randomizer.next();
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                5 => {
+                    self.settings.luck = !self.settings.luck;
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                6 => self.open_display(),
+                7 => self.open_accessibility(),
+                8 => {
+                    self.settings.handling.lock_policy = self.settings.handling.lock_policy.next();
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                9 => {
+                    self.settings.rotation = self.settings.rotation.next();
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                10 => {
+                    self.settings.clear_gravity = self.settings.clear_gravity.next();
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                11 => {
+                    self.settings.items = !self.settings.items;
+                    self.save_settings();
+                    self.refresh_settings_menu();
+                }
+                12 => self.open_controls(),
+                13 => self.export_settings(),
+                14 => self.import_settings(),
+                15 => self.start_sync(),
+                _ => self.close(),
+            },
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum HolePattern {
+    // Every row shares one hole, so the whole batch can be dug out with one well.
+    Clean,
+    // Each row gets its own hole.
+    Messy,
+}
+
+// Changes to the board as a whole. `Grid` is a plain array, so they hang off it as a trait.
+trait Board {
+    // Pushes the stack up by `rows` full rows of garbage, each with a single hole. Holes come
+    // from `randomizer`, so a seed reproduces them.
+    fn add_garbage(&mut self, rows: usize, pattern: HolePattern, randomizer: &mut Randomizer);
+}
+
+impl Board for Grid {
+    fn add_garbage(&mut self, rows: usize, pattern: HolePattern, randomizer: &mut Randomizer) {
+        let mut hole = randomizer.below(GRID_WIDTH);
+        for _ in 0..rows.min(GRID_HEIGHT) {
+            if pattern == HolePattern::Messy {
+                hole = randomizer.below(GRID_WIDTH);
+            }
+            self.copy_within(1.., 0);
+            self[GRID_HEIGHT - 1] = std::array::from_fn(|x| (x != hole).then_some(Cell::garbage()));
+        }
+    }
+}
+
+// Moves every group of connected blocks with nothing under it down one row. Returns whether
+// anything moved.
+fn cascade_step(grid: &mut Grid) -> bool {
+    let mut group = [[usize::MAX; GRID_WIDTH]; GRID_HEIGHT];
+    let mut groups: Vec<Vec<(usize, usize)>> = Vec::new();
+    for y in 0..GRID_HEIGHT {
+        for x in 0..GRID_WIDTH {
+            if grid[y][x].is_none() || group[y][x] != usize::MAX {
+                continue;
+            }
+            let id = groups.len();
+            let mut cells = Vec::new();
+            let mut stack = vec![(x, y)];
+            group[y][x] = id;
+            while let Some((cx, cy)) = stack.pop() {
+                cells.push((cx, cy));
+                let neighbours = [(cx.wrapping_sub(1), cy), (cx + 1, cy), (cx, cy.wrapping_sub(1)), (cx, cy + 1)];
+                for (nx, ny) in neighbours {
+                    if nx < GRID_WIDTH && ny < GRID_HEIGHT && grid[ny][nx].is_some() && group[ny][nx] == usize::MAX {
+                        group[ny][nx] = id;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            groups.push(cells);
+        }
+    }
+
+    // Lowest groups first, so a group resting on a falling one can follow it down.
+    groups.sort_by_key(|cells| std::cmp::Reverse(cells.iter().map(|&(_, y)| y).max()));
+    let mut moved = false;
+    for cells in &mut groups {
+        let id = group[cells[0].1][cells[0].0];
+        let free = cells.iter().all(|&(x, y)| {
+            y + 1 < GRID_HEIGHT && (grid[y + 1][x].is_none() || group[y + 1][x] == id)
+        });
+        if !free {
+            continue;
+        }
+        cells.sort_by_key(|&(_, y)| std::cmp::Reverse(y));
+        for &(x, y) in cells.iter() {
+            grid[y + 1][x] = grid[y][x].take();
+            group[y + 1][x] = id;
+            group[y][x] = usize::MAX;
+        }
+        moved = true;
+    }
+    moved
+}
+
+// Runs a cascade to the end at once, chain clears included.
+fn settle(grid: &mut Grid) {
+    loop {
+        while cascade_step(grid) {}
+        if remove_full_rows(grid) == 0 {
+            return;
+        }
+    }
+}
+
+fn lock_cells(grid: &mut Grid, block: &Block) {
+    let item_cell = block.item_cell();
+    for (y, row) in block.shape.iter().enumerate() {
+        for (x, &cell) in row.iter().enumerate() {
+            if cell {
+                let grid_y = (block.y + y as i32) as usize;
+                let grid_x = (block.x + x as i32) as usize;
+                if grid_y < GRID_HEIGHT {
+                    let item = block.item.filter(|_| item_cell == Some((x, y)));
+                    grid[grid_y][grid_x] = Some(Cell { color: block.color, kind: Some(block.kind), item, garbage: false });
+                }
+            }
+        }
+    }
+}
+
+// Each cell of `shape` blown up to `scale` by `scale` cells.
+fn enlarged(shape: &[Vec<bool>], scale: i32) -> Vec<Vec<bool>> {
+    let scale = scale as usize;
+    shape
+        .iter()
+        .flat_map(|row| {
+            let row: Vec<bool> = row.iter().flat_map(|&cell| [cell].repeat(scale)).collect();
+            vec![row; scale]
+        })
+        .collect()
+}
+
+fn full_rows(grid: &Grid) -> Vec<usize> {
+    (0..GRID_HEIGHT).filter(|&y| grid[y].iter().all(|cell| cell.is_some())).collect()
+}
+
+// Items in the rows about to be cleared.
+fn full_row_items(grid: &Grid) -> Vec<Item> {
+    grid.iter()
+        .filter(|row| row.iter().all(|cell| cell.is_some()))
+        .flatten()
+        .filter_map(|cell| cell.and_then(|cell| cell.item))
+        .collect()
+}
+
+// Gives every settled block of one color another color that was already on the board, so
+// the stack looks scrambled while keeping to the palette. Garbage stays gray. Recolored
+// cells forget their piece, so standard colors don't put them back.
+fn shuffle_colors(grid: &mut Grid, randomizer: &mut Randomizer) {
+    let mut colors: Vec<Color> = Vec::new();
+    for cell in grid.iter().flatten().flatten() {
+        if !cell.garbage && !colors.contains(&cell.color) {
+            colors.push(cell.color);
+        }
+    }
+    for i in (1..colors.len()).rev() {
+        colors.swap(i, randomizer.below(i + 1));
+    }
+    for cell in grid.iter_mut().flatten().flatten().filter(|cell| !cell.garbage) {
+        if let Some(i) = colors.iter().position(|&color| color == cell.color) {
+            cell.color = colors[(i + 1) % colors.len()];
+            cell.kind = None;
+        }
+    }
+}
+
+// Compacts the surviving rows downwards, bottom first, then blanks the rows left at the top.
+fn remove_full_rows(grid: &mut Grid) -> u32 {
+    let mut write = GRID_HEIGHT;
+    for read in (0..GRID_HEIGHT).rev() {
+        if grid[read].iter().all(|cell| cell.is_some()) {
+            continue;
+        }
+        write -= 1;
+        grid[write] = grid[read];
+    }
+    grid[..write].fill([None; GRID_WIDTH]);
+    write as u32
+}
+
+// Where a sound comes from: the sound pack, then the theme, then the game. A pack with its own
+// music decides the stems the same way a theme does.
+fn sound_path(pack: Option<&SoundPack>, theme: Option<&ThemeFile>, sound: Sound) -> Option<String> {
+    let Some(pack) = pack else {
+        return theme_sound(theme, sound);
+    };
+    match pack.path(sound) {
+        Some(path) => Some(path.to_string()),
+        None if sound.is_layer() && pack.path(Sound::Music).is_some() => None,
+        None => theme_sound(theme, sound),
+    }
+}
+
+// The theme's replacement for a sound, or the game's own file. None for a stem of the theme's
+// own music that it didn't bring, since the game's stems wouldn't fit, and for a sound the
+// game has no file for.
+fn theme_sound(theme: Option<&ThemeFile>, sound: Sound) -> Option<String> {
+    let sounds = theme.map(|theme| &theme.sounds);
+    let own_music = sounds.is_some_and(|sounds| sounds.music.is_some());
+    let themed = sounds.and_then(|sounds| match sound {
+        Sound::Death => sounds.death.as_ref(),
+        Sound::Clear => sounds.clear.as_ref(),
+        Sound::Tetris => sounds.tetris.as_ref(),
+        Sound::Start => sounds.start.as_ref(),
+        Sound::Music => sounds.music.as_ref(),
+        Sound::Drums => sounds.layers.first(),
+        Sound::Arpeggio => sounds.layers.get(1),
+        Sound::Move => sounds.shift.as_ref(),
+        Sound::Rotate => sounds.rotate.as_ref(),
+        Sound::Land => sounds.land.as_ref(),
+        Sound::Lock => sounds.lock.as_ref(),
+        Sound::HardDrop => sounds.hard_drop.as_ref(),
+    });
+    match themed {
+        Some(path) => Some(path.clone()),
+        None if own_music && sound.is_layer() => None,
+        None => sound.path().map(str::to_string),
+    }
+}
+
+// Rows from the floor up to and including the highest one with anything in it.
+fn stack_height(grid: &Grid) -> usize {
+    grid.iter().position(|row| row.iter().any(|cell| cell.is_some())).map_or(0, |top| GRID_HEIGHT - top)
+}
+
+// An empty cell with something above it in the same column.
+fn count_holes(grid: &Grid) -> u32 {
+    let mut holes = 0;
+    for x in 0..GRID_WIDTH {
+        let mut covered = false;
+        for row in grid.iter() {
+            match row[x] {
+                Some(_) => covered = true,
+                None if covered => holes += 1,
+                None => {}
+            }
+        }
+    }
+    holes
+}
+
+fn draw_grid(cells: &CellSprites, grid: &Grid, settings: &Settings) {
+    let skin = shop::skin(&settings.skin);
+    for (y, row) in grid.iter().enumerate().skip(BUFFER_ROWS) {
+        for (x, cell) in row.iter().enumerate() {
+            let Some(cell) = cell else {
+                continue;
+            };
+            if cell.garbage {
+                draw_garbage_cell(cells, x as f32, y as f32, skin);
+            } else {
+                draw_cell(cells, x as f32, y as f32, settings.piece_colors.color(cell.kind, cell.color), skin);
+                if settings.cell_patterns {
+                    draw_pattern(cells, x as f32, y as f32, cell.kind);
+                }
+            }
+            if cell.item.is_some() {
+                draw_sparkle(cells, x as f32, y as f32);
+            }
+        }
+    }
+}
+
+// Faint lines between the columns and rows, so it's easier to tell where a piece will go, and
+// a frame along the walls and floor. `grid_opacity` is in percent; 0 leaves just the frame.
+fn draw_frame(canvas: &mut graphics::Canvas, grid_opacity: u32) {
+    let board = Region::Board.rect();
+    if grid_opacity > 0 {
+        let line = Color::new(1.0, 1.0, 1.0, grid_opacity as f32 / 100.0);
+        for x in 1..GRID_WIDTH {
+            ui::fill_rect(canvas, Rect::new(board.x + x as f32 * CELL_SIZE, board.y, 1.0, board.h), line);
+        }
+        for y in 1..VISIBLE_ROWS {
+            ui::fill_rect(canvas, Rect::new(board.x, board.y + y as f32 * CELL_SIZE, board.w, 1.0), line);
+        }
+    }
+    let frame = Color::new(1.0, 1.0, 1.0, 0.5);
+    ui::fill_rect(canvas, Rect::new(board.x, board.y, 2.0, board.h), frame);
+    ui::fill_rect(canvas, Rect::new(board.right() - 2.0, board.y, 2.0, board.h), frame);
+    ui::fill_rect(canvas, Rect::new(board.x, board.bottom() - 2.0, board.w, 2.0), frame);
+}
+
+fn cell_rect(x: f32, y: f32) -> Rect {
+    let board = Region::Board.rect();
+    Rect::new(board.x + x * CELL_SIZE, board.y + (y - BUFFER_ROWS as f32) * CELL_SIZE, CELL_SIZE, CELL_SIZE)
+}
+
+// The grid column and visible row under a point, which may be off the board.
+fn cell_at(x: f32, y: f32) -> (i32, i32) {
+    let board = Region::Board.rect();
+    (((x - board.x) / CELL_SIZE).floor() as i32, ((y - board.y) / CELL_SIZE).floor() as i32)
+}
+
+// A piece's shape in small squares, centered in `area`, for the side panels.
+fn draw_mini_piece(canvas: &mut graphics::Canvas, shape: &[Vec<bool>], area: Rect, color: Color) {
+    const MINI: f32 = 10.0;
+    let cells: Vec<(usize, usize)> = shape
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().filter(|(_, &cell)| cell).map(move |(x, _)| (x, y)))
+        .collect();
+    let (Some(left), Some(top)) = (cells.iter().map(|c| c.0).min(), cells.iter().map(|c| c.1).min()) else {
+        return;
+    };
+    let columns = cells.iter().map(|c| c.0).max().unwrap_or(left) - left + 1;
+    let rows = cells.iter().map(|c| c.1).max().unwrap_or(top) - top + 1;
+    let mini = ((area.w - 8.0) / columns as f32).min((area.h - 8.0) / rows as f32).min(MINI);
+    let x = area.x + (area.w - columns as f32 * mini) / 2.0;
+    let y = area.y + (area.h - rows as f32 * mini) / 2.0;
+    for (cx, cy) in cells {
+        let rect = Rect::new(x + (cx - left) as f32 * mini, y + (cy - top) as f32 * mini, mini - 1.0, mini - 1.0);
+        ui::fill_rect(canvas, rect, color);
+    }
+}
+
+// `x` and `y` are grid coordinates; rows in the buffer end up above the window. Cells are only
+// queued here; they appear at the next `CellSprites::flush`.
+fn draw_cell(cells: &CellSprites, x: f32, y: f32, color: Color, skin: Skin) {
+    let rect = cell_rect(x, y);
+    cells.push_cell(skin, [rect.x, rect.y], color);
+}
+
+fn draw_garbage_cell(cells: &CellSprites, x: f32, y: f32, skin: Skin) {
+    let rect = cell_rect(x, y);
+    cells.push(skin, Tile::Garbage, [rect.x, rect.y], Color::WHITE);
+}
+
+// Nothing for cells that aren't one of the seven standard pieces.
+fn draw_pattern(cells: &CellSprites, x: f32, y: f32, kind: Option<PieceKind>) {
+    if let Some(index) = kind.and_then(PieceKind::index) {
+        let rect = cell_rect(x, y);
+        cells.push_pattern(index, [rect.x, rect.y]);
+    }
+}
+
+fn draw_sparkle(cells: &CellSprites, x: f32, y: f32) {
+    let rect = cell_rect(x, y);
+    cells.push_sparkle([rect.x, rect.y]);
+}
+
+// Over a piece that just locked, fading out as `fade` goes from 1 to 0.
+fn draw_flash_cell(cells: &CellSprites, x: f32, y: f32, fade: f32, skin: Skin) {
+    let rect = cell_rect(x, y);
+    cells.push(skin, Tile::Flash, [rect.x, rect.y], Color::new(1.0, 1.0, 1.0, fade));
+}
+
+// The edges of a ghost cell that aren't shared with another cell of the piece; `neighbors`
+// says which cells of the piece are next to it, clockwise from above.
+fn draw_outline_cell(canvas: &mut graphics::Canvas, x: f32, y: f32, neighbors: [bool; 4], color: Color) {
+    const WIDTH: f32 = 2.0;
+    let rect = cell_rect(x, y);
+    let edges = [
+        Rect::new(rect.x, rect.y, rect.w, WIDTH),
+        Rect::new(rect.right() - WIDTH, rect.y, WIDTH, rect.h),
+        Rect::new(rect.x, rect.bottom() - WIDTH, rect.w, WIDTH),
+        Rect::new(rect.x, rect.y, WIDTH, rect.h),
+    ];
+    for (edge, shared) in edges.into_iter().zip(neighbors) {
+        if !shared {
+            ui::fill_rect(canvas, edge, color);
+        }
+    }
+}
+
+// Where the active block would land.
+fn draw_ghost_cell(cells: &CellSprites, x: f32, y: f32, color: Color, skin: Skin) {
+    let rect = cell_rect(x, y);
+    cells.push(skin, Tile::Ghost, [rect.x, rect.y], color);
+}
+
+// Streaming overlay: a borderless, always-on-top window whose playfield has no background, either
+// truly transparent or a flat green for chroma keying.
+#[derive(Clone, Copy, PartialEq)]
+enum Overlay {
+    Off,
+    Transparent,
+    ChromaKey,
+}
+
+struct Options {
+    tas: Option<TasPlayer>,
+    overlay: Overlay,
+    api_port: Option<u16>,
+}
+
+impl Options {
+    // `--tas <script>` plays a TAS script from the start and `--tas-speed <x>` scales its playback;
+    // `--overlay` and `--chroma-key` pick the overlay window; `--api [port]` serves the live state.
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let flag = |name: &str| args.iter().any(|arg| arg == name);
+        let value = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+        let speed = value("--tas-speed").and_then(|speed| speed.parse::<f32>().ok()).unwrap_or(1.0);
+        let tas = value("--tas").and_then(|path| match TasScript::load(Path::new(path)) {
+            Ok(script) => Some(TasPlayer::new(script, speed.max(0.0))),
+            Err(e) => {
+                eprintln!("failed to load TAS script {}: {}", path, e);
+                None
+            }
+        });
+        let overlay = if flag("--chroma-key") {
+            Overlay::ChromaKey
+        } else if flag("--overlay") {
+            Overlay::Transparent
+        } else {
+            Overlay::Off
+        };
+        let api_port = flag("--api").then(|| {
+            value("--api").and_then(|port| port.parse().ok()).unwrap_or(api::DEFAULT_PORT)
+        });
+        Options { tas, overlay, api_port }
+    }
+}
+
+// The window is SCREEN_WIDTH x SCREEN_HEIGHT physical pixels when it opens, which is tiny on a
+// high-DPI display, so it's grown by the monitor's scale factor, as far as the monitor has
+// room for. Moving it to a monitor with another scale factor resizes it again.
+fn fit_to_display(ctx: &mut Context) -> GameResult {
+    let window = ctx.gfx.window();
+    let mut scale = window.scale_factor() as f32;
+    if let Some(monitor) = window.current_monitor() {
+        let size = monitor.size();
+        scale = scale.min(size.width as f32 * 0.9 / SCREEN_WIDTH).min(size.height as f32 * 0.9 / SCREEN_HEIGHT);
+    }
+    if scale > 1.0 {
+        ctx.gfx.set_drawable_size(SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale)?;
+    }
+    Ok(())
+}
+
+fn main() -> GameResult {
+    let options = Options::from_args();
+    let overlay = options.overlay != Overlay::Off;
+    // Vsync has to be known before the window opens, so the settings are read once early,
+    // from the same directory the game will use.
+    let fs = ggez::filesystem::Filesystem::new("lollypop", "cascade", "resources", "resources.zip")?;
+    let vsync = Settings::load(&fs.user_config_dir().join("settings.toml")).vsync;
+    let cb = ggez::ContextBuilder::new("lollypop", "cascade")
+        .window_setup(ggez::conf::WindowSetup::default().title("Lollypop Tetris").vsync(vsync))
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(SCREEN_WIDTH, SCREEN_HEIGHT)
+                .resizable(true)
+                .resize_on_scale_factor_change(true)
+                .borderless(overlay)
+                .transparent(options.overlay == Overlay::Transparent),
+        )
+        .add_resource_path(RESOURCE_DIR);
 
     let (mut ctx, event_loop) = cb.build()?;
-    let state = GameState::new(&mut ctx)?;
+    fit_to_display(&mut ctx)?;
+    if overlay {
+        ctx.gfx.window().set_window_level(ggez::winit::window::WindowLevel::AlwaysOnTop);
+    }
+    let state = GameState::new(&mut ctx, options)?;
+    state.apply_fullscreen(&mut ctx);
     event::run(ctx, event_loop, state)
 }
@@ -0,0 +1,92 @@
+use crate::stats::{GameMode, GameOutcome, GameRecord};
+
+const SECS_PER_WEEK: u64 = 7 * 86_400;
+// The Unix epoch was a Thursday; shifting by three days makes weeks start on Monday.
+const WEEK_OFFSET: u64 = 3 * 86_400;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Goal {
+    Lines(u32),
+    Tetrises(u32),
+    Pieces(u32),
+    SprintFinishes(u32),
+    ScoreInGame(u32),
+}
+
+impl Goal {
+    pub fn target(&self) -> u32 {
+        match *self {
+            Goal::Lines(n) | Goal::Tetrises(n) | Goal::Pieces(n) | Goal::SprintFinishes(n) | Goal::ScoreInGame(n) => n,
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match *self {
+            Goal::Lines(n) => format!("Limpe {} linhas", n),
+            Goal::Tetrises(n) => format!("Faça {} Tetris", n),
+            Goal::Pieces(n) => format!("Coloque {} peças", n),
+            Goal::SprintFinishes(n) => format!("Complete {} Sprints", n),
+            Goal::ScoreInGame(n) => format!("Faça {} pontos em um jogo", n),
+        }
+    }
+
+    // New progress value after `game`: most goals add up over the week, score goals keep the best game.
+    pub fn advance(&self, progress: u32, game: &GameRecord) -> u32 {
+        match self {
+            Goal::Lines(_) => progress + game.lines(),
+            Goal::Tetrises(_) => progress + game.tetrises,
+            Goal::Pieces(_) => progress + game.pieces,
+            Goal::SprintFinishes(_) => {
+                let finished = game.mode == GameMode::Sprint && game.outcome == GameOutcome::Completed;
+                progress + finished as u32
+            }
+            Goal::ScoreInGame(_) => progress.max(game.score),
+        }
+    }
+}
+
+pub struct Mission {
+    pub goal: Goal,
+    pub reward: u32,
+}
+
+pub fn week_number(unix_secs: u64) -> u64 {
+    (unix_secs + WEEK_OFFSET) / SECS_PER_WEEK
+}
+
+pub fn days_left(unix_secs: u64) -> u64 {
+    let into_week = (unix_secs + WEEK_OFFSET) % SECS_PER_WEEK;
+    (SECS_PER_WEEK - into_week).div_ceil(86_400)
+}
+
+// splitmix64, so every machine derives the same missions from the same week number
+// regardless of the rand version it was built with.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+pub fn weekly(week: u64) -> [Mission; 3] {
+    let mut seed = week;
+    let mut roll = |n: u64| {
+        seed = mix(seed);
+        seed % n
+    };
+    // Each slot draws from its own pool so a week never repeats a goal type.
+    let volume = match roll(2) {
+        0 => Goal::Lines(100 + 25 * roll(5) as u32),
+        _ => Goal::Pieces(300 + 100 * roll(4) as u32),
+    };
+    let skill = match roll(2) {
+        0 => Goal::Tetrises(3 + roll(5) as u32),
+        _ => Goal::SprintFinishes(2 + roll(3) as u32),
+    };
+    let score = Goal::ScoreInGame(2000 + 500 * roll(5) as u32);
+    [
+        Mission { goal: volume, reward: 30 },
+        Mission { goal: skill, reward: 50 },
+        Mission { goal: score, reward: 40 },
+    ]
+}
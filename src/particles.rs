@@ -0,0 +1,118 @@
+use ggez::graphics::{Canvas, Color, DrawParam, InstanceArray, Rect};
+use ggez::Context;
+use rand::Rng;
+use std::cell::RefCell;
+use std::time::Duration;
+
+// Most particles alive at once; a burst past that throws fewer.
+const POOL: usize = 600;
+// Downward pull, in pixels per second squared.
+const GRAVITY: f32 = 900.0;
+// Sides of a particle's square, in pixels.
+const SIZE: f32 = 4.0;
+
+#[derive(Clone, Copy)]
+struct Particle {
+    pos: [f32; 2],
+    vel: [f32; 2],
+    color: Color,
+    // Seconds left, and seconds it started with: it fades out as one runs down to the other.
+    life: f32,
+    lifetime: f32,
+}
+
+const DEAD: Particle = Particle {
+    pos: [0.0, 0.0],
+    vel: [0.0, 0.0],
+    color: Color::WHITE,
+    life: 0.0,
+    lifetime: 1.0,
+};
+
+// Sparks off cleared cells and confetti for perfect clears, drawn over the board. They're
+// cosmetic, so they run on frame time and their own randomness, never the game's seed. The
+// pool is allocated once: live particles sit at the front, and one that dies swaps places
+// with the last live one, so neither bursts nor updates allocate.
+pub struct Particles {
+    pool: Vec<Particle>,
+    live: usize,
+    batch: RefCell<InstanceArray>,
+}
+
+impl Particles {
+    pub fn new(ctx: &Context) -> Self {
+        Particles {
+            pool: vec![DEAD; POOL],
+            live: 0,
+            batch: RefCell::new(InstanceArray::new(ctx, None)),
+        }
+    }
+
+    // `count` sparks flying out of `at` in every direction, mostly upwards, at up to `speed`
+    // pixels per second.
+    pub fn burst(&mut self, at: [f32; 2], color: Color, count: usize, speed: f32) {
+        let mut rng = rand::thread_rng();
+        for _ in 0..count {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let speed = rng.gen_range(0.3..1.0) * speed;
+            let vel = [angle.cos() * speed, angle.sin() * speed - speed * 0.5];
+            self.spawn(at, vel, color, rng.gen_range(0.4..0.9));
+        }
+    }
+
+    // `count` pieces of confetti in `colors`, fluttering down from all over the top half of
+    // `area`.
+    pub fn confetti(&mut self, area: Rect, colors: &[Color], count: usize) {
+        let mut rng = rand::thread_rng();
+        for _ in 0..count {
+            let at = [rng.gen_range(area.x..area.right()), rng.gen_range(area.y..area.y + area.h / 2.0)];
+            let vel = [rng.gen_range(-80.0..80.0), rng.gen_range(-400.0..-100.0)];
+            let color = colors[rng.gen_range(0..colors.len())];
+            self.spawn(at, vel, color, rng.gen_range(1.2..2.2));
+        }
+    }
+
+    fn spawn(&mut self, pos: [f32; 2], vel: [f32; 2], color: Color, lifetime: f32) {
+        if self.live == POOL {
+            return;
+        }
+        self.pool[self.live] = Particle { pos, vel, color, life: lifetime, lifetime };
+        self.live += 1;
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        let mut i = 0;
+        while i < self.live {
+            let particle = &mut self.pool[i];
+            particle.life -= dt;
+            if particle.life <= 0.0 {
+                self.live -= 1;
+                self.pool.swap(i, self.live);
+                continue;
+            }
+            particle.vel[1] += GRAVITY * dt;
+            particle.pos[0] += particle.vel[0] * dt;
+            particle.pos[1] += particle.vel[1] * dt;
+            i += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.live = 0;
+    }
+
+    // Every live particle in one draw call.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let mut batch = self.batch.borrow_mut();
+        batch.clear();
+        for particle in &self.pool[..self.live] {
+            let [x, y] = particle.pos;
+            let fade = particle.life / particle.lifetime;
+            let color = Color::new(particle.color.r, particle.color.g, particle.color.b, particle.color.a * fade);
+            let rect = Rect::new(x - SIZE / 2.0, y - SIZE / 2.0, SIZE, SIZE);
+            batch.push(DrawParam::default().dest_rect(rect).color(color));
+        }
+        canvas.draw(&*batch, DrawParam::default());
+    }
+}
@@ -0,0 +1,144 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// 180° kicks by starting state.
+const KICKS_180: [&[(i32, i32)]; 4] = [
+    &[(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    &[(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    &[(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    &[(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PieceKind {
+    #[default]
+    I,
+    O,
+    T,
+    L,
+    J,
+    S,
+    Z,
+    // A piece from the loaded piece set, by its place in the set.
+    Custom(u8),
+}
+
+// Saved by name, "T", or "custom3" for a piece set's fourth piece.
+impl Serialize for PieceKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            PieceKind::Custom(index) => serializer.collect_str(&format_args!("custom{}", index)),
+            kind => serializer.serialize_str(kind.name()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PieceKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.strip_prefix("custom") {
+            Some(index) => index.parse().map(PieceKind::Custom).map_err(D::Error::custom),
+            None => PieceKind::from_name(&name).ok_or_else(|| D::Error::custom(format!("unknown piece {}", name))),
+        }
+    }
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 7] = [
+        PieceKind::I,
+        PieceKind::O,
+        PieceKind::T,
+        PieceKind::L,
+        PieceKind::J,
+        PieceKind::S,
+        PieceKind::Z,
+    ];
+
+    // Place in `ALL`; custom pieces have none.
+    pub fn index(self) -> Option<usize> {
+        PieceKind::ALL.iter().position(|&kind| kind == self)
+    }
+
+    pub fn from_name(name: &str) -> Option<PieceKind> {
+        PieceKind::ALL.into_iter().find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PieceKind::I => "I",
+            PieceKind::O => "O",
+            PieceKind::T => "T",
+            PieceKind::L => "L",
+            PieceKind::J => "J",
+            PieceKind::S => "S",
+            PieceKind::Z => "Z",
+            PieceKind::Custom(_) => "?",
+        }
+    }
+
+    // Shapes are laid out in their SRS spawn orientation inside the rotation box. Custom
+    // pieces carry their own shape from the piece set; they get a single cell here.
+    pub fn shape(self) -> Vec<Vec<bool>> {
+        match self {
+            PieceKind::I => vec![
+                vec![false, false, false, false],
+                vec![true, true, true, true],
+                vec![false, false, false, false],
+                vec![false, false, false, false],
+            ],
+            PieceKind::O => vec![
+                vec![true, true],
+                vec![true, true],
+            ],
+            PieceKind::T => vec![
+                vec![false, true, false],
+                vec![true, true, true],
+                vec![false, false, false],
+            ],
+            PieceKind::L => vec![
+                vec![true, false, false],
+                vec![true, true, true],
+                vec![false, false, false],
+            ],
+            PieceKind::J => vec![
+                vec![false, false, true],
+                vec![true, true, true],
+                vec![false, false, false],
+            ],
+            PieceKind::S => vec![
+                vec![false, true, true],
+                vec![true, true, false],
+                vec![false, false, false],
+            ],
+            PieceKind::Z => vec![
+                vec![true, true, false],
+                vec![false, true, true],
+                vec![false, false, false],
+            ],
+            PieceKind::Custom(_) => vec![vec![true]],
+        }
+    }
+
+    // SRS wall kicks for rotating from one state to another (0 spawn, 1 right, 2 reversed,
+    // 3 left), tried in order. Offsets are (right, up), as the guideline tables write them.
+    // SRS has no 180° turns; those use the common SRS+ table for every piece.
+    pub fn kicks(self, from: usize, to: usize) -> &'static [(i32, i32)] {
+        match self {
+            PieceKind::O => &[(0, 0)],
+            PieceKind::I => match (from, to) {
+                (0, 1) | (3, 2) => &[(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+                (1, 0) | (2, 3) => &[(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+                (1, 2) | (0, 3) => &[(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+                (2, 1) | (3, 0) => &[(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+                _ => KICKS_180[from],
+            },
+            _ => match (from, to) {
+                (0, 1) | (2, 1) => &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+                (1, 0) | (1, 2) => &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+                (2, 3) | (0, 3) => &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+                (3, 2) | (3, 0) => &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+                _ => KICKS_180[from],
+            },
+        }
+    }
+}
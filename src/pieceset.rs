@@ -0,0 +1,127 @@
+use ggez::graphics::Color;
+use ggez::Context;
+use serde::Deserialize;
+use std::io::Read;
+
+use crate::piece::PieceKind;
+use crate::GRID_WIDTH;
+
+// Other pieces to play with instead of the seven tetrominoes, from TOML files in
+// resource/pieces/:
+//
+//     name = "Trominós"
+//     [[piece]]
+//     name = "I3"
+//     shape = ["...", "###", "..."]
+//     color = [0.9, 0.4, 0.6]
+//
+// Shapes are written in their spawn orientation. Rows are padded out to a square box, which
+// the piece turns about. `color` is optional; pieces without one use the theme's colors.
+#[derive(Deserialize)]
+struct PieceSetFile {
+    name: String,
+    #[serde(default, rename = "piece")]
+    pieces: Vec<PieceDef>,
+}
+
+#[derive(Deserialize)]
+struct PieceDef {
+    name: String,
+    shape: Vec<String>,
+    #[serde(default)]
+    color: Option<[f32; 3]>,
+}
+
+pub struct PieceSet {
+    pub name: String,
+    pieces: Vec<Piece>,
+}
+
+struct Piece {
+    name: String,
+    shape: Vec<Vec<bool>>,
+    color: Option<Color>,
+}
+
+impl PieceSet {
+    // Every valid set in resource/pieces/, by file name. Broken files are reported and skipped.
+    pub fn load_all(ctx: &Context) -> Vec<PieceSet> {
+        let Ok(entries) = ctx.fs.read_dir("/pieces") else {
+            return Vec::new();
+        };
+        let mut paths: Vec<_> = entries.filter(|path| path.extension().is_some_and(|ext| ext == "toml")).collect();
+        paths.sort();
+        let mut sets = Vec::new();
+        for path in paths {
+            let mut data = String::new();
+            let read = ctx.fs.open(&path).map_err(|e| e.to_string()).and_then(|mut file| {
+                file.read_to_string(&mut data).map_err(|e| e.to_string())
+            });
+            let set = read
+                .and_then(|_| toml::from_str(&data).map_err(|e| e.to_string()))
+                .and_then(PieceSet::from_file);
+            match set {
+                Ok(set) => sets.push(set),
+                Err(e) => eprintln!("failed to load piece set {}: {}", path.display(), e),
+            }
+        }
+        sets
+    }
+
+    fn from_file(file: PieceSetFile) -> Result<Self, String> {
+        if file.pieces.is_empty() {
+            return Err("no pieces".to_string());
+        }
+        if file.pieces.len() > u8::MAX as usize + 1 {
+            return Err("too many pieces".to_string());
+        }
+        let pieces = file
+            .pieces
+            .into_iter()
+            .map(|def| {
+                let shape = boxed(&def.shape).ok_or_else(|| format!("piece {} has a bad shape", def.name))?;
+                Ok(Piece {
+                    name: def.name,
+                    shape,
+                    color: def.color.map(|[r, g, b]| Color::new(r, g, b, 1.0)),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(PieceSet { name: file.name, pieces })
+    }
+
+    pub fn kinds(&self) -> Vec<PieceKind> {
+        (0..self.pieces.len()).map(|index| PieceKind::Custom(index as u8)).collect()
+    }
+
+    // Spawn shape of one of this set's pieces, inside its square box.
+    pub fn shape(&self, index: u8) -> Vec<Vec<bool>> {
+        self.pieces[index as usize].shape.clone()
+    }
+
+    pub fn color(&self, index: u8) -> Option<Color> {
+        self.pieces[index as usize].color
+    }
+
+    pub fn piece_name(&self, index: u8) -> &str {
+        &self.pieces[index as usize].name
+    }
+}
+
+// Parses `#`/`.` rows and centers them in a square box. Fails on shapes with no cells or
+// wider than the board.
+fn boxed(rows: &[String]) -> Option<Vec<Vec<bool>>> {
+    let width = rows.iter().map(|row| row.chars().count()).max()?;
+    let size = width.max(rows.len());
+    if size > GRID_WIDTH || !rows.iter().any(|row| row.contains('#')) {
+        return None;
+    }
+    let (top, left) = ((size - rows.len()) / 2, (size - width) / 2);
+    let mut shape = vec![vec![false; size]; size];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            shape[top + y][left + x] = c == '#';
+        }
+    }
+    Some(shape)
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+use crate::missions::{self, Mission};
+use crate::secret::SECRETS;
+use crate::shop::ShopItem;
+use crate::stats::GameRecord;
+use crate::storage;
+
+pub struct Milestone {
+    pub deaths: u32,
+    pub name: &'static str,
+    pub description: &'static str,
+    // Theme from config::THEMES that stays hidden until the milestone is reached.
+    pub theme: &'static str,
+}
+
+pub const MILESTONES: [Milestone; 3] = [
+    Milestone {
+        deaths: 10,
+        name: "Freguês",
+        description: "Tema luto",
+        theme: "luto",
+    },
+    Milestone {
+        deaths: 50,
+        name: "Saco de pancada",
+        description: "Tema sangue",
+        theme: "sangue",
+    },
+    Milestone {
+        deaths: 100,
+        name: "Lenda da vergonha",
+        description: "Tema ouro de tolo",
+        theme: "ouro de tolo",
+    },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Achievement {
+    FirstTetris,
+    SprintUnderTwoMinutes,
+    WeeklySweep,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 3] = [
+        Achievement::FirstTetris,
+        Achievement::SprintUnderTwoMinutes,
+        Achievement::WeeklySweep,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "Faça um Tetris",
+            Achievement::SprintUnderTwoMinutes => "Sprint em menos de 2 minutos",
+            Achievement::WeeklySweep => "Complete todas as missões de uma semana",
+        }
+    }
+
+    pub fn theme(&self) -> &'static str {
+        match self {
+            Achievement::FirstTetris => "tetris",
+            Achievement::SprintUnderTwoMinutes => "relâmpago",
+            Achievement::WeeklySweep => "arco-íris",
+        }
+    }
+}
+
+pub enum Requirement {
+    Deaths(u32),
+    Secret,
+    Achievement(Achievement),
+}
+
+impl Requirement {
+    pub fn label(&self) -> String {
+        match self {
+            Requirement::Deaths(deaths) => format!("Morra {} vezes", deaths),
+            Requirement::Secret => "Segredo".to_string(),
+            Requirement::Achievement(achievement) => achievement.name().to_string(),
+        }
+    }
+}
+
+// What it takes to unlock a theme, or None for themes that are always available.
+pub fn theme_requirement(theme: &str) -> Option<Requirement> {
+    if let Some(milestone) = MILESTONES.iter().find(|milestone| milestone.theme == theme) {
+        return Some(Requirement::Deaths(milestone.deaths));
+    }
+    if SECRETS.iter().any(|secret| secret.theme == theme) {
+        return Some(Requirement::Secret);
+    }
+    Achievement::ALL
+        .into_iter()
+        .find(|achievement| achievement.theme() == theme)
+        .map(Requirement::Achievement)
+}
+
+// Lifetime progress that outlives a single session, unlike the per-session death count.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub deaths: u32,
+    pub unlocked: Vec<String>,
+    pub achievements: Vec<Achievement>,
+    pub lollipops: u32,
+    // Shop item ids bought with lollipops.
+    pub owned: Vec<String>,
+    pub mission_week: u64,
+    pub mission_progress: [u32; 3],
+    pub missions_done: [bool; 3],
+}
+
+impl Progress {
+    pub fn load(path: &Path) -> Self {
+        storage::load_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::save_toml(self, path)
+    }
+
+    pub fn unlock(&mut self, name: &str) -> bool {
+        if self.is_unlocked(name) {
+            return false;
+        }
+        self.unlocked.push(name.to_string());
+        true
+    }
+
+    pub fn award(&mut self, achievement: Achievement) -> bool {
+        if self.achievements.contains(&achievement) {
+            return false;
+        }
+        self.achievements.push(achievement);
+        true
+    }
+
+    // Counts a death and returns the milestone it just reached, if any.
+    pub fn record_death(&mut self) -> Option<&'static Milestone> {
+        self.deaths += 1;
+        MILESTONES.iter().find(|milestone| milestone.deaths == self.deaths)
+    }
+
+    pub fn is_unlocked(&self, name: &str) -> bool {
+        self.unlocked.iter().any(|unlocked| unlocked == name)
+    }
+
+    pub fn theme_available(&self, theme: &str) -> bool {
+        match theme_requirement(theme) {
+            None => true,
+            Some(Requirement::Deaths(deaths)) => self.deaths >= deaths,
+            Some(Requirement::Secret) => self.is_unlocked(theme),
+            Some(Requirement::Achievement(achievement)) => self.achievements.contains(&achievement),
+        }
+    }
+
+    pub fn owns(&self, item: &ShopItem) -> bool {
+        item.price == 0 || self.owned.iter().any(|owned| owned == item.id)
+    }
+
+    pub fn buy(&mut self, item: &ShopItem) -> bool {
+        if self.owns(item) || self.lollipops < item.price {
+            return false;
+        }
+        self.lollipops -= item.price;
+        self.owned.push(item.id.to_string());
+        true
+    }
+
+    // Progress from an older week is thrown away once that week's missions have rotated out.
+    pub fn roll_week(&mut self, week: u64) {
+        if self.mission_week != week {
+            self.mission_week = week;
+            self.mission_progress = [0; 3];
+            self.missions_done = [false; 3];
+        }
+    }
+
+    // Advances this week's missions with a finished game and returns the ones it completed.
+    pub fn record_missions(&mut self, game: &GameRecord) -> Vec<Mission> {
+        self.roll_week(missions::week_number(game.timestamp));
+        let mut completed = Vec::new();
+        for (i, mission) in missions::weekly(self.mission_week).into_iter().enumerate() {
+            self.mission_progress[i] = mission.goal.advance(self.mission_progress[i], game);
+            if !self.missions_done[i] && self.mission_progress[i] >= mission.goal.target() {
+                self.missions_done[i] = true;
+                completed.push(mission);
+            }
+        }
+        completed
+    }
+
+    pub fn next_milestone(&self) -> Option<&'static Milestone> {
+        MILESTONES.iter().find(|milestone| self.deaths < milestone.deaths)
+    }
+}
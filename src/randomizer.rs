@@ -0,0 +1,185 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::piece::PieceKind;
+
+// With luck on, no more than this many pieces go by without an I...
+const MAX_I_GAP: u32 = 12;
+// ...and S/Z never come more than this many times in a row.
+const MAX_SZ_RUN: u32 = 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RandomizerKind {
+    #[default]
+    Random,
+    Bag,
+}
+
+impl RandomizerKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RandomizerKind::Random => "aleatório",
+            RandomizerKind::Bag => "saco de 7",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            RandomizerKind::Random => RandomizerKind::Bag,
+            RandomizerKind::Bag => RandomizerKind::Random,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Randomizer {
+    rng: StdRng,
+    bag: Vec<PieceKind>,
+    // How many bags have been opened.
+    bags: u64,
+    since_i: u32,
+    sz_run: u32,
+}
+
+impl Randomizer {
+    pub fn new() -> Self {
+        Randomizer {
+            rng: StdRng::from_entropy(),
+            bag: Vec::new(),
+            bags: 0,
+            since_i: 0,
+            sz_run: 0,
+        }
+    }
+
+    // Deterministic piece sequence from here on, starting from a fresh bag.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.bag.clear();
+        self.bags = 0;
+        self.since_i = 0;
+        self.sz_run = 0;
+    }
+
+    // Deals one of `pieces`. Luck only has a say when they're the standard seven.
+    pub fn next(&mut self, kind: RandomizerKind, luck: bool, pieces: &[PieceKind]) -> PieceKind {
+        let luck = luck && pieces.contains(&PieceKind::I);
+        let piece = match kind {
+            RandomizerKind::Random => {
+                let mut piece = self.roll(pieces);
+                if luck {
+                    if self.since_i >= MAX_I_GAP {
+                        piece = PieceKind::I;
+                    }
+                    while self.sz_run >= MAX_SZ_RUN && is_sz(piece) {
+                        piece = self.roll(pieces);
+                    }
+                }
+                piece
+            }
+            RandomizerKind::Bag => {
+                if self.bag.is_empty() {
+                    self.bag = pieces.to_vec();
+                    self.bag.shuffle(&mut self.rng);
+                    self.bags += 1;
+                }
+                // Pieces are dealt from the end of the bag; luck only reorders what's left in it.
+                if luck {
+                    let pick = if self.since_i >= MAX_I_GAP {
+                        self.bag.iter().position(|&p| p == PieceKind::I)
+                    } else if self.sz_run >= MAX_SZ_RUN && is_sz(*self.bag.last().unwrap()) {
+                        self.bag.iter().rposition(|&p| !is_sz(p))
+                    } else {
+                        None
+                    };
+                    if let Some(index) = pick {
+                        let last = self.bag.len() - 1;
+                        self.bag.swap(index, last);
+                    }
+                }
+                self.bag.pop().unwrap()
+            }
+        };
+        self.since_i = if piece == PieceKind::I { 0 } else { self.since_i + 1 };
+        self.sz_run = if is_sz(piece) { self.sz_run + 1 } else { 0 };
+        piece
+    }
+
+    // Which bag the last piece was dealt from, counting from 1; always 0 for the random
+    // randomizer.
+    pub fn bag(&self) -> u64 {
+        self.bags
+    }
+
+    // Pieces still to be dealt from the current bag; always empty for the random randomizer.
+    pub fn remaining_bag(&self) -> &[PieceKind] {
+        &self.bag
+    }
+
+    // Any other roll the simulation needs, so one seed covers the whole game.
+    pub fn below(&mut self, n: usize) -> usize {
+        self.rng.gen_range(0..n)
+    }
+
+    fn roll(&mut self, pieces: &[PieceKind]) -> PieceKind {
+        pieces[self.rng.gen_range(0..pieces.len())]
+    }
+}
+
+fn is_sz(piece: PieceKind) -> bool {
+    matches!(piece, PieceKind::S | PieceKind::Z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deal(randomizer: &mut Randomizer, kind: RandomizerKind, luck: bool, count: usize) -> Vec<PieceKind> {
+        (0..count).map(|_| randomizer.next(kind, luck, &PieceKind::ALL)).collect()
+    }
+
+    #[test]
+    fn bags_hold_each_piece_once() {
+        let mut randomizer = Randomizer::new();
+        randomizer.reseed(7);
+        for bag in 1..=5 {
+            let mut pieces = deal(&mut randomizer, RandomizerKind::Bag, false, 7);
+            assert_eq!(randomizer.bag(), bag);
+            assert!(randomizer.remaining_bag().is_empty());
+            pieces.sort_by_key(|kind| kind.index());
+            assert_eq!(pieces, PieceKind::ALL);
+        }
+    }
+
+    #[test]
+    fn seeds_repeat() {
+        let (mut a, mut b) = (Randomizer::new(), Randomizer::new());
+        a.reseed(42);
+        b.reseed(42);
+        assert_eq!(deal(&mut a, RandomizerKind::Random, true, 100), deal(&mut b, RandomizerKind::Random, true, 100));
+    }
+
+    #[test]
+    fn luck_caps_droughts_and_floods() {
+        for kind in [RandomizerKind::Random, RandomizerKind::Bag] {
+            let mut randomizer = Randomizer::new();
+            randomizer.reseed(3);
+            let pieces = deal(&mut randomizer, kind, true, 2000);
+            let mut since_i = 0;
+            let mut sz_run = 0;
+            for piece in pieces {
+                since_i = if piece == PieceKind::I { 0 } else { since_i + 1 };
+                sz_run = if is_sz(piece) { sz_run + 1 } else { 0 };
+                assert!(since_i <= MAX_I_GAP);
+                assert!(sz_run <= MAX_SZ_RUN);
+            }
+        }
+    }
+}
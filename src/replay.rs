@@ -0,0 +1,122 @@
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::piece::PieceKind;
+use crate::stats::GameMode;
+use crate::{lock_cells, remove_full_rows, settle, storage, Block, Grid, BUFFER_ROWS, EMPTY_GRID};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Placement {
+    pub time_ms: u64,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub kind: PieceKind,
+    pub shape: Vec<Vec<bool>>,
+    pub color: [f32; 4],
+}
+
+// A replay is the list of locked placements; replaying them through the same
+// lock/clear code rebuilds every board state the player saw.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub mode: GameMode,
+    pub placements: Vec<Placement>,
+    // Hidden rows above the board when it was recorded; older replays have none.
+    #[serde(default)]
+    pub buffer_rows: usize,
+    // Blocks fell in connected groups after clears (sticky clear gravity).
+    #[serde(default)]
+    pub cascade: bool,
+}
+
+impl Replay {
+    pub fn new(mode: GameMode) -> Self {
+        Replay {
+            mode,
+            placements: Vec::new(),
+            buffer_rows: BUFFER_ROWS,
+            cascade: false,
+        }
+    }
+
+    pub fn record(&mut self, time: Duration, block: &Block) {
+        self.placements.push(Placement {
+            time_ms: time.as_millis() as u64,
+            x: block.x,
+            y: block.y,
+            kind: block.kind,
+            shape: block.shape.clone(),
+            color: block.color.into(),
+        });
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut replay: Replay = storage::read_toml(path)?;
+        let shift = BUFFER_ROWS as i32 - replay.buffer_rows as i32;
+        for placement in &mut replay.placements {
+            placement.y += shift;
+        }
+        replay.buffer_rows = BUFFER_ROWS;
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::save_toml(self, path)
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.placements.last().map(|p| p.time_ms).unwrap_or(0))
+    }
+}
+
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    pub grid: Grid,
+    pub elapsed: Duration,
+    next: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            replay,
+            grid: EMPTY_GRID,
+            elapsed: Duration::ZERO,
+            next: 0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.replay.placements.len()
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.elapsed += dt;
+        let now_ms = self.elapsed.as_millis() as u64;
+        while let Some(placement) = self.replay.placements.get(self.next) {
+            if placement.time_ms > now_ms {
+                break;
+            }
+            let block = Block {
+                x: placement.x,
+                y: placement.y,
+                kind: placement.kind,
+                shape: placement.shape.clone(),
+                rotation: 0,
+                color: Color::from(placement.color),
+                item: None,
+                scale: 1,
+                bag: 0,
+            };
+            lock_cells(&mut self.grid, &block);
+            if remove_full_rows(&mut self.grid) > 0 && self.replay.cascade {
+                settle(&mut self.grid);
+            }
+            self.next += 1;
+        }
+    }
+}
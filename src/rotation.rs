@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::piece::PieceKind;
+
+// How pieces look in each rotation state and where they may be nudged when a turn doesn't fit.
+// States go 0 (spawn), 1 (right), 2 (reversed), 3 (left).
+pub trait RotationSystem {
+    // The piece in `rotation` state, laid out inside its rotation box.
+    fn shape(&self, kind: PieceKind, rotation: usize) -> Vec<Vec<bool>>;
+
+    // Offsets (right, up) tried in order when turning from `from` to `to` doesn't fit as is.
+    // `blocked` is the first cell of the new shape, in reading order, that overlaps the stack.
+    fn kicks(&self, kind: PieceKind, from: usize, to: usize, blocked: (usize, usize)) -> &'static [(i32, i32)];
+
+    // Where the T's center sits in its box in `rotation` state, for the three-corner rule.
+    // SRS keeps it in the middle in every orientation.
+    fn t_center(&self, _rotation: usize) -> (i32, i32) {
+        (1, 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationKind {
+    #[default]
+    Srs,
+    Ars,
+    NoKick,
+}
+
+impl RotationKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RotationKind::Srs => "SRS",
+            RotationKind::Ars => "ARS",
+            RotationKind::NoKick => "sem chutes",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            RotationKind::Srs => RotationKind::Ars,
+            RotationKind::Ars => RotationKind::NoKick,
+            RotationKind::NoKick => RotationKind::Srs,
+        }
+    }
+
+    pub fn system(&self) -> &'static dyn RotationSystem {
+        match self {
+            RotationKind::Srs => &Srs,
+            RotationKind::Ars => &Ars,
+            RotationKind::NoKick => &NoKick,
+        }
+    }
+}
+
+// The guideline system, with the SRS+ table for 180° turns.
+pub struct Srs;
+
+// Arika's classic system: pieces spawn flat side up and sit at the bottom of their box, kicks
+// are one cell right then one cell left, and the I never kicks.
+pub struct Ars;
+
+// SRS shapes, but a turn that doesn't fit where the piece is simply fails.
+pub struct NoKick;
+
+impl RotationSystem for Srs {
+    fn shape(&self, kind: PieceKind, rotation: usize) -> Vec<Vec<bool>> {
+        srs_shape(kind, rotation)
+    }
+
+    fn kicks(&self, kind: PieceKind, from: usize, to: usize, _blocked: (usize, usize)) -> &'static [(i32, i32)] {
+        kind.kicks(from, to)
+    }
+}
+
+impl RotationSystem for Ars {
+    fn shape(&self, kind: PieceKind, rotation: usize) -> Vec<Vec<bool>> {
+        let rows: [&str; 3] = match (kind, rotation % 4) {
+            (PieceKind::I, 0 | 2) => return cells(&["....", "####", "....", "...."]),
+            (PieceKind::I, _) => return cells(&["..#.", "..#.", "..#.", "..#."]),
+            (PieceKind::O, _) => return cells(&["##", "##"]),
+            (PieceKind::T, 0) => ["...", "###", ".#."],
+            (PieceKind::T, 1) => [".#.", "##.", ".#."],
+            (PieceKind::T, 2) => ["...", ".#.", "###"],
+            (PieceKind::T, _) => [".#.", ".##", ".#."],
+            (PieceKind::L, 0) => ["...", "###", "#.."],
+            (PieceKind::L, 1) => ["##.", ".#.", ".#."],
+            (PieceKind::L, 2) => ["...", "..#", "###"],
+            (PieceKind::L, _) => [".#.", ".#.", ".##"],
+            (PieceKind::J, 0) => ["...", "###", "..#"],
+            (PieceKind::J, 1) => [".#.", ".#.", "##."],
+            (PieceKind::J, 2) => ["...", "#..", "###"],
+            (PieceKind::J, _) => [".##", ".#.", ".#."],
+            (PieceKind::S, 0 | 2) => ["...", ".##", "##."],
+            (PieceKind::S, _) => ["#..", "##.", ".#."],
+            (PieceKind::Z, 0 | 2) => ["...", "##.", ".##"],
+            (PieceKind::Z, _) => ["..#", ".##", ".#."],
+            (PieceKind::Custom(_), _) => return srs_shape(kind, rotation),
+        };
+        cells(&rows)
+    }
+
+    fn kicks(&self, kind: PieceKind, _from: usize, _to: usize, blocked: (usize, usize)) -> &'static [(i32, i32)] {
+        match kind {
+            PieceKind::I | PieceKind::O => &[],
+            // An L, J or T stopped by something in its middle column stays put.
+            PieceKind::L | PieceKind::J | PieceKind::T if blocked.0 == 1 => &[],
+            _ => &[(1, 0), (-1, 0)],
+        }
+    }
+
+    // Flat side down, the T rests on the bottom row of its box.
+    fn t_center(&self, rotation: usize) -> (i32, i32) {
+        if rotation % 4 == 2 {
+            (1, 2)
+        } else {
+            (1, 1)
+        }
+    }
+}
+
+impl RotationSystem for NoKick {
+    fn shape(&self, kind: PieceKind, rotation: usize) -> Vec<Vec<bool>> {
+        srs_shape(kind, rotation)
+    }
+
+    fn kicks(&self, _kind: PieceKind, _from: usize, _to: usize, _blocked: (usize, usize)) -> &'static [(i32, i32)] {
+        &[]
+    }
+}
+
+fn srs_shape(kind: PieceKind, rotation: usize) -> Vec<Vec<bool>> {
+    turned(&kind.shape(), rotation)
+}
+
+// `shape` turned `turns` quarter turns clockwise about the middle of its box.
+pub fn turned(shape: &[Vec<bool>], turns: usize) -> Vec<Vec<bool>> {
+    let mut shape = shape.to_vec();
+    for _ in 0..turns % 4 {
+        shape = rotated_cw(&shape);
+    }
+    shape
+}
+
+fn rotated_cw(shape: &[Vec<bool>]) -> Vec<Vec<bool>> {
+    let rows = shape.len();
+    let cols = shape[0].len();
+    let mut rotated = vec![vec![false; rows]; cols];
+    for (y, row) in shape.iter().enumerate() {
+        for (x, &cell) in row.iter().enumerate() {
+            rotated[x][rows - 1 - y] = cell;
+        }
+    }
+    rotated
+}
+
+fn cells(rows: &[&str]) -> Vec<Vec<bool>> {
+    rows.iter().map(|row| row.chars().map(|c| c == '#').collect()).collect()
+}
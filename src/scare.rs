@@ -0,0 +1,239 @@
+use ggez::audio::{self, SoundData};
+use ggez::graphics::{Canvas, Color, DrawParam, Image, Rect};
+use ggez::{Context, GameResult};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::sound::AudioManager;
+use crate::storage;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    // Fires on the `at`-th death of the session.
+    Death,
+    // Fires once per run when the score reaches `at`.
+    Score,
+    // Fires once per run when `at` lines have been cleared.
+    Lines,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScareEvent {
+    pub image: String,
+    #[serde(default)]
+    pub sound: Option<String>,
+    #[serde(default = "default_duration_ms")]
+    pub duration_ms: u64,
+    pub trigger: Trigger,
+    #[serde(default = "default_at")]
+    pub at: u32,
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+fn default_duration_ms() -> u64 {
+    1500
+}
+
+fn default_at() -> u32 {
+    1
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ScareManifest {
+    #[serde(default, rename = "scare")]
+    pub scares: Vec<ScareEvent>,
+}
+
+impl ScareManifest {
+    // A scares.toml in the config dir replaces the one shipped in resource/; image and
+    // sound paths are resolved through the resource filesystem either way.
+    pub fn load(ctx: &Context, config_dir: &Path) -> Self {
+        let user_path = config_dir.join("scares.toml");
+        if user_path.exists() {
+            return match storage::read_toml(&user_path) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    eprintln!("failed to load {}: {}", user_path.display(), e);
+                    ScareManifest::default()
+                }
+            };
+        }
+        let mut data = String::new();
+        match ctx.fs.open("/scares.toml") {
+            Ok(mut file) => {
+                if let Err(e) = file.read_to_string(&mut data) {
+                    eprintln!("failed to read scares.toml: {}", e);
+                }
+            }
+            Err(_) => return ScareManifest::default(),
+        }
+        toml::from_str(&data).unwrap_or_else(|e| {
+            eprintln!("failed to parse scares.toml: {}", e);
+            ScareManifest::default()
+        })
+    }
+}
+
+struct ActiveScare {
+    image: Image,
+    started: Duration,
+    duration: Duration,
+    intensity: f32,
+}
+
+pub struct Scares {
+    manifest: ScareManifest,
+    fired: Vec<bool>,
+    active: Option<ActiveScare>,
+    // Filled in by the asset loader; anything missing is loaded when its scare fires.
+    images: HashMap<String, Image>,
+    sounds: HashMap<String, SoundData>,
+}
+
+impl Scares {
+    pub fn new(manifest: ScareManifest) -> Self {
+        let fired = vec![false; manifest.scares.len()];
+        Scares {
+            manifest,
+            fired,
+            active: None,
+            images: HashMap::new(),
+            sounds: HashMap::new(),
+        }
+    }
+
+    // Every image and sound the manifest refers to.
+    pub fn asset_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        for scare in &self.manifest.scares {
+            paths.push(scare.image.clone());
+            paths.extend(scare.sound.clone());
+        }
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    pub fn preload(&mut self, ctx: &Context, path: &str, bytes: &[u8]) {
+        if self.manifest.scares.iter().any(|scare| scare.image == path) {
+            match Image::from_bytes(ctx, bytes) {
+                Ok(image) => {
+                    self.images.insert(path.to_string(), image);
+                }
+                Err(e) => eprintln!("failed to decode scare image {}: {}", path, e),
+            }
+        } else {
+            self.sounds.insert(path.to_string(), SoundData::from_bytes(bytes));
+        }
+    }
+
+    // Score and line scares may fire again in the next run; death scares count the whole session.
+    pub fn reset_run(&mut self) {
+        for (fired, scare) in self.fired.iter_mut().zip(&self.manifest.scares) {
+            if scare.trigger != Trigger::Death {
+                *fired = false;
+            }
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    // `level` is the global intensity from the settings; 0 turns scares off entirely. The
+    // sound plays through `audio`, at the effects volume.
+    pub fn check(
+        &mut self,
+        ctx: &mut Context,
+        trigger: Trigger,
+        value: u32,
+        level: f32,
+        audio: &AudioManager,
+    ) -> GameResult {
+        if level <= 0.0 || self.active.is_some() {
+            return Ok(());
+        }
+        let Some(index) = self.manifest.scares.iter().zip(&self.fired).position(|(scare, &fired)| {
+            !fired
+                && scare.trigger == trigger
+                && match trigger {
+                    Trigger::Death => value == scare.at,
+                    Trigger::Score | Trigger::Lines => value >= scare.at,
+                }
+        }) else {
+            return Ok(());
+        };
+        self.fired[index] = true;
+
+        let scare = &self.manifest.scares[index];
+        let intensity = (scare.intensity * level).clamp(0.0, 1.0);
+        let image = match self.images.get(&scare.image) {
+            Some(image) => image.clone(),
+            None => match Image::from_path(ctx, &scare.image) {
+                Ok(image) => image,
+                Err(e) => {
+                    eprintln!("failed to load scare image {}: {}", scare.image, e);
+                    return Ok(());
+                }
+            },
+        };
+        if let Some(sound) = &scare.sound {
+            let source = match self.sounds.get(sound) {
+                Some(data) => audio::Source::from_data(ctx, data.clone()),
+                None => audio::Source::new(ctx, sound),
+            };
+            match source {
+                Ok(source) => audio.play_clip(ctx, source, intensity)?,
+                Err(e) => eprintln!("failed to load scare sound {}: {}", sound, e),
+            }
+        }
+        self.active = Some(ActiveScare {
+            image,
+            started: ctx.time.time_since_start(),
+            duration: Duration::from_millis(scare.duration_ms),
+            intensity,
+        });
+        Ok(())
+    }
+
+    pub fn update(&mut self, now: Duration) {
+        if let Some(active) = &self.active {
+            if now - active.started >= active.duration {
+                self.active = None;
+            }
+        }
+    }
+
+    // Stretched over `area`, the whole window in logical coordinates, even the parts the
+    // letterboxed game leaves empty, and shaken harder the higher the intensity; weaker scares
+    // are also drawn partly transparent.
+    pub fn draw(&self, canvas: &mut Canvas, area: Rect) {
+        let Some(active) = &self.active else {
+            return;
+        };
+        let mut rng = rand::thread_rng();
+        let shake = 12.0 * active.intensity;
+        let offset = [rng.gen_range(-1.0..=1.0) * shake, rng.gen_range(-1.0..=1.0) * shake];
+        let scale = [
+            (area.w + shake * 2.0) / active.image.width() as f32,
+            (area.h + shake * 2.0) / active.image.height() as f32,
+        ];
+        canvas.draw(
+            &active.image,
+            DrawParam::default()
+                .dest([area.x + offset[0] - shake, area.y + offset[1] - shake])
+                .scale(scale)
+                .color(Color::new(1.0, 1.0, 1.0, 0.4 + 0.6 * active.intensity)),
+        );
+    }
+}
@@ -0,0 +1,67 @@
+// How points are awarded. Only the guideline table exists so far; other schemes (NES, TGM grade
+// points) would be new variants with their own numbers.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScoreTable {
+    #[default]
+    Guideline,
+}
+
+impl ScoreTable {
+    // A placement clearing `lines` rows (possibly none), before any back-to-back bonus. `level`
+    // counts from 0 here, while the guideline multiplies by levels counted from 1.
+    pub fn clear(self, lines: u32, spin: bool, level: u32) -> u32 {
+        let base = match self {
+            ScoreTable::Guideline => match (spin, lines) {
+                (false, 0) => 0,
+                (false, 1) => 100,
+                (false, 2) => 300,
+                (false, 3) => 500,
+                (false, _) => 800,
+                (true, 0) => 400,
+                (true, 1) => 800,
+                (true, 2) => 1200,
+                (true, _) => 1600,
+            },
+        };
+        base * (level + 1)
+    }
+
+    // Clears that keep a back-to-back chain going; any other line clear breaks it.
+    pub fn difficult(self, lines: u32, spin: bool) -> bool {
+        match self {
+            ScoreTable::Guideline => lines >= 4 || (spin && lines > 0),
+        }
+    }
+
+    pub fn back_to_back(self, points: u32) -> u32 {
+        match self {
+            ScoreTable::Guideline => points * 3 / 2,
+        }
+    }
+
+    // `combo` is how many clears in a row came before this one.
+    pub fn combo(self, combo: u32, level: u32) -> u32 {
+        match self {
+            ScoreTable::Guideline => 50 * combo * (level + 1),
+        }
+    }
+
+    pub fn perfect_clear(self, lines: u32, level: u32) -> u32 {
+        let base = match self {
+            ScoreTable::Guideline => [800, 1200, 1800, 2000][(lines as usize).clamp(1, 4) - 1],
+        };
+        base * (level + 1)
+    }
+
+    pub fn soft_drop(self, rows: u32) -> u32 {
+        match self {
+            ScoreTable::Guideline => rows,
+        }
+    }
+
+    pub fn hard_drop(self, rows: u32) -> u32 {
+        match self {
+            ScoreTable::Guideline => rows * 2,
+        }
+    }
+}
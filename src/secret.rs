@@ -0,0 +1,44 @@
+use ggez::input::keyboard::KeyCode;
+
+pub struct Secret {
+    pub name: &'static str,
+    pub sequence: &'static [KeyCode],
+    // Theme from config::THEMES that the sequence unlocks.
+    pub theme: &'static str,
+}
+
+pub const SECRETS: [Secret; 1] = [Secret {
+    name: "Código Konami",
+    sequence: &[
+        KeyCode::Up,
+        KeyCode::Up,
+        KeyCode::Down,
+        KeyCode::Down,
+        KeyCode::Left,
+        KeyCode::Right,
+        KeyCode::Left,
+        KeyCode::Right,
+        KeyCode::B,
+        KeyCode::A,
+    ],
+    theme: "retrô",
+}];
+
+// Keeps the last few keys pressed and reports a secret whenever they end with its sequence.
+#[derive(Default)]
+pub struct SequenceDetector {
+    recent: Vec<KeyCode>,
+}
+
+impl SequenceDetector {
+    pub fn key(&mut self, keycode: KeyCode) -> Option<&'static Secret> {
+        let longest = SECRETS.iter().map(|secret| secret.sequence.len()).max().unwrap_or(0);
+        self.recent.push(keycode);
+        if self.recent.len() > longest {
+            self.recent.remove(0);
+        }
+        let secret = SECRETS.iter().find(|secret| self.recent.ends_with(secret.sequence))?;
+        self.recent.clear();
+        Some(secret)
+    }
+}
@@ -0,0 +1,142 @@
+use ggez::graphics::Color;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Skin {
+    Flat,
+    Candy,
+    Glass,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cosmetic {
+    Skin(Skin),
+    Background(Color),
+    // Pitch ratio applied to every sound effect.
+    SfxPack(f32),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Skin,
+    Background,
+    SfxPack,
+}
+
+impl Category {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Category::Skin => "blocos",
+            Category::Background => "fundo",
+            Category::SfxPack => "sons",
+        }
+    }
+}
+
+pub struct ShopItem {
+    pub id: &'static str,
+    pub price: u32,
+    pub cosmetic: Cosmetic,
+}
+
+impl ShopItem {
+    pub fn category(&self) -> Category {
+        match self.cosmetic {
+            Cosmetic::Skin(_) => Category::Skin,
+            Cosmetic::Background(_) => Category::Background,
+            Cosmetic::SfxPack(_) => Category::SfxPack,
+        }
+    }
+}
+
+// Free items are the defaults and are always owned.
+pub const ITEMS: [ShopItem; 9] = [
+    ShopItem {
+        id: "liso",
+        price: 0,
+        cosmetic: Cosmetic::Skin(Skin::Flat),
+    },
+    ShopItem {
+        id: "bala",
+        price: 60,
+        cosmetic: Cosmetic::Skin(Skin::Candy),
+    },
+    ShopItem {
+        id: "vidro",
+        price: 120,
+        cosmetic: Cosmetic::Skin(Skin::Glass),
+    },
+    ShopItem {
+        id: "preto",
+        price: 0,
+        cosmetic: Cosmetic::Background(Color::BLACK),
+    },
+    ShopItem {
+        id: "noite",
+        price: 40,
+        cosmetic: Cosmetic::Background(Color::new(0.04, 0.04, 0.15, 1.0)),
+    },
+    ShopItem {
+        id: "algodão doce",
+        price: 80,
+        cosmetic: Cosmetic::Background(Color::new(0.18, 0.06, 0.14, 1.0)),
+    },
+    ShopItem {
+        id: "padrão",
+        price: 0,
+        cosmetic: Cosmetic::SfxPack(1.0),
+    },
+    ShopItem {
+        id: "grave",
+        price: 50,
+        cosmetic: Cosmetic::SfxPack(0.7),
+    },
+    ShopItem {
+        id: "esquilo",
+        price: 50,
+        cosmetic: Cosmetic::SfxPack(1.6),
+    },
+];
+
+fn find(category: Category, id: &str) -> &'static ShopItem {
+    ITEMS
+        .iter()
+        .find(|item| item.category() == category && item.id == id)
+        .or_else(|| ITEMS.iter().find(|item| item.category() == category))
+        .expect("every category has a default item")
+}
+
+pub fn skin(id: &str) -> Skin {
+    match find(Category::Skin, id).cosmetic {
+        Cosmetic::Skin(skin) => skin,
+        _ => Skin::Flat,
+    }
+}
+
+pub fn background(id: &str) -> Color {
+    match find(Category::Background, id).cosmetic {
+        Cosmetic::Background(color) => color,
+        _ => Color::BLACK,
+    }
+}
+
+pub fn sfx_pitch(id: &str) -> f32 {
+    match find(Category::SfxPack, id).cosmetic {
+        Cosmetic::SfxPack(pitch) => pitch,
+        _ => 1.0,
+    }
+}
+
+// Lollipops for a clear of 1-4 lines.
+pub fn clear_reward(lines: u32) -> u32 {
+    match lines {
+        0 => 0,
+        1 => 1,
+        2 => 3,
+        3 => 5,
+        _ => 8,
+    }
+}
+
+pub fn completion_reward(score: u32) -> u32 {
+    5 + score / 500
+}
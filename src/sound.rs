@@ -0,0 +1,359 @@
+use ggez::audio::{self, SoundSource};
+use ggez::{Context, GameResult};
+use std::io::Cursor;
+use std::time::Duration;
+
+// The game's own sounds, each loaded once and replayed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Death,
+    // Line clears and combos.
+    Clear,
+    // A four-line clear. The game has none of its own: without one, a tetris plays the clear
+    // sound four times over, like any other clear plays it once a line.
+    Tetris,
+    // Plays over the death freeze and the results.
+    Start,
+    // Loops for as long as a run is going.
+    Music,
+    // Stems that play in time with the music, louder the more intense the run gets: drums,
+    // then an arpeggio.
+    Drums,
+    Arpeggio,
+    // A step left or right.
+    Move,
+    Rotate,
+    // The piece touches down on the stack or the floor, before it locks.
+    Land,
+    Lock,
+    // Stands in for the landing and the lock when a hard drop does both at once.
+    HardDrop,
+}
+
+impl Sound {
+    pub const ALL: [Sound; SOUNDS] = [
+        Sound::Death,
+        Sound::Clear,
+        Sound::Tetris,
+        Sound::Start,
+        Sound::Music,
+        Sound::Drums,
+        Sound::Arpeggio,
+        Sound::Move,
+        Sound::Rotate,
+        Sound::Land,
+        Sound::Lock,
+        Sound::HardDrop,
+    ];
+
+    // Its file in the resource filesystem, unless a sound pack or the theme brings its own.
+    pub fn path(self) -> Option<&'static str> {
+        let path = match self {
+            Sound::Death => "/death.ogg",
+            Sound::Clear => "/atk.ogg",
+            Sound::Tetris => return None,
+            Sound::Start => "/random.mp3",
+            Sound::Music => "/music.wav",
+            Sound::Drums => "/music_drums.wav",
+            Sound::Arpeggio => "/music_arp.wav",
+            Sound::Move => "/move.wav",
+            Sound::Rotate => "/rotate.wav",
+            Sound::Land => "/land.wav",
+            Sound::Lock => "/lock.wav",
+            Sound::HardDrop => "/harddrop.wav",
+        };
+        Some(path)
+    }
+
+    // What a sound pack's file for it is called, before the extension.
+    pub fn name(self) -> &'static str {
+        match self {
+            Sound::Death => "death",
+            Sound::Clear => "clear",
+            Sound::Tetris => "tetris",
+            Sound::Start => "start",
+            Sound::Music => "music",
+            Sound::Drums => "music_drums",
+            Sound::Arpeggio => "music_arp",
+            Sound::Move => "move",
+            Sound::Rotate => "rotate",
+            Sound::Land => "land",
+            Sound::Lock => "lock",
+            Sound::HardDrop => "hard_drop",
+        }
+    }
+
+    pub fn is_layer(self) -> bool {
+        self.layer().is_some()
+    }
+
+    fn is_music(self) -> bool {
+        self == Sound::Start || self.is_track()
+    }
+
+    // The run's music and its stems, which loop and play together. They're declared next to
+    // each other, so they can be picked out of the sources by their index.
+    fn is_track(self) -> bool {
+        matches!(self, Sound::Music | Sound::Drums | Sound::Arpeggio)
+    }
+
+    fn layer(self) -> Option<usize> {
+        match self {
+            Sound::Drums => Some(0),
+            Sound::Arpeggio => Some(1),
+            _ => None,
+        }
+    }
+
+    // How loud it is next to the others, before the music or effects volume. Sounds made on
+    // nearly every input sit well under the rare ones, and the start track is mastered much
+    // quieter than everything else.
+    fn gain(self) -> f32 {
+        match self {
+            Sound::Death | Sound::Clear | Sound::Tetris => 1.0,
+            Sound::Start => 10.0,
+            Sound::Music | Sound::Drums | Sound::Arpeggio => 0.6,
+            Sound::Move => 0.3,
+            Sound::Rotate => 0.4,
+            Sound::Land => 0.5,
+            Sound::Lock => 0.6,
+            Sound::HardDrop => 0.9,
+        }
+    }
+}
+
+const SOUNDS: usize = 12;
+
+// What the run's music should be doing.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Music {
+    Stopped,
+    Paused,
+    // `speed` is a ratio: 1 is the track as it is, above that is faster and higher. `layers`
+    // is how loud each stem should be, from 0 to 1; they fade there over `LAYER_FADE`.
+    Playing { speed: f32, layers: [f32; 2] },
+}
+
+// How long a stem takes to fade all the way in or out.
+const LAYER_FADE: Duration = Duration::from_millis(1500);
+
+// Owns every sound the game keeps around and plays them at the music and effects volumes from
+// the settings. Changing a volume reaches sounds that are already playing, except for
+// detached effects, which are short.
+pub struct AudioManager {
+    sources: [Option<audio::Source>; SOUNDS],
+    // 0-1, already zero when muted.
+    music: f32,
+    sfx: f32,
+    // Sounds asked for by the simulation, which has no context to play them with. Each plays
+    // once however many times it was cued in between.
+    cued: [bool; SOUNDS],
+    // The speed the music is playing at, and where in the track it was started from. Sources
+    // can't change speed while they play, so a new speed starts the track over from where
+    // it had got to.
+    music_speed: f32,
+    music_start: Duration,
+    // How long the music is, when it could be worked out.
+    music_length: Option<Duration>,
+    // How loud each stem is right now, from 0 to 1.
+    layers: [f32; 2],
+}
+
+impl Default for AudioManager {
+    fn default() -> Self {
+        AudioManager {
+            sources: Default::default(),
+            music: 1.0,
+            sfx: 1.0,
+            cued: [false; SOUNDS],
+            music_speed: 1.0,
+            music_start: Duration::ZERO,
+            music_length: None,
+            layers: [0.0; 2],
+        }
+    }
+}
+
+impl AudioManager {
+    // Without `data`, or with data that doesn't decode, the sound stays silent.
+    pub fn set_source(&mut self, ctx: &Context, sound: Sound, data: Option<audio::SoundData>) -> GameResult {
+        self.sources[sound as usize] = None;
+        let Some(data) = data else {
+            self.apply();
+            return Ok(());
+        };
+        if sound == Sound::Music {
+            self.music_length = track_length(&data);
+        }
+        let mut source = audio::Source::from_data(ctx, data)?;
+        source.set_repeat(sound.is_track());
+        self.sources[sound as usize] = Some(source);
+        self.apply();
+        Ok(())
+    }
+
+    pub fn has(&self, sound: Sound) -> bool {
+        self.sources[sound as usize].is_some()
+    }
+
+    // `music` and `sfx` are in percent.
+    pub fn set_volumes(&mut self, music: u32, sfx: u32, muted: bool) {
+        let gain = if muted { 0.0 } else { 1.0 };
+        self.music = music.min(100) as f32 / 100.0 * gain;
+        self.sfx = sfx.min(100) as f32 / 100.0 * gain;
+        self.apply();
+    }
+
+    fn volume(&self, sound: Sound) -> f32 {
+        let channel = if sound.is_music() { self.music } else { self.sfx };
+        let layer = sound.layer().map_or(1.0, |layer| self.layers[layer]);
+        channel * sound.gain() * layer
+    }
+
+    fn apply(&mut self) {
+        let volumes = Sound::ALL.map(|sound| self.volume(sound));
+        for (source, volume) in self.sources.iter_mut().zip(volumes) {
+            if let Some(source) = source {
+                source.set_volume(volume);
+            }
+        }
+    }
+
+    // Effects play over each other; music starts over from the top.
+    pub fn play(&mut self, ctx: &mut Context, sound: Sound, pitch: f32) -> GameResult {
+        if let Some(source) = &mut self.sources[sound as usize] {
+            source.set_pitch(pitch);
+            if sound.is_music() {
+                source.play(ctx)?;
+            } else {
+                source.play_detached(ctx)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn cue(&mut self, sound: Sound) {
+        self.cued[sound as usize] = true;
+    }
+
+    // Plays and forgets everything cued since the last call.
+    pub fn play_cued(&mut self, ctx: &mut Context, pitch: f32) -> GameResult {
+        let mut cued = std::mem::take(&mut self.cued);
+        if cued[Sound::HardDrop as usize] {
+            cued[Sound::Land as usize] = false;
+            cued[Sound::Lock as usize] = false;
+        }
+        for sound in Sound::ALL {
+            if cued[sound as usize] {
+                self.play(ctx, sound, pitch)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Called every frame with what the music should be doing, and the time since the last
+    // frame for the stems' fades. The stems follow the music: they start, stop, pause and
+    // change speed with it, from the same point, so they stay in time.
+    pub fn update_music(&mut self, ctx: &mut Context, music: Music, dt: Duration) -> GameResult {
+        let Some(source) = &self.sources[Sound::Music as usize] else {
+            return Ok(());
+        };
+        let (stopped, paused, elapsed) = (source.stopped(), source.paused(), source.elapsed());
+        match music {
+            Music::Stopped => {
+                if !stopped {
+                    self.stop_tracks(ctx)?;
+                }
+            }
+            Music::Paused => self.tracks().for_each(|source| source.pause()),
+            Music::Playing { speed, layers } => {
+                self.fade_layers(layers, dt);
+                if paused {
+                    self.tracks().for_each(|source| source.resume());
+                }
+                if stopped {
+                    self.music_start = Duration::ZERO;
+                } else if speed != self.music_speed {
+                    // The track repeats forever, so this runs past its end. Sources get to
+                    // their start by decoding their way there, so it's wrapped back into the
+                    // track, or the music starts over if its length isn't known.
+                    let start = self.music_start + elapsed.mul_f32(self.music_speed);
+                    self.music_start = match self.music_length {
+                        Some(length) if !length.is_zero() => {
+                            Duration::from_secs_f64(start.as_secs_f64() % length.as_secs_f64())
+                        }
+                        _ => Duration::ZERO,
+                    };
+                } else {
+                    return Ok(());
+                }
+                self.music_speed = speed;
+                let start = self.music_start;
+                for source in self.tracks() {
+                    source.set_start(start);
+                    source.set_pitch(speed);
+                    source.play(ctx)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tracks(&mut self) -> impl Iterator<Item = &mut audio::Source> {
+        self.sources[Sound::Music as usize..=Sound::Arpeggio as usize].iter_mut().flatten()
+    }
+
+    fn stop_tracks(&mut self, ctx: &mut Context) -> GameResult {
+        for source in self.tracks() {
+            source.stop(ctx)?;
+        }
+        // The next run starts with just the music.
+        self.layers = [0.0; 2];
+        self.apply();
+        Ok(())
+    }
+
+    fn fade_layers(&mut self, targets: [f32; 2], dt: Duration) {
+        let step = dt.as_secs_f32() / LAYER_FADE.as_secs_f32();
+        let mut changed = false;
+        for (layer, target) in self.layers.iter_mut().zip(targets) {
+            let faded = if *layer < target { (*layer + step).min(target) } else { (*layer - step).max(target) };
+            changed |= faded != *layer;
+            *layer = faded;
+        }
+        if changed {
+            self.apply();
+        }
+    }
+
+    pub fn stop(&mut self, ctx: &mut Context, sound: Sound) -> GameResult {
+        if let Some(source) = &mut self.sources[sound as usize] {
+            source.stop(ctx)?;
+        }
+        Ok(())
+    }
+
+    // A one-off effect from somewhere else, like a scare, at `gain` times the effects volume.
+    pub fn play_clip(&self, ctx: &mut Context, mut source: audio::Source, gain: f32) -> GameResult {
+        if self.sfx <= 0.0 {
+            return Ok(());
+        }
+        source.set_volume(gain * self.sfx);
+        source.play_detached(ctx)
+    }
+}
+
+// How long a sound plays for, from its header when that says, otherwise by decoding it all.
+fn track_length(data: &audio::SoundData) -> Option<Duration> {
+    use rodio::Source;
+    let decoder = rodio::Decoder::new(Cursor::new(data.clone())).ok()?;
+    if let Some(length) = decoder.total_duration() {
+        return Some(length);
+    }
+    let rate = decoder.channels() as u64 * decoder.sample_rate() as u64;
+    if rate == 0 {
+        return None;
+    }
+    let samples = decoder.count() as u64;
+    Some(Duration::from_micros(samples * 1_000_000 / rate))
+}
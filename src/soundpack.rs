@@ -0,0 +1,57 @@
+use ggez::Context;
+use std::collections::HashMap;
+
+use crate::sound::Sound;
+
+// Formats a pack's sounds can be in.
+const EXTENSIONS: [&str; 4] = ["ogg", "wav", "mp3", "flac"];
+
+// A folder in resource/soundpacks/ replacing some of the game's sounds. Files are named after
+// what they replace (`move.ogg`, `clear.wav`, `tetris.ogg`, ...); anything else in the folder
+// is ignored, and anything missing comes from the theme or the game.
+pub struct SoundPack {
+    // The folder's name.
+    pub name: String,
+    paths: HashMap<&'static str, String>,
+}
+
+impl SoundPack {
+    // Every folder in resource/soundpacks/ with at least one sound the game knows, by name.
+    pub fn load_all(ctx: &Context) -> Vec<SoundPack> {
+        let Ok(entries) = ctx.fs.read_dir("/soundpacks") else {
+            return Vec::new();
+        };
+        let mut dirs: Vec<_> = entries.filter(|path| ctx.fs.is_dir(path)).collect();
+        dirs.sort();
+        let mut packs = Vec::new();
+        for dir in dirs {
+            let Some(name) = dir.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            let Ok(files) = ctx.fs.read_dir(&dir) else {
+                continue;
+            };
+            let mut files: Vec<_> = files.collect();
+            files.sort();
+            let mut paths = HashMap::new();
+            for file in files {
+                let known = file.extension().is_some_and(|ext| EXTENSIONS.iter().any(|known| ext == *known));
+                let stem = file.file_stem().and_then(|stem| stem.to_str());
+                let sound = Sound::ALL.into_iter().find(|sound| Some(sound.name()) == stem);
+                if let (true, Some(sound)) = (known, sound) {
+                    paths.entry(sound.name()).or_insert_with(|| file.to_string_lossy().replace('\\', "/"));
+                }
+            }
+            if paths.is_empty() {
+                eprintln!("sound pack {} has no sounds the game knows", dir.display());
+                continue;
+            }
+            packs.push(SoundPack { name, paths });
+        }
+        packs
+    }
+
+    pub fn path(&self, sound: Sound) -> Option<&str> {
+        self.paths.get(sound.name()).map(String::as_str)
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+// NES (NTSC) frames per row for levels 0-28; from 29 on it's a row every frame.
+const NES: [u64; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+// TGM: the internal level each gravity, in 256ths of a row per frame, takes over from.
+const TGM: [(u32, u64); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+// Guideline marathon: frames per 1000 rows for levels 1-20, from (0.8 - (level - 1) * 0.007)
+// ^ (level - 1) seconds per row. Past level 20 it's 20G.
+const GUIDELINE: [u64; 20] = [
+    60000, 47580, 37068, 28364, 21312, 15720, 11381, 8084, 5633, 3849, 2579, 1693, 1089, 686, 424, 256, 151, 87, 49,
+    27,
+];
+
+// Both TGM and the guideline lock a grounded piece after half a second.
+const LOCK_TICKS: u64 = 30;
+
+// Fixed goal: a new level every this many lines.
+const FIXED_GOAL: u32 = 10;
+// Variable goal: level n, counting from 1, takes this many times n line credits.
+const VARIABLE_GOAL: u32 = 5;
+
+// `rows` rows every `ticks` ticks, so slow speeds and several rows a tick both come out exact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gravity {
+    pub rows: u64,
+    pub ticks: u64,
+}
+
+impl Gravity {
+    // 20G: down to the stack within the tick.
+    const MAX: Gravity = Gravity { rows: 20, ticks: 1 };
+
+    pub fn scaled(self, rows: u64, ticks: u64) -> Gravity {
+        Gravity {
+            rows: self.rows * rows,
+            ticks: self.ticks * ticks,
+        }
+    }
+}
+
+pub struct Speed {
+    pub gravity: Gravity,
+    // None leaves the lock delay to the handling settings.
+    pub lock_ticks: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedCurve {
+    #[default]
+    Nes,
+    Tgm,
+    Guideline,
+}
+
+impl SpeedCurve {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpeedCurve::Nes => "NES",
+            SpeedCurve::Tgm => "TGM",
+            SpeedCurve::Guideline => "Guideline",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            SpeedCurve::Nes => SpeedCurve::Tgm,
+            SpeedCurve::Tgm => SpeedCurve::Guideline,
+            SpeedCurve::Guideline => SpeedCurve::Nes,
+        }
+    }
+
+    // `level` counts from 0. TGM has no levels in that sense; it goes by lines cleared, which
+    // stand in for its internal level.
+    pub fn speed(&self, level: u32, lines: u32) -> Speed {
+        match self {
+            SpeedCurve::Nes => Speed {
+                gravity: Gravity {
+                    rows: 1,
+                    ticks: NES.get(level as usize).copied().unwrap_or(1),
+                },
+                lock_ticks: None,
+            },
+            SpeedCurve::Tgm => {
+                let (_, rows) = TGM.iter().rev().find(|&&(from, _)| lines >= from).copied().unwrap_or(TGM[0]);
+                Speed {
+                    gravity: Gravity { rows, ticks: 256 },
+                    lock_ticks: Some(LOCK_TICKS),
+                }
+            }
+            SpeedCurve::Guideline => Speed {
+                gravity: GUIDELINE
+                    .get(level as usize)
+                    .map_or(Gravity::MAX, |&ticks| Gravity { rows: 1000, ticks }),
+                lock_ticks: Some(LOCK_TICKS),
+            },
+        }
+    }
+}
+
+// How many lines it takes to go up a level.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelGoal {
+    #[default]
+    Fixed,
+    Variable,
+}
+
+impl LevelGoal {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LevelGoal::Fixed => "fixa",
+            LevelGoal::Variable => "variável",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            LevelGoal::Fixed => LevelGoal::Variable,
+            LevelGoal::Variable => LevelGoal::Fixed,
+        }
+    }
+
+    // Line credits needed to leave `level`, which counts from 0.
+    pub fn goal(&self, level: u32) -> u32 {
+        match self {
+            LevelGoal::Fixed => FIXED_GOAL,
+            LevelGoal::Variable => VARIABLE_GOAL * (level + 1),
+        }
+    }
+
+    // What a clear counts towards the goal. The fixed goal counts lines; the variable goal
+    // gives guideline credits, 1/3/5/8 for one to four lines and 4 per line plus 4 for a
+    // spin, half again on back-to-back.
+    pub fn credit(&self, lines: u32, spin: bool, back_to_back: bool) -> u32 {
+        match self {
+            LevelGoal::Fixed => lines,
+            LevelGoal::Variable => {
+                let credit = if spin {
+                    4 * (lines + 1)
+                } else {
+                    [0, 1, 3, 5, 8][lines.min(4) as usize]
+                };
+                if back_to_back {
+                    credit + credit / 2
+                } else {
+                    credit
+                }
+            }
+        }
+    }
+}
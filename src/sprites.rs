@@ -0,0 +1,304 @@
+use ggez::graphics::{Canvas, Color, DrawParam, Image, ImageFormat, InstanceArray, Rect};
+use ggez::Context;
+use std::cell::RefCell;
+
+use crate::config::BlockStyle;
+use crate::shop::{self, Cosmetic, Skin};
+use crate::{CELL_SIZE, GARBAGE, GHOST_ALPHA};
+
+// Width of the edges of a beveled block, in pixels.
+const BEVEL: u32 = 4;
+// The sparkle and the patterns are rendered at 4x4 points per pixel, so their diagonals come
+// out smooth.
+const SAMPLES: usize = 4;
+// One pattern for each of the seven standard pieces, in `PieceKind::ALL` order.
+const PATTERNS: usize = 7;
+
+// Each block skin in the shop has an atlas in resource/skins/, named after it (liso.png,
+// bala.png, vidro.png): one row of square tiles, of any size, in this order:
+//
+//     normal | overlay | garbage | ghost | flash
+//
+// Normal and ghost tiles are white and take the cell's color as a tint. The overlay goes
+// untinted over every normal cell, for highlights that shouldn't pick up the color; it's left
+// transparent when the skin has none. Garbage is drawn as it is, and the flash fades out over
+// a piece that has just locked.
+#[derive(Clone, Copy)]
+pub enum Tile {
+    Normal,
+    Overlay,
+    Garbage,
+    Ghost,
+    Flash,
+}
+
+const TILES: u32 = 5;
+
+// One skin's atlas, with the cells queued for it this frame. Every tile comes from the same
+// image, so the whole board goes out in one draw call.
+pub struct BlockSkin {
+    batch: InstanceArray,
+    // Window pixels per atlas pixel.
+    scale: f32,
+}
+
+impl BlockSkin {
+    // Falls back to plain tiles if the atlas is missing or isn't a row of square tiles.
+    fn load(ctx: &Context, path: &str) -> Self {
+        let atlas = match Image::from_path(ctx, path) {
+            Ok(atlas) if atlas.width() == atlas.height() * TILES => atlas,
+            Ok(_) => {
+                eprintln!("skin atlas {} is not a row of {} square tiles", path, TILES);
+                plain_atlas(ctx)
+            }
+            Err(e) => {
+                eprintln!("failed to load skin atlas {}: {}", path, e);
+                plain_atlas(ctx)
+            }
+        };
+        BlockSkin::new(ctx, atlas)
+    }
+
+    fn new(ctx: &Context, atlas: Image) -> Self {
+        BlockSkin {
+            scale: CELL_SIZE / atlas.height() as f32,
+            batch: InstanceArray::new(ctx, atlas),
+        }
+    }
+
+    fn push(&mut self, tile: Tile, pos: [f32; 2], color: Color) {
+        let src = Rect::new(tile as u32 as f32 / TILES as f32, 0.0, 1.0 / TILES as f32, 1.0);
+        self.batch.push(DrawParam::default().src(src).dest(pos).scale([self.scale, self.scale]).color(color));
+    }
+}
+
+// Board cells queue up here during a frame and `flush` draws them: one call for the skin in
+// use and one for item sparkles, however many cells there are. Like `TextCache`, it's shared
+// behind `&self` while drawing.
+pub struct CellSprites {
+    // Textured blocks come from these; the other styles ignore the skin asked for.
+    style: BlockStyle,
+    skins: RefCell<Vec<(Skin, BlockSkin)>>,
+    // The active theme's own atlas, drawn instead of whichever skin is asked for.
+    theme: RefCell<Option<BlockSkin>>,
+    // The game's own tiles for the flat and beveled styles.
+    flat: RefCell<BlockSkin>,
+    beveled: RefCell<BlockSkin>,
+    // A four-pointed star marking a cell with an item, the same in every skin.
+    sparkles: RefCell<InstanceArray>,
+    // Shapes that tell the pieces apart without color, one row of tiles like a skin atlas.
+    patterns: RefCell<InstanceArray>,
+}
+
+impl CellSprites {
+    pub fn new(ctx: &Context) -> Self {
+        let skins = shop::ITEMS
+            .iter()
+            .filter_map(|item| match item.cosmetic {
+                Cosmetic::Skin(skin) => Some((skin, BlockSkin::load(ctx, &format!("/skins/{}.png", item.id)))),
+                _ => None,
+            })
+            .collect();
+        CellSprites {
+            style: BlockStyle::default(),
+            skins: RefCell::new(skins),
+            theme: RefCell::new(None),
+            flat: RefCell::new(BlockSkin::new(ctx, plain_atlas(ctx))),
+            beveled: RefCell::new(BlockSkin::new(ctx, beveled_atlas(ctx))),
+            sparkles: RefCell::new(InstanceArray::new(ctx, sparkle_image(ctx))),
+            patterns: RefCell::new(InstanceArray::new(ctx, pattern_atlas(ctx))),
+        }
+    }
+
+    // Queues a tile of `skin` with its top-left corner at `pos`, in window pixels, tinted by
+    // `color`.
+    pub fn push(&self, skin: Skin, tile: Tile, pos: [f32; 2], color: Color) {
+        match self.style {
+            BlockStyle::Textured => {}
+            BlockStyle::Flat => return self.flat.borrow_mut().push(tile, pos, color),
+            BlockStyle::Beveled => return self.beveled.borrow_mut().push(tile, pos, color),
+        }
+        if let Some(theme) = self.theme.borrow_mut().as_mut() {
+            theme.push(tile, pos, color);
+            return;
+        }
+        let mut skins = self.skins.borrow_mut();
+        if let Some((_, block_skin)) = skins.iter_mut().find(|(s, _)| *s == skin) {
+            block_skin.push(tile, pos, color);
+        }
+    }
+
+    // A settled or falling cell: the normal tile in its color, with the overlay on top.
+    pub fn push_cell(&self, skin: Skin, pos: [f32; 2], color: Color) {
+        self.push(skin, Tile::Normal, pos, color);
+        self.push(skin, Tile::Overlay, pos, Color::WHITE);
+    }
+
+    pub fn set_style(&mut self, style: BlockStyle) {
+        self.style = style;
+    }
+
+    // The atlas at `path` overrides the shop skins until it's set back to None.
+    pub fn set_theme_skin(&mut self, ctx: &Context, path: Option<&str>) {
+        *self.theme.get_mut() = path.map(|path| BlockSkin::load(ctx, path));
+    }
+
+    pub fn push_sparkle(&self, pos: [f32; 2]) {
+        self.sparkles.borrow_mut().push(DrawParam::default().dest(pos));
+    }
+
+    // The pattern for the piece at `index` in `PieceKind::ALL`, darkening the cell under it.
+    pub fn push_pattern(&self, index: usize, pos: [f32; 2]) {
+        let src = Rect::new(index as f32 / PATTERNS as f32, 0.0, 1.0 / PATTERNS as f32, 1.0);
+        let color = Color::new(0.0, 0.0, 0.0, 0.45);
+        self.patterns.borrow_mut().push(DrawParam::default().src(src).dest(pos).color(color));
+    }
+
+    // Draws everything queued since the last flush, in the order it was queued, and starts
+    // over.
+    pub fn flush(&self, canvas: &mut Canvas) {
+        for (_, skin) in self.skins.borrow_mut().iter_mut() {
+            canvas.draw(&skin.batch, DrawParam::default());
+            skin.batch.clear();
+        }
+        if let Some(theme) = self.theme.borrow_mut().as_mut() {
+            canvas.draw(&theme.batch, DrawParam::default());
+            theme.batch.clear();
+        }
+        for skin in [&self.flat, &self.beveled] {
+            let mut skin = skin.borrow_mut();
+            canvas.draw(&skin.batch, DrawParam::default());
+            skin.batch.clear();
+        }
+        let mut patterns = self.patterns.borrow_mut();
+        canvas.draw(&*patterns, DrawParam::default());
+        patterns.clear();
+        let mut sparkles = self.sparkles.borrow_mut();
+        canvas.draw(&*sparkles, DrawParam::default());
+        sparkles.clear();
+    }
+}
+
+// Flat tiles in the atlas layout, for the flat style and for a skin whose atlas can't be
+// loaded.
+fn plain_atlas(ctx: &Context) -> Image {
+    let tiles = [Color::WHITE, Color::new(0.0, 0.0, 0.0, 0.0), GARBAGE, Color::new(1.0, 1.0, 1.0, GHOST_ALPHA), Color::WHITE];
+    build_atlas(ctx, |tile, _, _| tiles[tile])
+}
+
+// Flat tiles with the bevel in the overlay, so it shades every color the same way. Garbage
+// has it baked in.
+fn beveled_atlas(ctx: &Context) -> Image {
+    build_atlas(ctx, |tile, px, py| match (tile, bevel(px, py)) {
+        (1, Some(true)) => Color::new(1.0, 1.0, 1.0, 0.5),
+        (1, Some(false)) => Color::new(0.0, 0.0, 0.0, 0.4),
+        (1, None) => Color::new(0.0, 0.0, 0.0, 0.0),
+        (2, Some(true)) => Color::new(GARBAGE.r + 0.2, GARBAGE.g + 0.2, GARBAGE.b + 0.2, 1.0),
+        (2, Some(false)) => Color::new(GARBAGE.r * 0.6, GARBAGE.g * 0.6, GARBAGE.b * 0.6, 1.0),
+        (2, None) => GARBAGE,
+        (3, _) => Color::new(1.0, 1.0, 1.0, GHOST_ALPHA),
+        _ => Color::WHITE,
+    })
+}
+
+// Whether a pixel of a cell is on its bevel, and if so, whether on the light side. The
+// corners are split along the diagonal, where the edges meet.
+fn bevel(px: u32, py: u32) -> Option<bool> {
+    let last = CELL_SIZE as u32 - 1;
+    let (light, dark) = (px.min(py), (last - px).min(last - py));
+    (light.min(dark) < BEVEL).then_some(light < dark)
+}
+
+// One row of cell-sized tiles in `Tile` order, with `pixel` giving the color of each pixel of
+// each tile.
+fn build_atlas(ctx: &Context, pixel: impl Fn(usize, u32, u32) -> Color) -> Image {
+    let size = CELL_SIZE as u32;
+    let mut pixels = Vec::with_capacity((size * size * TILES * 4) as usize);
+    for py in 0..size {
+        for tile in 0..TILES as usize {
+            for px in 0..size {
+                let (r, g, b, a) = pixel(tile, px, py).to_rgba();
+                pixels.extend_from_slice(&[r, g, b, a]);
+            }
+        }
+    }
+    Image::from_pixels(ctx, &pixels, ImageFormat::Rgba8UnormSrgb, size * TILES, size)
+}
+
+fn sparkle_image(ctx: &Context) -> Image {
+    let size = CELL_SIZE as usize;
+    let mut pixels = Vec::with_capacity(size * size * 4);
+    for py in 0..size {
+        for px in 0..size {
+            pixels.extend_from_slice(&[255, 255, 255, coverage(px, py, in_star)]);
+        }
+    }
+    Image::from_pixels(ctx, &pixels, ImageFormat::Rgba8UnormSrgb, size as u32, size as u32)
+}
+
+// White patterns to be tinted: bars for I, a ring for O, a dot for T, stripes one way for L
+// and the other for J, columns for S and a cross for Z.
+fn pattern_atlas(ctx: &Context) -> Image {
+    let size = CELL_SIZE as usize;
+    let mut pixels = Vec::with_capacity(size * size * PATTERNS * 4);
+    for py in 0..size {
+        for index in 0..PATTERNS {
+            for px in 0..size {
+                pixels.extend_from_slice(&[255, 255, 255, coverage(px, py, |x, y| in_pattern(index, x, y))]);
+            }
+        }
+    }
+    Image::from_pixels(ctx, &pixels, ImageFormat::Rgba8UnormSrgb, (size * PATTERNS) as u32, size as u32)
+}
+
+// How much of pixel (`px`, `py`) is inside a shape, as an alpha value.
+fn coverage(px: usize, py: usize, inside: impl Fn(f32, f32) -> bool) -> u8 {
+    let mut covered = 0;
+    for sy in 0..SAMPLES {
+        for sx in 0..SAMPLES {
+            let x = px as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+            let y = py as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+            covered += inside(x, y) as usize;
+        }
+    }
+    (covered * 255 / (SAMPLES * SAMPLES)) as u8
+}
+
+fn in_pattern(index: usize, x: f32, y: f32) -> bool {
+    let mid = CELL_SIZE / 2.0;
+    // Patterns stay clear of the cell's edge, where skins draw their bevels.
+    let inner = (5.0..CELL_SIZE - 5.0).contains(&x) && (5.0..CELL_SIZE - 5.0).contains(&y);
+    let stripe = |t: f32| t.rem_euclid(8.0) < 3.0;
+    match index {
+        0 => inner && stripe(y - 1.0),
+        1 => inner && !((9.0..CELL_SIZE - 9.0).contains(&x) && (9.0..CELL_SIZE - 9.0).contains(&y)),
+        2 => (x - mid).powi(2) + (y - mid).powi(2) < 36.0,
+        3 => inner && stripe(x + y),
+        4 => inner && stripe(x - y),
+        5 => inner && stripe(x - 1.0),
+        _ => inner && ((x - y).abs() < 2.0 || (x + y - CELL_SIZE).abs() < 2.0),
+    }
+}
+
+fn in_star(x: f32, y: f32) -> bool {
+    let mid = CELL_SIZE / 2.0;
+    let star = [
+        (mid, 4.0),
+        (mid + 3.0, mid - 3.0),
+        (CELL_SIZE - 4.0, mid),
+        (mid + 3.0, mid + 3.0),
+        (mid, CELL_SIZE - 4.0),
+        (mid - 3.0, mid + 3.0),
+        (4.0, mid),
+        (mid - 3.0, mid - 3.0),
+    ];
+    // Even-odd rule: count the edges a ray to the right crosses.
+    let mut inside = false;
+    for (i, &(x1, y1)) in star.iter().enumerate() {
+        let (x2, y2) = star[(i + 1) % star.len()];
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
@@ -0,0 +1,314 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::piece::PieceKind;
+use crate::storage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    Classic,
+    Sprint,
+    Sandbox,
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "classic",
+            GameMode::Sprint => "sprint",
+            GameMode::Sandbox => "sandbox",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOutcome {
+    #[default]
+    ToppedOut,
+    Abandoned,
+    Completed,
+}
+
+impl GameOutcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameOutcome::ToppedOut => "topou",
+            GameOutcome::Abandoned => "abandonou",
+            GameOutcome::Completed => "completou",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PieceStats {
+    pub dealt: u32,
+    pub placed: u32,
+    pub place_time_ms: u64,
+    pub misdrops: u32,
+}
+
+impl PieceStats {
+    pub fn average_place_secs(&self) -> f32 {
+        if self.placed == 0 {
+            0.0
+        } else {
+            self.place_time_ms as f32 / self.placed as f32 / 1000.0
+        }
+    }
+
+    pub fn misdrop_rate(&self) -> f32 {
+        if self.placed == 0 {
+            0.0
+        } else {
+            self.misdrops as f32 / self.placed as f32
+        }
+    }
+
+    fn add(&mut self, other: &PieceStats) {
+        self.dealt += other.dealt;
+        self.placed += other.placed;
+        self.place_time_ms += other.place_time_ms;
+        self.misdrops += other.misdrops;
+    }
+}
+
+#[derive(Default)]
+pub struct RunStats {
+    pub play_time: Duration,
+    pub pieces: u32,
+    pub keys: u32,
+    pub clears: [u32; 4],
+    pub by_piece: [PieceStats; 7],
+    // Pieces dealt since the last I, and the longest such stretch this run.
+    pub drought: u32,
+    pub max_drought: u32,
+    // Item mode was on.
+    pub items: bool,
+}
+
+impl RunStats {
+    pub fn lines(&self) -> u32 {
+        self.clears.iter().enumerate().map(|(i, &count)| count * (i as u32 + 1)).sum()
+    }
+
+    // Per-piece numbers are only kept for the standard seven.
+    pub fn record_spawn(&mut self, kind: PieceKind) {
+        let Some(index) = kind.index() else {
+            return;
+        };
+        self.by_piece[index].dealt += 1;
+        if kind == PieceKind::I {
+            self.drought = 0;
+        } else {
+            self.drought += 1;
+            self.max_drought = self.max_drought.max(self.drought);
+        }
+    }
+
+    pub fn record_placement(&mut self, kind: PieceKind, place_time: Duration) {
+        self.pieces += 1;
+        if let Some(index) = kind.index() {
+            let piece = &mut self.by_piece[index];
+            piece.placed += 1;
+            piece.place_time_ms += place_time.as_millis() as u64;
+        }
+    }
+
+    pub fn record_misdrop(&mut self, kind: PieceKind) {
+        if let Some(index) = kind.index() {
+            self.by_piece[index].misdrops += 1;
+        }
+    }
+
+    pub fn misdrops(&self) -> u32 {
+        self.by_piece.iter().map(|piece| piece.misdrops).sum()
+    }
+
+    pub fn record_clear(&mut self, lines: usize) {
+        if lines > 0 {
+            self.clears[lines.min(4) - 1] += 1;
+        }
+    }
+
+    pub fn pps(&self) -> f32 {
+        pps(self.pieces, self.play_time.as_millis() as u64)
+    }
+
+    pub fn kpp(&self) -> f32 {
+        kpp(self.keys, self.pieces)
+    }
+
+    pub fn kps(&self) -> f32 {
+        pps(self.keys, self.play_time.as_millis() as u64)
+    }
+
+    pub fn to_record(&self, mode: GameMode, score: u32, outcome: GameOutcome) -> GameRecord {
+        GameRecord {
+            timestamp: unix_now(),
+            mode,
+            outcome,
+            score,
+            duration_ms: self.play_time.as_millis() as u64,
+            pieces: self.pieces,
+            keys: self.keys,
+            singles: self.clears[0],
+            doubles: self.clears[1],
+            triples: self.clears[2],
+            tetrises: self.clears[3],
+            by_piece: self.by_piece,
+            max_drought: self.max_drought,
+            items: self.items,
+            replay: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub timestamp: u64,
+    pub mode: GameMode,
+    #[serde(default)]
+    pub outcome: GameOutcome,
+    pub score: u32,
+    pub duration_ms: u64,
+    pub pieces: u32,
+    #[serde(default)]
+    pub keys: u32,
+    pub singles: u32,
+    pub doubles: u32,
+    pub triples: u32,
+    pub tetrises: u32,
+    #[serde(default)]
+    pub by_piece: [PieceStats; 7],
+    #[serde(default)]
+    pub max_drought: u32,
+    #[serde(default)]
+    pub items: bool,
+    #[serde(default)]
+    pub replay: Option<String>,
+}
+
+impl GameRecord {
+    pub fn pps(&self) -> f32 {
+        pps(self.pieces, self.duration_ms)
+    }
+
+    pub fn kpp(&self) -> f32 {
+        kpp(self.keys, self.pieces)
+    }
+
+    pub fn misdrops(&self) -> u32 {
+        self.by_piece.iter().map(|piece| piece.misdrops).sum()
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.singles + self.doubles * 2 + self.triples * 3 + self.tetrises * 4
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct StatsHistory {
+    pub games: Vec<GameRecord>,
+}
+
+impl StatsHistory {
+    pub fn load(path: &Path) -> Self {
+        storage::load_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        storage::save_toml(self, path)
+    }
+
+    pub fn max_drought(&self) -> u32 {
+        self.games.iter().map(|game| game.max_drought).max().unwrap_or(0)
+    }
+
+    pub fn piece_totals(&self) -> [PieceStats; 7] {
+        let mut totals = [PieceStats::default(); 7];
+        for game in &self.games {
+            for (total, piece) in totals.iter_mut().zip(&game.by_piece) {
+                total.add(piece);
+            }
+        }
+        totals
+    }
+
+    pub fn export_csv(&self, path: &Path) -> io::Result<()> {
+        let mut csv =
+            String::from("date,mode,score,time_s,pieces,pps,keys,kpp,misdrops,lines,singles,doubles,triples,tetrises\n");
+        for game in &self.games {
+            csv.push_str(&format!(
+                "{},{},{},{:.2},{},{:.2},{},{:.2},{},{},{},{},{},{}\n",
+                format_timestamp(game.timestamp),
+                game.mode.name(),
+                game.score,
+                game.duration_ms as f32 / 1000.0,
+                game.pieces,
+                game.pps(),
+                game.keys,
+                game.kpp(),
+                game.misdrops(),
+                game.lines(),
+                game.singles,
+                game.doubles,
+                game.triples,
+                game.tetrises,
+            ));
+        }
+        fs::write(path, csv)
+    }
+}
+
+fn kpp(keys: u32, pieces: u32) -> f32 {
+    if pieces == 0 {
+        0.0
+    } else {
+        keys as f32 / pieces as f32
+    }
+}
+
+// Also used for keys per second; anything counted over the run's play time.
+fn pps(pieces: u32, duration_ms: u64) -> f32 {
+    if duration_ms == 0 {
+        0.0
+    } else {
+        pieces as f32 / (duration_ms as f32 / 1000.0)
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// UTC "YYYY-MM-DD HH:MM:SS", using the days-to-civil conversion so we don't need a date crate.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
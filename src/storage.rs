@@ -0,0 +1,26 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub fn read_toml<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let data = fs::read_to_string(path)?;
+    toml::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn load_toml<T: DeserializeOwned + Default>(path: &Path) -> T {
+    read_toml(path).unwrap_or_default()
+}
+
+pub fn save_toml<T: Serialize>(value: &T, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Going through `toml::Value` sorts plain values ahead of tables, which toml
+    // otherwise refuses to serialize when a struct declares a table field first.
+    let data = toml::Value::try_from(value)
+        .and_then(|value| toml::to_string(&value))
+        .map_err(io::Error::other)?;
+    fs::write(path, data)
+}
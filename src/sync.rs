@@ -0,0 +1,165 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::config::Settings;
+use crate::http;
+use crate::stats::GameRecord;
+use crate::storage;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    pub server: String,
+    pub profile: String,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            enabled: false,
+            server: "http://127.0.0.1:8787".to_string(),
+            profile: String::new(),
+        }
+    }
+}
+
+impl SyncConfig {
+    // Written out on first run so players have a file to edit; the profile id is what
+    // ties machines together, so it's generated once and then left alone.
+    pub fn load_or_create(path: &Path) -> Self {
+        let mut config: SyncConfig = storage::load_toml(path);
+        if config.profile.is_empty() {
+            let mut rng = rand::thread_rng();
+            config.profile = (0..16).map(|_| format!("{:x}", rng.gen_range(0..16))).collect();
+            if let Err(e) = storage::save_toml(&config, path) {
+                eprintln!("failed to save sync config: {}", e);
+            }
+        }
+        config
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncPayload {
+    pub settings: Settings,
+    pub history: Vec<GameRecord>,
+}
+
+pub fn merge(local: &SyncPayload, remote: &SyncPayload) -> SyncPayload {
+    let settings = if remote.settings.updated_at > local.settings.updated_at {
+        remote.settings.clone()
+    } else {
+        local.settings.clone()
+    };
+    SyncPayload {
+        settings,
+        history: merge_history(&local.history, &remote.history),
+    }
+}
+
+// Game records never change once written, so merging is a union ordered by when they were played.
+// A game is known by when it was played and how it went, not by its replay link, which names a
+// file on one machine only: the local copy wins and keeps its link, and games from elsewhere
+// come in without one.
+pub fn merge_history(local: &[GameRecord], remote: &[GameRecord]) -> Vec<GameRecord> {
+    let remote = remote.iter().map(|g| GameRecord { replay: None, ..g.clone() });
+    let mut games: Vec<GameRecord> = local.iter().cloned().chain(remote).collect();
+    // The sort is stable, so a game on both sides keeps its local copy first.
+    games.sort_by_key(|g| (g.timestamp, g.score, g.duration_ms, g.pieces));
+    games.dedup_by_key(|g| (g.timestamp, g.score, g.duration_ms, g.pieces));
+    games
+}
+
+pub enum SyncResult {
+    Done(Box<SyncPayload>),
+    Failed(String),
+}
+
+pub struct SyncClient {
+    pub config: SyncConfig,
+    pending: Option<Receiver<SyncResult>>,
+}
+
+impl SyncClient {
+    pub fn new(config: SyncConfig) -> Self {
+        SyncClient { config, pending: None }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn start(&mut self, local: SyncPayload) {
+        if !self.config.enabled || self.is_busy() {
+            return;
+        }
+        let url = format!(
+            "{}/profiles/{}",
+            self.config.server.trim_end_matches('/'),
+            self.config.profile
+        );
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let result = match run(&url, &local) {
+                Ok(merged) => SyncResult::Done(Box::new(merged)),
+                Err(e) => SyncResult::Failed(e),
+            };
+            let _ = sender.send(result);
+        });
+        self.pending = Some(receiver);
+    }
+
+    pub fn poll(&mut self) -> Option<SyncResult> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => SyncResult::Failed("sync thread exited".to_string()),
+        };
+        self.pending = None;
+        Some(result)
+    }
+}
+
+fn run(url: &str, local: &SyncPayload) -> Result<SyncPayload, String> {
+    let response = http::request("GET", url, None).map_err(|e| e.to_string())?;
+    let remote = match response.status {
+        200 => serde_json::from_str(&response.body).map_err(|e| e.to_string())?,
+        404 => SyncPayload::default(),
+        status => return Err(format!("GET {} returned {}", url, status)),
+    };
+
+    let merged = merge(local, &remote);
+    let body = serde_json::to_string(&merged).map_err(|e| e.to_string())?;
+    let response = http::request("PUT", url, Some(&body)).map_err(|e| e.to_string())?;
+    if !(200..300).contains(&response.status) {
+        return Err(format!("PUT {} returned {}", url, response.status));
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{GameMode, GameOutcome, RunStats};
+
+    fn game(timestamp: u64, score: u32, replay: Option<&str>) -> GameRecord {
+        let mut record = RunStats::default().to_record(GameMode::Classic, score, GameOutcome::ToppedOut);
+        record.timestamp = timestamp;
+        record.replay = replay.map(str::to_string);
+        record
+    }
+
+    #[test]
+    fn games_on_both_sides_keep_the_local_replay() {
+        let local = [game(1, 100, Some("1-0.toml")), game(3, 300, None)];
+        let remote = [game(1, 100, None), game(2, 200, Some("2-5.toml")), game(3, 300, Some("3-1.toml"))];
+        let merged = merge_history(&local, &remote);
+        let games: Vec<(u64, Option<&str>)> = merged.iter().map(|g| (g.timestamp, g.replay.as_deref())).collect();
+        assert_eq!(games, [(1, Some("1-0.toml")), (2, None), (3, None)]);
+    }
+}
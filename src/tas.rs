@@ -0,0 +1,109 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::Action;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TasInput {
+    Press(Action),
+    // Lets go of a held left/right, ending its auto-shift.
+    Release,
+}
+
+impl TasInput {
+    fn from_name(name: &str) -> Option<TasInput> {
+        let input = match name {
+            "left" => TasInput::Press(Action::MoveLeft),
+            "right" => TasInput::Press(Action::MoveRight),
+            "soft" => TasInput::Press(Action::SoftDrop),
+            "hard" => TasInput::Press(Action::HardDrop),
+            "sonic" => TasInput::Press(Action::SonicDrop),
+            "rotate" => TasInput::Press(Action::RotateCw),
+            "rotateccw" => TasInput::Press(Action::RotateCcw),
+            "rotate180" => TasInput::Press(Action::Rotate180),
+            "hold" => TasInput::Press(Action::Hold),
+            "hold2" => TasInput::Press(Action::Hold2),
+            "release" => TasInput::Release,
+            _ => return None,
+        };
+        Some(input)
+    }
+}
+
+// A script is one `<frame> <input>` per line, frames being simulation ticks (60 per
+// second), plus an optional `seed <n>` line; `#` starts a comment. Frames must not go backwards.
+pub struct TasScript {
+    pub seed: u64,
+    pub inputs: Vec<(u64, TasInput)>,
+}
+
+impl TasScript {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut script = TasScript {
+            seed: 0,
+            inputs: Vec::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number + 1, message))
+            };
+            let mut words = line.split_whitespace();
+            let (Some(first), Some(second), None) = (words.next(), words.next(), words.next()) else {
+                return Err(invalid("expected `<frame> <input>`"));
+            };
+            if first == "seed" {
+                script.seed = second.parse().map_err(|_| invalid("bad seed"))?;
+                continue;
+            }
+            let frame: u64 = first.parse().map_err(|_| invalid("bad frame number"))?;
+            if script.inputs.last().is_some_and(|&(last, _)| frame < last) {
+                return Err(invalid("frames must be in order"));
+            }
+            let input = TasInput::from_name(second).ok_or_else(|| invalid("unknown input"))?;
+            script.inputs.push((frame, input));
+        }
+        Ok(script)
+    }
+}
+
+pub struct TasPlayer {
+    script: TasScript,
+    pub speed: f32,
+    next: usize,
+}
+
+impl TasPlayer {
+    pub fn new(script: TasScript, speed: f32) -> Self {
+        TasPlayer { script, speed, next: 0 }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.script.seed
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.script.inputs.len()
+    }
+
+    // The inputs due by `tick`, the run's tick counter; each script frame is one tick.
+    pub fn inputs_until(&mut self, tick: u64) -> Vec<TasInput> {
+        let mut inputs = Vec::new();
+        while let Some(&(frame, input)) = self.script.inputs.get(self.next) {
+            if frame > tick {
+                break;
+            }
+            inputs.push(input);
+            self.next += 1;
+        }
+        inputs
+    }
+}
@@ -0,0 +1,137 @@
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, Color, DrawParam, Rect, Text, TextAlign, TextLayout};
+use ggez::{Context, GameResult};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::{ui, SCREEN_WIDTH};
+
+// Space between boxed text and the edge of its box.
+const BOX_PADDING: f32 = 6.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Align {
+    Left,
+    // Centered on both axes around the given point, wrapping at the window width.
+    Center,
+    // Ends at the given point.
+    Right,
+}
+
+#[derive(Clone, Copy)]
+pub struct Style {
+    pub scale: f32,
+    pub color: Color,
+    pub align: Align,
+}
+
+impl Style {
+    pub const fn new(scale: f32, color: Color, align: Align) -> Self {
+        Style { scale, color, align }
+    }
+}
+
+pub const HUD: Style = Style::new(16.0, Color::new(1.0, 1.0, 1.0, 0.7), Align::Left);
+pub const HUD_RIGHT: Style = Style::new(16.0, Color::new(1.0, 1.0, 1.0, 0.7), Align::Right);
+
+type Key = (String, u32, Align);
+
+// Laid-out `Text`s are kept between frames and reused while their content stays the same.
+// Entries not drawn during a frame are dropped by `end_frame`, so changing text (timers,
+// counters) doesn't pile up.
+pub struct TextCache {
+    texts: RefCell<HashMap<Key, (Text, bool)>>,
+    // A font added to the graphics context by name, or None for ggez's own.
+    font: Option<String>,
+    // Window pixels per logical pixel. Glyphs are rasterized at the size they end up on
+    // screen and drawn scaled back down, so text stays sharp however far the view is scaled up.
+    density: f32,
+}
+
+impl Default for TextCache {
+    fn default() -> Self {
+        TextCache {
+            texts: RefCell::default(),
+            font: None,
+            density: 1.0,
+        }
+    }
+}
+
+impl TextCache {
+    pub fn draw(&self, canvas: &mut Canvas, content: &str, pos: [f32; 2], style: Style) {
+        let param = DrawParam::default().dest(pos).scale([1.0 / self.density; 2]).color(style.color);
+        self.with(content, style, |text| canvas.draw(text, param));
+    }
+
+    pub fn draw_centered(&self, canvas: &mut Canvas, content: &str, y: f32, scale: f32, color: Color) {
+        self.draw(canvas, content, [SCREEN_WIDTH / 2.0, y], Style::new(scale, color, Align::Center));
+    }
+
+    // Size of the laid-out text, for backdrops and for placing things next to it.
+    pub fn measure(&self, ctx: &Context, content: &str, style: Style) -> GameResult<Vec2> {
+        self.with(content, style, |text| text.measure(ctx).map(|size| Vec2::from(size) / self.density))
+    }
+
+    // Layouts are in window pixels, so they're all redone when the density changes.
+    pub fn set_density(&mut self, density: f32) {
+        if density != self.density {
+            self.density = density;
+            self.texts.get_mut().clear();
+        }
+    }
+
+    // Everything laid out so far used the old font, so it all goes.
+    pub fn set_font(&mut self, font: Option<String>) {
+        self.font = font;
+        self.texts.get_mut().clear();
+    }
+
+    // Text on a box of `fill` sized to fit it, placed by `pos` the same way as `draw`, so it
+    // stays readable over the board.
+    pub fn draw_boxed(&self, ctx: &Context, canvas: &mut Canvas, content: &str, pos: [f32; 2], style: Style, fill: Color) -> GameResult {
+        let size = self.measure(ctx, content, style)?;
+        let (x, y) = match style.align {
+            Align::Left => (pos[0], pos[1]),
+            Align::Center => (pos[0] - size.x / 2.0, pos[1] - size.y / 2.0),
+            Align::Right => (pos[0] - size.x, pos[1]),
+        };
+        let area = Rect::new(x - BOX_PADDING, y - BOX_PADDING, size.x + 2.0 * BOX_PADDING, size.y + 2.0 * BOX_PADDING);
+        ui::fill_rect(canvas, area, fill);
+        self.draw(canvas, content, pos, style);
+        Ok(())
+    }
+
+    pub fn end_frame(&self) {
+        self.texts.borrow_mut().retain(|_, (_, used)| std::mem::take(used));
+    }
+
+    fn with<R>(&self, content: &str, style: Style, f: impl FnOnce(&Text) -> R) -> R {
+        let mut texts = self.texts.borrow_mut();
+        let key = (content.to_string(), style.scale.to_bits(), style.align);
+        let (text, used) = texts.entry(key).or_insert_with(|| (layout(content, style, self.font.as_deref(), self.density), false));
+        *used = true;
+        f(text)
+    }
+}
+
+fn layout(content: &str, style: Style, font: Option<&str>, density: f32) -> Text {
+    let mut text = Text::new(content);
+    text.set_scale(style.scale * density);
+    if let Some(font) = font {
+        text.set_font(font);
+    }
+    match style.align {
+        Align::Left => {}
+        Align::Center => {
+            text.set_bounds([(SCREEN_WIDTH - 20.0) * density, f32::INFINITY]).set_layout(TextLayout::center());
+        }
+        Align::Right => {
+            text.set_layout(TextLayout {
+                h_align: TextAlign::End,
+                v_align: TextAlign::Begin,
+            });
+        }
+    }
+    text
+}
@@ -0,0 +1,100 @@
+use ggez::graphics::Color;
+use ggez::Context;
+use serde::Deserialize;
+use std::io::Read;
+
+use crate::config::THEMES;
+
+// Themes from TOML files in resource/themes/, listed after the built-in ones:
+//
+//     name = "oceano"
+//     colors = [[0.1, 0.5, 0.9], [0.0, 0.8, 0.7]]
+//     background = [0.02, 0.05, 0.1]
+//     skin = "/themes/oceano/blocos.png"
+//     backdrop = "/themes/oceano/fundo.png"
+//     font = "/themes/oceano/fonte.ttf"
+//
+//     [sounds]
+//     death = "/themes/oceano/morte.ogg"
+//     clear = "/themes/oceano/linha.ogg"
+//     start = "/themes/oceano/inicio.mp3"
+//     music = "/themes/oceano/musica.ogg"
+//     layers = ["/themes/oceano/bateria.ogg", "/themes/oceano/arpejo.ogg"]
+//
+// Only `name` and `colors` are required; anything left out falls back to what was bought in
+// the shop, or to the game's own files. `skin` is an atlas laid out like the ones in
+// resource/skins/. Paths are in the resource filesystem, the same as in scares.toml.
+#[derive(Deserialize)]
+pub struct ThemeFile {
+    pub name: String,
+    colors: [[f32; 3]; 2],
+    #[serde(default)]
+    background: Option<[f32; 3]>,
+    #[serde(default)]
+    pub skin: Option<String>,
+    #[serde(default)]
+    pub backdrop: Option<String>,
+    #[serde(default)]
+    pub font: Option<String>,
+    #[serde(default)]
+    pub sounds: ThemeSounds,
+}
+
+#[derive(Default, Deserialize)]
+pub struct ThemeSounds {
+    pub death: Option<String>,
+    // Played for line clears and combos.
+    pub clear: Option<String>,
+    pub tetris: Option<String>,
+    pub start: Option<String>,
+    // Loops during runs, with up to two stems of the same length over it.
+    pub music: Option<String>,
+    #[serde(default)]
+    pub layers: Vec<String>,
+    #[serde(rename = "move")]
+    pub shift: Option<String>,
+    pub rotate: Option<String>,
+    pub land: Option<String>,
+    pub lock: Option<String>,
+    pub hard_drop: Option<String>,
+}
+
+impl ThemeFile {
+    // Every valid theme in resource/themes/, by file name. Broken files, and files reusing the
+    // name of a built-in theme, are reported and skipped.
+    pub fn load_all(ctx: &Context) -> Vec<ThemeFile> {
+        let Ok(entries) = ctx.fs.read_dir("/themes") else {
+            return Vec::new();
+        };
+        let mut paths: Vec<_> = entries.filter(|path| path.extension().is_some_and(|ext| ext == "toml")).collect();
+        paths.sort();
+        let mut themes: Vec<ThemeFile> = Vec::new();
+        for path in paths {
+            let mut data = String::new();
+            let read = ctx.fs.open(&path).map_err(|e| e.to_string()).and_then(|mut file| {
+                file.read_to_string(&mut data).map_err(|e| e.to_string())
+            });
+            let theme = read.and_then(|_| toml::from_str::<ThemeFile>(&data).map_err(|e| e.to_string())).and_then(|theme| {
+                let taken = THEMES.iter().any(|(name, _)| *name == theme.name) || themes.iter().any(|t| t.name == theme.name);
+                if taken {
+                    Err(format!("there is already a theme called {}", theme.name))
+                } else {
+                    Ok(theme)
+                }
+            });
+            match theme {
+                Ok(theme) => themes.push(theme),
+                Err(e) => eprintln!("failed to load theme {}: {}", path.display(), e),
+            }
+        }
+        themes
+    }
+
+    pub fn palette(&self) -> [Color; 2] {
+        self.colors.map(|[r, g, b]| Color::new(r, g, b, 1.0))
+    }
+
+    pub fn background(&self) -> Option<Color> {
+        self.background.map(|[r, g, b]| Color::new(r, g, b, 1.0))
+    }
+}
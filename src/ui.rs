@@ -0,0 +1,200 @@
+use ggez::graphics::{Canvas, Color, DrawParam, Quad, Rect};
+use ggez::input::keyboard::KeyCode;
+
+use crate::text::{Align, Style, TextCache};
+use crate::{PINK, SCREEN_WIDTH};
+
+const BUTTON_WIDTH: f32 = 220.0;
+const BUTTON_HEIGHT: f32 = 36.0;
+const BUTTON_SPACING: f32 = 46.0;
+
+pub struct Menu {
+    items: Vec<String>,
+    selected: usize,
+    top: f32,
+    button_height: f32,
+    spacing: f32,
+    text_scale: f32,
+}
+
+impl Menu {
+    pub fn new(items: &[&str], top: f32) -> Self {
+        Menu {
+            items: items.iter().map(|s| s.to_string()).collect(),
+            selected: 0,
+            top,
+            button_height: BUTTON_HEIGHT,
+            spacing: BUTTON_SPACING,
+            text_scale: 20.0,
+        }
+    }
+
+    // Smaller buttons for long option lists.
+    pub fn compact(mut self) -> Self {
+        self.button_height = 24.0;
+        self.spacing = 28.0;
+        self.text_scale = 16.0;
+        self
+    }
+
+    pub fn set_label(&mut self, index: usize, label: &str) {
+        self.items[index] = label.to_string();
+    }
+
+    fn button_rect(&self, index: usize) -> Rect {
+        Rect::new(
+            (SCREEN_WIDTH - BUTTON_WIDTH) / 2.0,
+            self.top + index as f32 * self.spacing,
+            BUTTON_WIDTH,
+            self.button_height,
+        )
+    }
+
+    pub fn key_down(&mut self, keycode: KeyCode) -> Option<usize> {
+        match keycode {
+            KeyCode::Up => {
+                self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+                None
+            }
+            KeyCode::Down => {
+                self.selected = (self.selected + 1) % self.items.len();
+                None
+            }
+            KeyCode::Return | KeyCode::Space => Some(self.selected),
+            _ => None,
+        }
+    }
+
+    pub fn mouse_down(&mut self, x: f32, y: f32) -> Option<usize> {
+        let index = (0..self.items.len()).find(|&i| self.button_rect(i).contains([x, y]))?;
+        self.selected = index;
+        Some(index)
+    }
+
+    pub fn mouse_motion(&mut self, x: f32, y: f32) {
+        if let Some(index) = (0..self.items.len()).find(|&i| self.button_rect(i).contains([x, y])) {
+            self.selected = index;
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, text: &TextCache) {
+        for (i, item) in self.items.iter().enumerate() {
+            let rect = self.button_rect(i);
+            let (fill, text_color) = if i == self.selected {
+                (PINK, Color::BLACK)
+            } else {
+                (Color::new(0.2, 0.2, 0.2, 1.0), Color::WHITE)
+            };
+            fill_rect(canvas, rect, fill);
+            let style = Style::new(self.text_scale, text_color, Align::Center);
+            text.draw(canvas, item, [rect.x + rect.w / 2.0, rect.y + rect.h / 2.0], style);
+        }
+    }
+}
+
+// Solid rectangles all reuse ggez's built-in unit quad, stretched and tinted per draw,
+// instead of building a mesh each time.
+pub fn fill_rect(canvas: &mut Canvas, rect: Rect, color: Color) {
+    canvas.draw(&Quad, DrawParam::default().dest_rect(rect).color(color));
+}
+
+pub struct ScrollList {
+    pub selected: usize,
+    offset: usize,
+    len: usize,
+    top: f32,
+    row_height: f32,
+    rows: usize,
+}
+
+impl ScrollList {
+    pub fn new(top: f32, row_height: f32, rows: usize) -> Self {
+        ScrollList {
+            selected: 0,
+            offset: 0,
+            len: 0,
+            top,
+            row_height,
+            rows,
+        }
+    }
+
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len;
+        self.selected = self.selected.min(len.saturating_sub(1));
+        self.scroll_to_selected();
+    }
+
+    fn scroll_to_selected(&mut self) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.rows {
+            self.offset = self.selected + 1 - self.rows;
+        }
+    }
+
+    fn row_rect(&self, row: usize) -> Rect {
+        Rect::new(10.0, self.top + row as f32 * self.row_height, SCREEN_WIDTH - 20.0, self.row_height - 4.0)
+    }
+
+    pub fn key_down(&mut self, keycode: KeyCode) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        match keycode {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.len - 1),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(self.rows),
+            KeyCode::PageDown => self.selected = (self.selected + self.rows).min(self.len - 1),
+            KeyCode::Return | KeyCode::Space => return Some(self.selected),
+            _ => {}
+        }
+        self.scroll_to_selected();
+        None
+    }
+
+    // First click selects a row, clicking the selected row again activates it.
+    pub fn mouse_down(&mut self, x: f32, y: f32) -> Option<usize> {
+        let row = (0..self.rows).find(|&row| self.row_rect(row).contains([x, y]))?;
+        let index = self.offset + row;
+        if index >= self.len {
+            return None;
+        }
+        if index == self.selected {
+            Some(index)
+        } else {
+            self.selected = index;
+            None
+        }
+    }
+
+    pub fn scroll(&mut self, dy: f32) {
+        let max_offset = self.len.saturating_sub(self.rows);
+        if dy > 0.0 {
+            self.offset = self.offset.saturating_sub(1);
+        } else if dy < 0.0 {
+            self.offset = (self.offset + 1).min(max_offset);
+        }
+        self.selected = self.selected.clamp(self.offset, (self.offset + self.rows).min(self.len).saturating_sub(1));
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas, text: &TextCache, labels: &[String]) {
+        self.draw_rows(canvas, text, labels, &[]);
+    }
+
+    // Rows flagged in `disabled` are drawn greyed out; missing flags count as enabled.
+    pub fn draw_rows(&self, canvas: &mut Canvas, text: &TextCache, labels: &[String], disabled: &[bool]) {
+        for (row, label) in labels.iter().enumerate().skip(self.offset).take(self.rows) {
+            let rect = self.row_rect(row - self.offset);
+            let grey = Color::new(0.5, 0.5, 0.5, 1.0);
+            let (fill, text_color) = match (row == self.selected, disabled.get(row).copied().unwrap_or(false)) {
+                (true, false) => (PINK, Color::BLACK),
+                (true, true) => (grey, Color::BLACK),
+                (false, false) => (Color::new(0.15, 0.15, 0.15, 1.0), Color::WHITE),
+                (false, true) => (Color::new(0.1, 0.1, 0.1, 1.0), grey),
+            };
+            fill_rect(canvas, rect, fill);
+            text.draw(canvas, label, [rect.x + 6.0, rect.y + 4.0], Style::new(14.0, text_color, Align::Left));
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use ggez::graphics::Rect;
+
+use crate::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Where the game sits in the window. Everything is drawn in logical coordinates, a
+// SCREEN_WIDTH x SCREEN_HEIGHT view, which is scaled as large as fits without stretching and
+// centered; whatever the window has left over on the sides or at the top and bottom shows the
+// background.
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    // Window pixels per logical pixel.
+    scale: f32,
+    // Window pixels from the window's top-left corner to the view's.
+    offset: [f32; 2],
+    window: [f32; 2],
+}
+
+impl Viewport {
+    // The view letterboxed into a `width` x `height` window.
+    pub fn fit(width: f32, height: f32) -> Self {
+        let scale = (width / SCREEN_WIDTH).min(height / SCREEN_HEIGHT).max(f32::EPSILON);
+        Viewport {
+            scale,
+            offset: [(width - SCREEN_WIDTH * scale) / 2.0, (height - SCREEN_HEIGHT * scale) / 2.0],
+            window: [width, height],
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    // The whole window in logical coordinates, for `Canvas::set_screen_coordinates`.
+    pub fn screen(&self) -> Rect {
+        Rect::new(
+            -self.offset[0] / self.scale,
+            -self.offset[1] / self.scale,
+            self.window[0] / self.scale,
+            self.window[1] / self.scale,
+        )
+    }
+
+    // A point in window pixels, such as the mouse, in logical coordinates.
+    pub fn to_logical(self, x: f32, y: f32) -> (f32, f32) {
+        ((x - self.offset[0]) / self.scale, (y - self.offset[1]) / self.scale)
+    }
+}